@@ -217,10 +217,16 @@
 //!
 //! Event data returned by the `listen` and `grab` functions can be serialized and de-serialized with
 //! Serde if you install this library with the `serialize` feature.
+//!
+//! # Replaying events
+//!
+//! The `replay` module plays recorded events back through `simulate` on a
+//! background thread, with the original or a scaled timing.
 mod rdev;
 pub use crate::rdev::{
-    Button, DisplayError, Event, EventType, GrabCallback, GrabError, Key, KeyCode, KeyboardState,
-    ListenError, RawKey, SimulateError,
+    ActiveWindow, Button, DisplayError, Event, EventType, GesturePhase, GrabCallback, GrabError,
+    Key, KeyCode, KeyStateError, KeyboardState, ListenError, LockState, PenState, RawKey,
+    SimulateError, TouchPhase, TypeTextOptions,
 };
 
 mod keycodes;
@@ -232,6 +238,9 @@ mod macos;
 mod windows;
 
 mod codes_conv;
+#[cfg(any(target_os = "linux", target_os = "windows"))]
+mod repeat;
+pub mod replay;
 
 pub use crate::codes_conv::*;
 
@@ -268,7 +277,10 @@ pub use core_graphics::{event::CGEventTapLocation, event_source::CGEventSourceSt
 #[cfg(any(target_os = "android", target_os = "linux"))]
 pub use crate::keycodes::linux::{code_from_key, key_from_code};
 #[cfg(target_os = "linux")]
-use crate::linux::{display_size as _display_size, listen as _listen, simulate as _simulate};
+use crate::linux::{
+    display_size as _display_size, listen as _listen, lock_state as _lock_state,
+    pressed_keys as _pressed_keys, simulate as _simulate, type_text as _type_text,
+};
 #[cfg(target_os = "linux")]
 pub use crate::linux::{simulate_char, simulate_unicode, Keyboard};
 
@@ -276,9 +288,10 @@ pub use crate::linux::{simulate_char, simulate_unicode, Keyboard};
 pub use crate::keycodes::windows::key_from_scancode;
 #[cfg(target_os = "windows")]
 pub use crate::windows::{
-    display_size as _display_size, get_modifier, listen as _listen, set_modifier,
-    simulate as _simulate, simulate_char, simulate_code, simulate_key_unicode, simulate_unicode,
-    simulate_unistr, vk_to_scancode, Keyboard,
+    display_size as _display_size, get_modifier, listen as _listen, lock_state as _lock_state,
+    pressed_keys as _pressed_keys, set_modifier, simulate as _simulate, simulate_char,
+    simulate_code, simulate_key_unicode, simulate_unicode, simulate_unistr, vk_to_scancode,
+    Keyboard,
 };
 
 pub use crate::rdev::UnicodeInfo;
@@ -346,9 +359,34 @@ where
 /// ```
 #[cfg(not(any(target_os = "android", target_os = "ios")))]
 pub fn simulate(event_type: &EventType) -> Result<(), SimulateError> {
+    // notifications like `LockStateChanged` fail the same everywhere
+    if !event_type.can_simulate() {
+        return Err(SimulateError);
+    }
     _simulate(event_type)
 }
 
+/// Types a string with simulated key presses. Each character is resolved
+/// against the active keyboard layout, including the modifiers it needs.
+/// Characters the layout can't produce are typed through a temporarily
+/// remapped spare keycode. Caps Lock is unlocked while typing and locked
+/// again afterwards, keys the user holds meanwhile are not compensated.
+///
+/// ```no_run
+/// use rdev::{type_text, TypeTextOptions};
+/// use std::time::Duration;
+///
+/// let options = TypeTextOptions {
+///     delay: Duration::from_millis(80),
+///     ..Default::default()
+/// };
+/// type_text("Hello, wörld! €", &options).unwrap();
+/// ```
+#[cfg(target_os = "linux")]
+pub fn type_text(text: &str, options: &TypeTextOptions) -> Result<(), SimulateError> {
+    _type_text(text, options)
+}
+
 /// Returns the size in pixels of the main screen.
 /// This is useful to use with x, y from MouseMove Event.
 ///
@@ -363,6 +401,71 @@ pub fn display_size() -> Result<(u64, u64), DisplayError> {
     _display_size()
 }
 
+/// Returns the keys that are physically held down right now, as reported by
+/// the OS. Useful to recover when a release event was never delivered
+/// (focus change, screen lock, another client grabbing the keyboard).
+///
+/// ```no_run
+/// use rdev::pressed_keys;
+///
+/// let keys = pressed_keys().unwrap();
+/// println!("Currently held: {:?}", keys);
+/// ```
+#[cfg(any(target_os = "linux", target_os = "windows"))]
+pub fn pressed_keys() -> Result<Vec<Key>, KeyStateError> {
+    _pressed_keys()
+}
+
+/// Returns whether Caps Lock, Num Lock and Scroll Lock are currently on.
+///
+/// ```no_run
+/// use rdev::lock_state;
+///
+/// let state = lock_state().unwrap();
+/// println!("Caps Lock is {}", if state.caps_lock { "on" } else { "off" });
+/// ```
+#[cfg(any(target_os = "linux", target_os = "windows"))]
+pub fn lock_state() -> Result<LockState, KeyStateError> {
+    _lock_state()
+}
+
+/// Polls the key state like [`pressed_keys`] and [`lock_state`], reusing
+/// one display connection on Linux instead of opening one per call.
+///
+/// ```no_run
+/// use rdev::KeyStateReader;
+///
+/// let reader = KeyStateReader::new().unwrap();
+/// println!("Currently held: {:?}", reader.pressed_keys());
+/// ```
+#[cfg(target_os = "linux")]
+pub use crate::linux::KeyStateReader;
+#[cfg(target_os = "windows")]
+pub use crate::windows::KeyStateReader;
+
+/// Display name of the layout a [`EventType::LayoutChanged`] event reports.
+///
+/// ```no_run
+/// use rdev::{layout_name, listen, EventType};
+///
+/// listen(|event| {
+///     if let EventType::LayoutChanged { layout } = event.event_type {
+///         println!("Switched to {:?}", layout_name(layout));
+///     }
+/// });
+/// ```
+pub fn layout_name(layout: usize) -> Option<String> {
+    #[cfg(target_os = "linux")]
+    return crate::linux::layout_name(layout);
+    #[cfg(not(target_os = "linux"))]
+    {
+        let _ = layout;
+        None
+    }
+}
+
+#[cfg(target_os = "linux")]
+pub use crate::linux::{active_window, watch_active_window, watch_keyboard_grabs};
 #[cfg(target_os = "linux")]
 pub use crate::linux::{
     disable_grab, enable_grab, exit_grab_listen, is_grabbed, start_grab_listen,
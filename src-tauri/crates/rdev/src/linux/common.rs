@@ -1,17 +1,38 @@
 use crate::linux::keyboard::Keyboard;
 use crate::keycodes::linux::key_from_code;
-use crate::rdev::{Button, Event, EventType, KeyboardState};
+use crate::rdev::{Button, Event, EventType, Key, KeyboardState, LockState};
+use crate::repeat::KeyRepeatTracker;
 use std::convert::TryInto;
-use std::os::raw::{c_int, c_uchar, c_uint};
-use std::ptr::null;
+use std::os::raw::{c_char, c_int, c_uchar, c_uint};
+use std::ptr::{null, null_mut};
+use std::sync::Mutex;
 use std::time::SystemTime;
 use x11::xlib;
 
 pub const TRUE: c_int = 1;
 pub const FALSE: c_int = 0;
+// XKB constants not exported by the x11 crate.
+pub const XKB_USE_CORE_KBD: c_uint = 0x0100;
+pub const XKB_SUCCESS: c_int = 0;
 
 // A global for the callbacks.
 pub static mut KEYBOARD: Option<Keyboard> = None;
+// Keycodes currently held down, shared by listen and grab.
+pub static KEYS_DOWN: Mutex<KeyRepeatTracker> = Mutex::new(KeyRepeatTracker::new());
+
+/// Updates the held-down keycodes and returns whether this event is an
+/// auto-repeated press of a key that was never released.
+pub fn track_repeat(code: c_uint, is_press: bool) -> bool {
+    let Ok(mut keys_down) = KEYS_DOWN.lock() else {
+        return false;
+    };
+    if is_press {
+        keys_down.press(code)
+    } else {
+        keys_down.release(code);
+        false
+    }
+}
 
 pub fn convert_event(code: c_uchar, type_: c_int, x: f64, y: f64) -> Option<EventType> {
     match type_ {
@@ -53,6 +74,46 @@ pub fn convert_event(code: c_uchar, type_: c_int, x: f64, y: f64) -> Option<Even
     }
 }
 
+// Last lock state we reported, to only send actual changes.
+static LAST_LOCK_STATE: Mutex<Option<LockState>> = Mutex::new(None);
+
+thread_local! {
+    // opened once on the listener thread, the record callback can't block on
+    // connecting for every lock key
+    static LOCK_STATE_DISPLAY: Option<Display> = Display::new();
+}
+
+/// Checks the lock indicators after a lock key event and returns a
+/// `LockStateChanged` event if the lock state is different from before.
+pub fn lock_state_event(event_type: &EventType) -> Option<Event> {
+    match event_type {
+        EventType::KeyPress(Key::CapsLock | Key::NumLock | Key::ScrollLock)
+        | EventType::KeyRelease(Key::CapsLock | Key::NumLock | Key::ScrollLock) => {}
+        _ => return None,
+    }
+    let lock_state =
+        LOCK_STATE_DISPLAY.with(|display| display.as_ref().map(Display::get_lock_state))?;
+    let mut last = LAST_LOCK_STATE.lock().ok()?;
+    if *last == Some(lock_state) {
+        return None;
+    }
+    *last = Some(lock_state);
+    Some(synthetic_event(EventType::LockStateChanged(lock_state)))
+}
+
+/// Wraps an event type that isn't tied to a key or button of its own.
+pub fn synthetic_event(event_type: EventType) -> Event {
+    Event {
+        event_type,
+        time: SystemTime::now(),
+        unicode: None,
+        platform_code: 0,
+        position_code: 0,
+        usb_hid: 0,
+        is_repeat: false,
+    }
+}
+
 pub fn convert(
     keyboard: &mut Option<Keyboard>,
     code: c_uint,
@@ -63,6 +124,11 @@ pub fn convert(
     let event_type = convert_event(code as c_uchar, type_, x, y)?;
     let kb: &mut Keyboard = (*keyboard).as_mut()?;
     let unicode = kb.add(&event_type);
+    let is_repeat = match event_type {
+        EventType::KeyPress(_) => track_repeat(code, true),
+        EventType::KeyRelease(_) => track_repeat(code, false),
+        _ => false,
+    };
     Some(Event {
         event_type,
         time: SystemTime::now(),
@@ -70,6 +136,7 @@ pub fn convert(
         platform_code: code as _,
         position_code: code as _,
         usb_hid: 0,
+        is_repeat,
     })
 }
 
@@ -102,6 +169,51 @@ impl Display {
         }
     }
 
+    /// Keycodes of all keys that are physically held down right now.
+    pub fn get_pressed_keycodes(&self) -> Vec<c_uint> {
+        let mut keymap = [0 as c_char; 32];
+        unsafe {
+            xlib::XQueryKeymap(self.display, keymap.as_mut_ptr());
+        }
+        let mut keycodes = vec![];
+        for (byte_index, byte) in keymap.iter().enumerate() {
+            for bit in 0..8 {
+                if (*byte as u8) & (1 << bit) != 0 {
+                    keycodes.push((byte_index * 8 + bit) as c_uint);
+                }
+            }
+        }
+        keycodes
+    }
+
+    pub fn get_lock_state(&self) -> LockState {
+        LockState {
+            caps_lock: self.get_named_indicator(b"Caps Lock\0"),
+            num_lock: self.get_named_indicator(b"Num Lock\0"),
+            scroll_lock: self.get_named_indicator(b"Scroll Lock\0"),
+        }
+    }
+
+    /// Looks up an XKB indicator by name, `name` must be nul terminated.
+    fn get_named_indicator(&self, name: &[u8]) -> bool {
+        unsafe {
+            let atom = xlib::XInternAtom(self.display, name.as_ptr() as *const c_char, TRUE);
+            if atom == 0 {
+                return false;
+            }
+            let mut state = FALSE;
+            let found = xlib::XkbGetNamedIndicator(
+                self.display,
+                atom,
+                null_mut(),
+                &mut state,
+                null_mut(),
+                null_mut(),
+            );
+            found != FALSE && state != FALSE
+        }
+    }
+
     #[allow(dead_code)]
     pub fn get_mouse_pos(&self) -> Option<(u64, u64)> {
         unsafe {
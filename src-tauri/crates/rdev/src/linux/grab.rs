@@ -16,7 +16,7 @@ use std::{
 };
 use x11::xlib::{self, GrabModeAsync, KeyPressMask, KeyReleaseMask, Window};
 
-use super::common::KEYBOARD;
+use super::common::{track_repeat, KEYBOARD};
 
 enum GrabEvent {
     Exit,
@@ -79,6 +79,9 @@ impl KeyboardGrabber {
                 grabber.window,
                 KeyPressMask | KeyReleaseMask,
             );
+            // Report auto-repeat as consecutive presses instead of
+            // release/press pairs, so repeats can be told apart.
+            xlib::XkbSetDetectableAutoRepeat(grabber.display, c_int::from(true), ptr::null_mut());
         }
 
         grabber.grab_fd = unsafe { xlib::XConnectionNumber(grabber.display) };
@@ -154,6 +157,7 @@ fn convert_event(code: u32, is_press: bool) -> Event {
         platform_code,
         position_code: code as _,
         usb_hid: 0,
+        is_repeat: track_repeat(code, is_press),
     }
 }
 
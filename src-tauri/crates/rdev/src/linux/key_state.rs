@@ -0,0 +1,36 @@
+use crate::keycodes::linux::key_from_code;
+use crate::linux::common::{Display, KEYS_DOWN};
+use crate::rdev::{Key, KeyStateError, LockState};
+
+/// Queries the key state over a connection of its own, kept open to poll
+/// without reconnecting every time.
+pub struct KeyStateReader {
+    display: Display,
+}
+
+impl KeyStateReader {
+    pub fn new() -> Result<KeyStateReader, KeyStateError> {
+        let display = Display::new().ok_or(KeyStateError::MissingDisplayError)?;
+        Ok(KeyStateReader { display })
+    }
+
+    pub fn pressed_keys(&self) -> Vec<Key> {
+        let keycodes = self.display.get_pressed_keycodes();
+        if let Ok(mut keys_down) = KEYS_DOWN.lock() {
+            keys_down.sync(&keycodes);
+        }
+        keycodes.into_iter().map(key_from_code).collect()
+    }
+
+    pub fn lock_state(&self) -> LockState {
+        self.display.get_lock_state()
+    }
+}
+
+pub fn pressed_keys() -> Result<Vec<Key>, KeyStateError> {
+    Ok(KeyStateReader::new()?.pressed_keys())
+}
+
+pub fn lock_state() -> Result<LockState, KeyStateError> {
+    Ok(KeyStateReader::new()?.lock_state())
+}
@@ -0,0 +1,158 @@
+use crate::linux::common::{TRUE, XKB_SUCCESS, XKB_USE_CORE_KBD};
+use std::ffi::CStr;
+use std::mem::zeroed;
+use std::os::raw::{c_int, c_uint, c_ulong};
+use std::ptr::null;
+use std::sync::{Mutex, Once, OnceLock};
+use std::thread;
+use x11::xlib;
+
+// Not exported by the x11 crate.
+const XKB_GROUP_NAMES_MASK: c_uint = 1 << 12;
+
+// Index in LAYOUT_NAMES of the layout the listen loop still has to report.
+static PENDING_LAYOUT: Mutex<Option<usize>> = Mutex::new(None);
+// Every layout name seen so far, `LayoutChanged` carries an index in here.
+static LAYOUT_NAMES: Mutex<Vec<String>> = Mutex::new(Vec::new());
+static START_WATCHER: Once = Once::new();
+// Read and write ends of the pipe waking the listen loop on a change.
+static WAKE_PIPE: OnceLock<[c_int; 2]> = OnceLock::new();
+
+/// Returns the layout switched to since the last call, if any.
+pub fn take_pending_layout() -> Option<usize> {
+    PENDING_LAYOUT.lock().ok()?.take()
+}
+
+/// Name of a layout reported by `LayoutChanged`.
+pub fn layout_name(layout: usize) -> Option<String> {
+    LAYOUT_NAMES.lock().ok()?.get(layout).cloned()
+}
+
+/// File descriptor that turns readable when a layout change is pending,
+/// for the listen loop to poll alongside its connections.
+pub fn layout_wake_fd() -> Option<c_int> {
+    WAKE_PIPE.get().map(|pipe| pipe[0])
+}
+
+/// Empties the wake pipe once the pending layout was taken.
+pub fn drain_layout_wake() {
+    let Some(fd) = layout_wake_fd() else {
+        return;
+    };
+    let mut buffer = [0u8; 64];
+    unsafe { while libc::read(fd, buffer.as_mut_ptr() as *mut _, buffer.len()) > 0 {} }
+}
+
+fn intern(name: String) -> Option<usize> {
+    let mut names = LAYOUT_NAMES.lock().ok()?;
+    match names.iter().position(|known| *known == name) {
+        Some(index) => Some(index),
+        None => {
+            names.push(name);
+            Some(names.len() - 1)
+        }
+    }
+}
+
+/// Watches XKB for group switches and keymap reloads on a dedicated display
+/// connection, the XRecord connection can't receive XKB events. Started once
+/// per process, however often `listen` runs.
+/// Changes are picked up by the listen loop through `take_pending_layout`,
+/// so the user callback is only ever called from the listen thread.
+pub fn start_layout_watcher() {
+    START_WATCHER.call_once(|| {
+        let mut pipe = [0; 2];
+        if unsafe { libc::pipe2(pipe.as_mut_ptr(), libc::O_NONBLOCK | libc::O_CLOEXEC) } == 0 {
+            let _ = WAKE_PIPE.set(pipe);
+        }
+        thread::spawn(watch_layout);
+    });
+}
+
+fn watch_layout() {
+    unsafe {
+        let display = xlib::XOpenDisplay(null());
+        if display.is_null() {
+            log::error!("Failed to open display for layout changes");
+            return;
+        }
+        let mut opcode = 0;
+        let mut event_base = 0;
+        let mut error_base = 0;
+        let mut major = 1;
+        let mut minor = 0;
+        if xlib::XkbQueryExtension(
+            display,
+            &mut opcode,
+            &mut event_base,
+            &mut error_base,
+            &mut major,
+            &mut minor,
+        ) == 0
+        {
+            log::error!("XKB extension is not available");
+            xlib::XCloseDisplay(display);
+            return;
+        }
+
+        xlib::XkbSelectEventDetails(
+            display,
+            XKB_USE_CORE_KBD,
+            xlib::XkbStateNotify as c_uint,
+            xlib::XkbGroupStateMask,
+            xlib::XkbGroupStateMask,
+        );
+        let keymap_events: c_ulong = xlib::XkbNewKeyboardNotifyMask | xlib::XkbMapNotifyMask;
+        xlib::XkbSelectEvents(display, XKB_USE_CORE_KBD, keymap_events, keymap_events);
+
+        let mut last_layout = active_layout_name(display);
+        let mut event: xlib::XEvent = zeroed();
+        loop {
+            xlib::XNextEvent(display, &mut event);
+            if event.get_type() != event_base {
+                continue;
+            }
+            let layout = active_layout_name(display);
+            if layout == last_layout {
+                continue;
+            }
+            let index = layout.clone().and_then(intern);
+            if let (Some(index), Ok(mut pending)) = (index, PENDING_LAYOUT.lock()) {
+                *pending = Some(index);
+                if let Some(pipe) = WAKE_PIPE.get() {
+                    libc::write(pipe[1], [1u8].as_ptr() as *const _, 1);
+                }
+            }
+            last_layout = layout;
+        }
+    }
+}
+
+/// Name of the active layout group, e.g. "English (US)".
+unsafe fn active_layout_name(display: *mut xlib::Display) -> Option<String> {
+    let mut state: xlib::XkbStateRec = zeroed();
+    if xlib::XkbGetState(display, XKB_USE_CORE_KBD, &mut state) != XKB_SUCCESS {
+        return None;
+    }
+    let desc = xlib::XkbAllocKeyboard();
+    if desc.is_null() {
+        return None;
+    }
+
+    let mut name = None;
+    if xlib::XkbGetNames(display, XKB_GROUP_NAMES_MASK, desc) == XKB_SUCCESS
+        && !(*desc).names.is_null()
+    {
+        let groups = &(*(*desc).names).groups;
+        let atom = groups[state.group as usize % groups.len()];
+        if atom != 0 {
+            let ptr = xlib::XGetAtomName(display, atom);
+            if !ptr.is_null() {
+                name = Some(CStr::from_ptr(ptr).to_string_lossy().into_owned());
+                xlib::XFree(ptr as *mut _);
+            }
+        }
+    }
+    xlib::XkbFreeKeyboard(desc, 0, TRUE);
+    name
+}
@@ -1,18 +1,33 @@
 extern crate libc;
 extern crate x11;
-use crate::linux::common::{convert, FALSE, KEYBOARD};
+use crate::linux::common::{
+    convert, lock_state_event, synthetic_event, FALSE, KEYBOARD, KEYS_DOWN, XKB_USE_CORE_KBD,
+};
 use crate::linux::keyboard::Keyboard;
-use crate::rdev::{Event, ListenError};
+use crate::linux::layout::{
+    drain_layout_wake, layout_wake_fd, start_layout_watcher, take_pending_layout,
+};
+use crate::linux::touchpad::HoldDetector;
+use crate::linux::xinput::{recorded_gesture, XInputListener};
+use crate::rdev::{Event, EventType, ListenError};
+use std::cell::Cell;
 use std::convert::TryInto;
 use std::ffi::CStr;
 use std::os::raw::{c_char, c_int, c_uchar, c_uint, c_ulong};
-use std::ptr::null;
+use std::ptr::{null, null_mut};
+use std::slice;
+use std::time::Duration;
 use x11::xlib;
 use x11::xrecord;
 
 static mut RECORD_ALL_CLIENTS: c_ulong = xrecord::XRecordAllClients;
 static mut GLOBAL_CALLBACK: Option<Box<dyn FnMut(Event)>> = None;
 
+thread_local! {
+    // XInput opcode for the record callback to pick out gesture events.
+    static XINPUT_OPCODE: Cell<Option<c_int>> = const { Cell::new(None) };
+}
+
 pub fn listen<T>(callback: T) -> Result<(), ListenError>
 where
     T: FnMut(Event) + 'static,
@@ -33,6 +48,16 @@ where
         if extension.is_null() {
             return Err(ListenError::XRecordExtensionError);
         }
+        // Recorded auto-repeat shows up as consecutive presses, `track_repeat`
+        // tells them from a press after a lost release by the repeat delay.
+        let (mut delay, mut interval) = (0, 0);
+        if xlib::XkbGetAutoRepeatRate(dpy_control, XKB_USE_CORE_KBD, &mut delay, &mut interval)
+            != FALSE
+        {
+            if let Ok(mut keys_down) = KEYS_DOWN.lock() {
+                keys_down.set_repeat_delay(Duration::from_millis(delay.into()));
+            }
+        }
 
         // Prepare record range
         let mut record_range: xrecord::XRecordRange = *xrecord::XRecordAllocRange();
@@ -42,6 +67,12 @@ where
         } else {
             xlib::MotionNotify
         } as c_uchar;
+        // Gestures go to a single client, recording the delivery sees them
+        // whichever one that is.
+        if !crate::keyboard_only() {
+            record_range.delivered_events.first = xlib::GenericEvent as c_uchar;
+            record_range.delivered_events.last = xlib::GenericEvent as c_uchar;
+        }
 
         // Create context
         let context = xrecord::XRecordCreateContext(
@@ -59,16 +90,90 @@ where
         }
 
         xlib::XSync(dpy_control, FALSE);
+        start_layout_watcher();
         // Run
-        let result =
-            xrecord::XRecordEnableContext(dpy_control, context, Some(record_callback), &mut 0);
+        let result = xrecord::XRecordEnableContextAsync(
+            dpy_control,
+            context,
+            Some(record_callback),
+            null_mut(),
+        );
         if result == 0 {
             return Err(ListenError::RecordContextEnablingError);
         }
+
+        // XRecord only sees core events, gestures come from XInput2 on the side.
+        // Both are dispatched from this thread so the callback never runs concurrently.
+        let mut xinput = XInputListener::new();
+        XINPUT_OPCODE.with(|opcode| opcode.set(xinput.as_ref().map(XInputListener::opcode)));
+        let mut holds = HoldDetector::new();
+        let mut fds = vec![libc::pollfd {
+            fd: xlib::XConnectionNumber(dpy_control),
+            events: libc::POLLIN,
+            revents: 0,
+        }];
+        if let Some(xinput) = &xinput {
+            fds.push(libc::pollfd {
+                fd: xinput.fd(),
+                events: libc::POLLIN,
+                revents: 0,
+            });
+        }
+        fds.extend(holds.fds().into_iter().map(|fd| libc::pollfd {
+            fd,
+            events: libc::POLLIN,
+            revents: 0,
+        }));
+        // Wakes the loop for layout changes made while no key is pressed.
+        if let Some(fd) = layout_wake_fd() {
+            fds.push(libc::pollfd {
+                fd,
+                events: libc::POLLIN,
+                revents: 0,
+            });
+        }
+        loop {
+            xrecord::XRecordProcessReplies(dpy_control);
+            drain_layout_wake();
+            if let Some(event) = layout_event() {
+                dispatch(event);
+            }
+            if let Some(xinput) = &mut xinput {
+                for event_type in xinput.pending_events() {
+                    dispatch(synthetic_event(event_type));
+                }
+            }
+            for event_type in holds.pending_events() {
+                dispatch(synthetic_event(event_type));
+            }
+            // holds begin after the fingers rested a while, without input
+            if libc::poll(fds.as_mut_ptr(), fds.len() as libc::nfds_t, holds.timeout()) < 0
+                && std::io::Error::last_os_error().kind() != std::io::ErrorKind::Interrupted
+            {
+                break;
+            }
+        }
     }
     Ok(())
 }
 
+/// The pending layout change, if any. The cached keymap is stale after a
+/// layout switch, so it's refreshed before the next key is looked up.
+unsafe fn layout_event() -> Option<Event> {
+    take_pending_layout().map(|layout| {
+        if let Some(keyboard) = Keyboard::new() {
+            KEYBOARD = Some(keyboard);
+        }
+        synthetic_event(EventType::LayoutChanged { layout })
+    })
+}
+
+unsafe fn dispatch(event: Event) {
+    if let Some(callback) = &mut GLOBAL_CALLBACK {
+        callback(event);
+    }
+}
+
 // No idea how to do that properly relevant doc lives here:
 // https://www.x.org/releases/X11R7.7/doc/libXtst/recordlib.html#Datum_Flags
 // https://docs.rs/xproto/1.1.5/xproto/struct._xEvent__bindgen_ty_1.html
@@ -108,6 +213,20 @@ unsafe extern "C" fn record_callback(
         return;
     };
 
+    if c_int::from(xdatum.type_ & 0x7f) == xlib::GenericEvent {
+        let data = slice::from_raw_parts(data.data, data.data_len as usize * 4);
+        let gesture = XINPUT_OPCODE
+            .with(Cell::get)
+            .and_then(|opcode| recorded_gesture(opcode, data));
+        if let Some(event_type) = gesture {
+            dispatch(synthetic_event(event_type));
+        }
+        xrecord::XRecordFreeData(raw_data);
+        return;
+    }
+
+    let layout_event = layout_event();
+
     let code: c_uint = xdatum.code.into();
     let type_: c_int = xdatum.type_.into();
     // let state = xdatum.state;
@@ -115,10 +234,12 @@ unsafe extern "C" fn record_callback(
     let x = xdatum.root_x as f64;
     let y = xdatum.root_y as f64;
 
-    if let Some(event) = convert(&mut KEYBOARD, code, type_, x, y) {
-        if let Some(callback) = &mut GLOBAL_CALLBACK {
-            callback(event);
-        }
+    let event = convert(&mut KEYBOARD, code, type_, x, y);
+    let lock_event = event
+        .as_ref()
+        .and_then(|event| lock_state_event(&event.event_type));
+    for event in layout_event.into_iter().chain(event).chain(lock_event) {
+        dispatch(event);
     }
     xrecord::XRecordFreeData(raw_data);
 }
@@ -4,14 +4,24 @@ extern crate x11;
 mod common;
 mod display;
 mod grab;
+mod key_state;
 mod keyboard;
+mod layout;
 mod listen;
 mod simulate;
+mod touchpad;
+mod type_text;
+mod window;
+mod xinput;
 
 pub use crate::linux::display::display_size;
 pub use crate::linux::grab::{
     disable_grab, enable_grab, exit_grab_listen, is_grabbed, start_grab_listen,
 };
+pub use crate::linux::key_state::{lock_state, pressed_keys, KeyStateReader};
 pub use crate::linux::keyboard::Keyboard;
+pub use crate::linux::layout::layout_name;
 pub use crate::linux::listen::listen;
 pub use crate::linux::simulate::{simulate, simulate_char, simulate_unicode};
+pub use crate::linux::type_text::type_text;
+pub use crate::linux::window::{active_window, watch_active_window, watch_keyboard_grabs};
@@ -75,6 +75,14 @@ unsafe fn send_native(event_type: &EventType, display: *mut xlib::Display) -> Op
             xtest::XTestFakeButtonEvent(display, code, TRUE, 0)
                 & xtest::XTestFakeButtonEvent(display, code, FALSE, 0)
         }
+        // notifications, gestures, touch and pen input, nothing to send
+        EventType::LockStateChanged(_)
+        | EventType::LayoutChanged { .. }
+        | EventType::GestureSwipe { .. }
+        | EventType::GesturePinch { .. }
+        | EventType::GestureHold { .. }
+        | EventType::Touch { .. }
+        | EventType::PenChanged(_) => return None,
     };
     if res == 0 {
         None
@@ -0,0 +1,344 @@
+use crate::rdev::{EventType, GesturePhase};
+use std::fs::{self, File};
+use std::mem::{size_of, zeroed};
+use std::os::raw::{c_int, c_ulong};
+use std::os::unix::fs::OpenOptionsExt;
+use std::os::unix::io::AsRawFd;
+use std::time::{Duration, Instant};
+
+// From linux/input-event-codes.h.
+const EV_SYN: u16 = 0x00;
+const EV_KEY: u16 = 0x01;
+const EV_ABS: u16 = 0x03;
+const SYN_REPORT: u16 = 0;
+const ABS_X: u16 = 0x00;
+const ABS_Y: u16 = 0x01;
+const BTN_TOUCH: u16 = 0x14a;
+const INPUT_PROP_DIRECT: usize = 0x01;
+// Tools a touchpad reports for the number of fingers on it.
+const FINGER_TOOLS: [(u16, u32); 5] = [
+    (0x145, 1), // BTN_TOOL_FINGER
+    (0x14d, 2), // BTN_TOOL_DOUBLETAP
+    (0x14e, 3), // BTN_TOOL_TRIPLETAP
+    (0x14f, 4), // BTN_TOOL_QUADTAP
+    (0x148, 5), // BTN_TOOL_QUINTTAP
+];
+
+/// How long fingers rest before it's a hold, libinput waits as long.
+const HOLD_DELAY: Duration = Duration::from_millis(180);
+/// Movement that turns resting fingers into a swipe or pinch.
+const HOLD_MOVE_MM: i32 = 1;
+/// One finger resting is how any pointer motion starts, only more count.
+const HOLD_MIN_FINGERS: u32 = 2;
+
+// _IOC(_IOC_READ, 'E', nr, size) from linux/input.h.
+const fn evdev_read_ioctl(nr: u32, size: usize) -> c_ulong {
+    ((2 << 30) | ((size as u32) << 16) | ((b'E' as u32) << 8) | nr) as c_ulong
+}
+
+fn bit_set(bits: &[u8], bit: usize) -> bool {
+    bits.get(bit / 8)
+        .is_some_and(|byte| byte & (1 << (bit % 8)) != 0)
+}
+
+struct Touchpad {
+    file: File,
+    // movement in device units that cancels a hold
+    threshold: i32,
+    // fingers as of the latest report, and as the tools currently say
+    fingers: u32,
+    tool_fingers: u32,
+    position: (i32, i32),
+    // where and when the fingers settled, `None` once they moved
+    rest: Option<((i32, i32), Instant)>,
+    holding: bool,
+}
+
+impl Touchpad {
+    /// Opens `path` if it's a touchpad that tells finger counts apart.
+    fn open(path: &std::path::Path) -> Option<Touchpad> {
+        let file = fs::OpenOptions::new()
+            .read(true)
+            .custom_flags(libc::O_NONBLOCK | libc::O_CLOEXEC)
+            .open(path)
+            .ok()?;
+        let fd = file.as_raw_fd();
+        unsafe {
+            let mut keys = [0u8; 0x300 / 8];
+            let mut props = [0u8; 4];
+            if libc::ioctl(
+                fd,
+                evdev_read_ioctl(0x20 + EV_KEY as u32, keys.len()) as _,
+                keys.as_mut_ptr(),
+            ) < 0
+                || libc::ioctl(
+                    fd,
+                    evdev_read_ioctl(0x09, props.len()) as _,
+                    props.as_mut_ptr(),
+                ) < 0
+            {
+                return None;
+            }
+            // touchscreens report fingers too, but are direct input devices
+            if !bit_set(&keys, BTN_TOUCH as usize)
+                || !bit_set(&keys, FINGER_TOOLS[1].0 as usize)
+                || bit_set(&props, INPUT_PROP_DIRECT)
+            {
+                return None;
+            }
+            let mut abs: libc::input_absinfo = zeroed();
+            let request = evdev_read_ioctl(0x40 + ABS_X as u32, size_of::<libc::input_absinfo>());
+            if libc::ioctl(fd, request as _, &mut abs) < 0 {
+                return None;
+            }
+            // resolution is in units per mm, some drivers leave it out
+            let threshold = if abs.resolution > 0 {
+                abs.resolution * HOLD_MOVE_MM
+            } else {
+                ((abs.maximum - abs.minimum) / 100).max(1)
+            };
+            Some(Touchpad {
+                file,
+                threshold,
+                fingers: 0,
+                tool_fingers: 0,
+                position: (0, 0),
+                rest: None,
+                holding: false,
+            })
+        }
+    }
+
+    /// Reads what's queued, `None` once the device is gone.
+    fn read(&mut self, now: Instant, events: &mut Vec<EventType>) -> Option<()> {
+        let mut buffer: [libc::input_event; 64] = unsafe { zeroed() };
+        loop {
+            let read = unsafe {
+                libc::read(
+                    self.file.as_raw_fd(),
+                    buffer.as_mut_ptr() as *mut _,
+                    size_of::<[libc::input_event; 64]>(),
+                )
+            };
+            if read < 0 {
+                let error = std::io::Error::last_os_error();
+                return (error.kind() == std::io::ErrorKind::WouldBlock).then_some(());
+            }
+            let count = read as usize / size_of::<libc::input_event>();
+            if count == 0 {
+                return None;
+            }
+            for input in &buffer[..count] {
+                self.handle(input, now, events);
+            }
+        }
+    }
+
+    fn handle(&mut self, input: &libc::input_event, now: Instant, events: &mut Vec<EventType>) {
+        match (input.type_, input.code) {
+            (EV_KEY, code) => {
+                if let Some((_, fingers)) = FINGER_TOOLS.iter().find(|(tool, _)| *tool == code) {
+                    if input.value != 0 {
+                        self.tool_fingers = *fingers;
+                    } else if self.tool_fingers == *fingers {
+                        self.tool_fingers = 0;
+                    }
+                }
+            }
+            (EV_ABS, ABS_X) => self.position.0 = input.value,
+            (EV_ABS, ABS_Y) => self.position.1 = input.value,
+            (EV_SYN, SYN_REPORT) => self.report(now, events),
+            _ => {}
+        }
+    }
+
+    /// Compares a complete report to the hold in progress.
+    fn report(&mut self, now: Instant, events: &mut Vec<EventType>) {
+        if self.tool_fingers != self.fingers {
+            self.end(GesturePhase::End, events);
+            self.fingers = self.tool_fingers;
+            self.rest = (self.fingers >= HOLD_MIN_FINGERS).then_some((self.position, now));
+            return;
+        }
+        let Some(((x, y), _)) = self.rest else {
+            return;
+        };
+        let moved = (self.position.0 - x).abs().max((self.position.1 - y).abs());
+        if moved > self.threshold {
+            self.end(GesturePhase::Cancel, events);
+            self.rest = None;
+        }
+    }
+
+    fn end(&mut self, phase: GesturePhase, events: &mut Vec<EventType>) {
+        if self.holding {
+            self.holding = false;
+            events.push(EventType::GestureHold {
+                phase,
+                fingers: self.fingers,
+            });
+        }
+    }
+
+    /// Begins the hold once the fingers rested long enough.
+    fn check_rest(&mut self, now: Instant, events: &mut Vec<EventType>) {
+        if let Some((_, since)) = self.rest {
+            if !self.holding && now >= since + HOLD_DELAY {
+                self.holding = true;
+                events.push(EventType::GestureHold {
+                    phase: GesturePhase::Begin,
+                    fingers: self.fingers,
+                });
+            }
+        }
+    }
+
+    fn hold_due(&self) -> Option<Instant> {
+        match self.rest {
+            Some((_, since)) if !self.holding => Some(since + HOLD_DELAY),
+            _ => None,
+        }
+    }
+}
+
+/// Detects hold gestures, fingers resting on a touchpad, from the touchpads'
+/// evdev devices the way libinput does, XInput doesn't forward them.
+/// Needs read access to `/dev/input`, usually by being in the `input` group,
+/// touchpads that can't be opened are skipped. Devices are looked up once,
+/// touchpads plugged in later are not watched.
+pub struct HoldDetector {
+    touchpads: Vec<Touchpad>,
+}
+
+impl HoldDetector {
+    pub fn new() -> HoldDetector {
+        let mut paths: Vec<_> = fs::read_dir("/dev/input")
+            .into_iter()
+            .flatten()
+            .flatten()
+            .map(|entry| entry.path())
+            .filter(|path| {
+                path.file_name()
+                    .and_then(|name| name.to_str())
+                    .is_some_and(|name| name.starts_with("event"))
+            })
+            .collect();
+        paths.sort();
+        let touchpads: Vec<_> = paths
+            .iter()
+            .filter_map(|path| Touchpad::open(path))
+            .collect();
+        log::debug!("Watching {} touchpads for hold gestures", touchpads.len());
+        HoldDetector { touchpads }
+    }
+
+    /// File descriptors to poll for new touchpad input.
+    pub fn fds(&self) -> Vec<c_int> {
+        self.touchpads
+            .iter()
+            .map(|touchpad| touchpad.file.as_raw_fd())
+            .collect()
+    }
+
+    /// Milliseconds until a hold is due to begin, -1 when none is.
+    pub fn timeout(&self) -> c_int {
+        let now = Instant::now();
+        self.touchpads
+            .iter()
+            .filter_map(Touchpad::hold_due)
+            .min()
+            .map_or(-1, |due| {
+                // round up, waking early would only poll again
+                let wait = due.saturating_duration_since(now);
+                wait.as_micros().div_ceil(1000).min(c_int::MAX as u128) as c_int
+            })
+    }
+
+    /// Reads all queued touchpad input without blocking.
+    pub fn pending_events(&mut self) -> Vec<EventType> {
+        let now = Instant::now();
+        let mut events = vec![];
+        self.touchpads.retain_mut(|touchpad| {
+            let present = touchpad.read(now, &mut events).is_some();
+            touchpad.check_rest(now, &mut events);
+            present
+        });
+        events
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn touchpad() -> Touchpad {
+        Touchpad {
+            file: File::open("/dev/null").unwrap(),
+            threshold: 10,
+            fingers: 0,
+            tool_fingers: 0,
+            position: (0, 0),
+            rest: None,
+            holding: false,
+        }
+    }
+
+    fn input(type_: u16, code: u16, value: i32) -> libc::input_event {
+        let mut input: libc::input_event = unsafe { zeroed() };
+        input.type_ = type_;
+        input.code = code;
+        input.value = value;
+        input
+    }
+
+    fn frame(touchpad: &mut Touchpad, inputs: &[(u16, u16, i32)], now: Instant) -> Vec<EventType> {
+        let mut events = vec![];
+        for &(type_, code, value) in inputs {
+            touchpad.handle(&input(type_, code, value), now, &mut events);
+        }
+        touchpad.handle(&input(EV_SYN, SYN_REPORT, 0), now, &mut events);
+        touchpad.check_rest(now, &mut events);
+        events
+    }
+
+    fn hold(phase: GesturePhase, fingers: u32) -> EventType {
+        EventType::GestureHold { phase, fingers }
+    }
+
+    #[test]
+    fn resting_fingers_hold_until_lifted_or_moved() {
+        let mut touchpad = touchpad();
+        let start = Instant::now();
+        let at = |ms| start + Duration::from_millis(ms);
+        let two = FINGER_TOOLS[1].0;
+
+        assert!(frame(&mut touchpad, &[(EV_KEY, two, 1)], at(0)).is_empty());
+        assert_eq!(touchpad.hold_due(), Some(at(180)));
+        assert_eq!(
+            frame(&mut touchpad, &[(EV_ABS, ABS_X, 5)], at(200)),
+            [hold(GesturePhase::Begin, 2)]
+        );
+        assert_eq!(
+            frame(&mut touchpad, &[(EV_KEY, two, 0)], at(300)),
+            [hold(GesturePhase::End, 2)]
+        );
+
+        frame(&mut touchpad, &[(EV_KEY, two, 1)], at(400));
+        frame(&mut touchpad, &[], at(600));
+        assert_eq!(
+            frame(&mut touchpad, &[(EV_ABS, ABS_Y, 20)], at(650)),
+            [hold(GesturePhase::Cancel, 2)]
+        );
+        // moving fingers don't hold again
+        assert!(frame(&mut touchpad, &[], at(2_000)).is_empty());
+        assert_eq!(touchpad.hold_due(), None);
+    }
+
+    #[test]
+    fn single_fingers_never_hold() {
+        let mut touchpad = touchpad();
+        let start = Instant::now();
+        frame(&mut touchpad, &[(EV_KEY, FINGER_TOOLS[0].0, 1)], start);
+        assert!(frame(&mut touchpad, &[], start + Duration::from_secs(1)).is_empty());
+    }
+}
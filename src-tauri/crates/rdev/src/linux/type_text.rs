@@ -0,0 +1,362 @@
+use crate::linux::common::{FALSE, TRUE, XKB_SUCCESS, XKB_USE_CORE_KBD};
+use crate::linux::keyboard::Keyboard;
+use crate::rdev::{SimulateError, TypeTextOptions};
+use std::collections::HashMap;
+use std::mem::zeroed;
+use std::os::raw::{c_int, c_uchar, c_uint, c_ulong, c_ushort};
+use std::ptr::null;
+use std::slice;
+use std::thread;
+use std::time::Duration;
+use x11::xlib;
+use x11::{keysym, xtest};
+
+// Not exported by the x11 crate.
+const XKB_KEY_TYPES_MASK: c_uint = 1 << 0;
+const XKB_KEY_SYMS_MASK: c_uint = 1 << 1;
+const XKB_NUM_KBD_GROUPS: usize = 4;
+const NO_SYMBOL: c_ulong = 0;
+
+// Clients re-read the keymap after a MappingNotify, give them a moment
+// before pressing a remapped key and before restoring it.
+const REMAP_SETTLE: Duration = Duration::from_millis(50);
+
+// The x11 crate leaves the client map opaque, see XKBstr.h.
+#[repr(C)]
+struct XkbModsRec {
+    mask: c_uchar,
+    real_mods: c_uchar,
+    vmods: c_ushort,
+}
+
+#[repr(C)]
+struct XkbKTMapEntryRec {
+    active: xlib::Bool,
+    level: c_uchar,
+    mods: XkbModsRec,
+}
+
+#[allow(dead_code)]
+#[repr(C)]
+struct XkbKeyTypeRec {
+    mods: XkbModsRec,
+    num_levels: c_uchar,
+    map_count: c_uchar,
+    map: *mut XkbKTMapEntryRec,
+    preserve: *mut XkbModsRec,
+    name: xlib::Atom,
+    level_names: *mut xlib::Atom,
+}
+
+#[allow(dead_code)]
+#[repr(C)]
+struct XkbSymMapRec {
+    kt_index: [c_uchar; XKB_NUM_KBD_GROUPS],
+    group_info: c_uchar,
+    width: c_uchar,
+    offset: c_ushort,
+}
+
+#[allow(dead_code)]
+#[repr(C)]
+struct XkbClientMapRec {
+    size_types: c_uchar,
+    num_types: c_uchar,
+    types: *mut XkbKeyTypeRec,
+    size_syms: c_ushort,
+    num_syms: c_ushort,
+    syms: *mut xlib::KeySym,
+    key_sym_map: *mut XkbSymMapRec,
+    modmap: *mut c_uchar,
+}
+
+/// A key and the modifiers to hold while pressing it.
+#[derive(Debug, Copy, Clone)]
+struct KeyStroke {
+    keycode: c_uint,
+    mods: c_uint,
+}
+
+pub fn type_text(text: &str, options: &TypeTextOptions) -> Result<(), SimulateError> {
+    unsafe {
+        let display = xlib::XOpenDisplay(null());
+        if display.is_null() {
+            return Err(SimulateError);
+        }
+        let result = type_text_on(display, text, options);
+        xlib::XCloseDisplay(display);
+        result
+    }
+}
+
+unsafe fn type_text_on(
+    display: *mut xlib::Display,
+    text: &str,
+    options: &TypeTextOptions,
+) -> Result<(), SimulateError> {
+    let mut keyboard = Keyboard::new().ok_or(SimulateError)?;
+    let strokes = resolve_keymap(display, &mut keyboard).ok_or(SimulateError)?;
+    let modifier_keys = modifier_keycodes(display);
+    let spare = spare_keycode(display);
+    let mut remapped = false;
+    // Caps Lock would flip the case of every letter, and of remapped ones
+    // too, so it's unlocked while typing and locked again afterwards.
+    let caps_locked = caps_locked(display);
+    if caps_locked {
+        xlib::XkbLockModifiers(display, XKB_USE_CORE_KBD, xlib::LockMask, 0);
+        xlib::XSync(display, FALSE);
+    }
+
+    let mut result = Ok(());
+    for chr in text.chars() {
+        let stroke = match strokes.get(&chr) {
+            Some(stroke) => *stroke,
+            None => {
+                // not on the layout, borrow the spare keycode
+                let keycode = match spare {
+                    Some(keycode) => keycode,
+                    None => {
+                        result = Err(SimulateError);
+                        break;
+                    }
+                };
+                let mut keysyms = [char_keysym(chr); 2];
+                xlib::XChangeKeyboardMapping(display, keycode as c_int, 2, keysyms.as_mut_ptr(), 1);
+                xlib::XSync(display, FALSE);
+                thread::sleep(REMAP_SETTLE);
+                remapped = true;
+                KeyStroke { keycode, mods: 0 }
+            }
+        };
+        send_stroke(display, stroke, &modifier_keys, options.hold);
+        thread::sleep(options.delay);
+    }
+
+    if let (true, Some(keycode)) = (remapped, spare) {
+        thread::sleep(REMAP_SETTLE);
+        let mut keysym = NO_SYMBOL;
+        xlib::XChangeKeyboardMapping(display, keycode as c_int, 1, &mut keysym, 1);
+        xlib::XSync(display, FALSE);
+    }
+    if caps_locked {
+        xlib::XkbLockModifiers(display, XKB_USE_CORE_KBD, xlib::LockMask, xlib::LockMask);
+        xlib::XSync(display, FALSE);
+    }
+    result
+}
+
+unsafe fn caps_locked(display: *mut xlib::Display) -> bool {
+    let mut state: xlib::XkbStateRec = zeroed();
+    xlib::XkbGetState(display, XKB_USE_CORE_KBD, &mut state) == XKB_SUCCESS
+        && c_uint::from(state.locked_mods) & xlib::LockMask != 0
+}
+
+unsafe fn send_stroke(
+    display: *mut xlib::Display,
+    stroke: KeyStroke,
+    modifier_keys: &[Option<c_uint>; 8],
+    hold: Duration,
+) {
+    let modifiers = stroke_modifiers(stroke.mods, modifier_keys);
+    for &keycode in &modifiers {
+        xtest::XTestFakeKeyEvent(display, keycode, TRUE, 0);
+    }
+    xtest::XTestFakeKeyEvent(display, stroke.keycode, TRUE, 0);
+    xlib::XSync(display, FALSE);
+    thread::sleep(hold);
+    xtest::XTestFakeKeyEvent(display, stroke.keycode, FALSE, 0);
+    for &keycode in modifiers.iter().rev() {
+        xtest::XTestFakeKeyEvent(display, keycode, FALSE, 0);
+    }
+    xlib::XSync(display, FALSE);
+}
+
+/// Keys to hold for the modifier mask `mods`, in modifier bit order.
+fn stroke_modifiers(mods: c_uint, modifier_keys: &[Option<c_uint>; 8]) -> Vec<c_uint> {
+    (0..8)
+        .filter(|bit| mods & (1 << bit) != 0)
+        .filter_map(|bit| modifier_keys[bit])
+        .collect()
+}
+
+/// Modifiers selecting `level` of a key type, `None` if no active entry
+/// of its map leads there. The first level needs none.
+fn level_mods(entries: &[XkbKTMapEntryRec], level: c_uchar) -> Option<c_uint> {
+    if level == 0 {
+        return Some(0);
+    }
+    entries
+        .iter()
+        .find(|entry| entry.active != FALSE && entry.level == level)
+        .map(|entry| entry.mods.mask as c_uint)
+}
+
+/// Maps every character the active layout group can produce to the key and
+/// modifiers producing it, preferring the lowest shift level.
+unsafe fn resolve_keymap(
+    display: *mut xlib::Display,
+    keyboard: &mut Keyboard,
+) -> Option<HashMap<char, KeyStroke>> {
+    let mut state: xlib::XkbStateRec = zeroed();
+    if xlib::XkbGetState(display, XKB_USE_CORE_KBD, &mut state) != XKB_SUCCESS {
+        return None;
+    }
+    let desc = xlib::XkbGetMap(
+        display,
+        XKB_KEY_TYPES_MASK | XKB_KEY_SYMS_MASK,
+        XKB_USE_CORE_KBD,
+    );
+    if desc.is_null() {
+        return None;
+    }
+    let map = ((*desc).map as *const XkbClientMapRec).as_ref();
+    let mut strokes = HashMap::new();
+    if let Some(map) = map {
+        let types = slice::from_raw_parts(map.types, map.num_types as usize);
+        let mut candidates = vec![];
+        for keycode in (*desc).min_key_code..=(*desc).max_key_code {
+            let sym_map = &*map.key_sym_map.add(keycode as usize);
+            let num_groups = sym_map.group_info & 0x0f;
+            if num_groups == 0 {
+                continue;
+            }
+            // out of range groups wrap around, like the server does by default
+            let group = state.group % num_groups;
+            let key_type = match types.get(sym_map.kt_index[group as usize] as usize) {
+                Some(key_type) => key_type,
+                None => continue,
+            };
+            let entries = slice::from_raw_parts(key_type.map, key_type.map_count as usize);
+            for level in 0..key_type.num_levels {
+                if let Some(mods) = level_mods(entries, level) {
+                    candidates.push((level, keycode as c_uint, mods));
+                }
+            }
+        }
+        candidates.sort_by_key(|&(level, keycode, _)| (level, keycode));
+        let group_state = (state.group as c_uint) << 13;
+        for (_, keycode, mods) in candidates {
+            let name = keyboard
+                .unicode_from_code(keycode, mods | group_state)
+                .and_then(|info| info.name);
+            let mut chars = name.as_deref().unwrap_or_default().chars();
+            if let (Some(chr), None) = (chars.next(), chars.next()) {
+                strokes.entry(chr).or_insert(KeyStroke { keycode, mods });
+            }
+        }
+    }
+    xlib::XkbFreeKeyboard(desc, 0, TRUE);
+
+    // control characters don't come out of the lookup
+    for (chr, keysym) in [('\n', keysym::XK_Return), ('\t', keysym::XK_Tab)] {
+        let keycode = xlib::XKeysymToKeycode(display, keysym as xlib::KeySym);
+        if keycode != 0 {
+            strokes.entry(chr).or_insert(KeyStroke {
+                keycode: keycode as c_uint,
+                mods: 0,
+            });
+        }
+    }
+    Some(strokes)
+}
+
+/// First keycode bound to each of the 8 modifiers.
+unsafe fn modifier_keycodes(display: *mut xlib::Display) -> [Option<c_uint>; 8] {
+    let mut keycodes = [None; 8];
+    let modmap = xlib::XGetModifierMapping(display);
+    if modmap.is_null() {
+        return keycodes;
+    }
+    let per_modifier = (*modmap).max_keypermod as usize;
+    let codes = slice::from_raw_parts((*modmap).modifiermap, per_modifier * 8);
+    for (bit, keycode) in keycodes.iter_mut().enumerate() {
+        *keycode = codes[bit * per_modifier..(bit + 1) * per_modifier]
+            .iter()
+            .find(|&&code| code != 0)
+            .map(|&code| code as c_uint);
+    }
+    xlib::XFreeModifiermap(modmap);
+    keycodes
+}
+
+/// A keycode without any symbols, searched from the top where they are usually free.
+unsafe fn spare_keycode(display: *mut xlib::Display) -> Option<c_uint> {
+    let mut min = 0;
+    let mut max = 0;
+    xlib::XDisplayKeycodes(display, &mut min, &mut max);
+    let count = max - min + 1;
+    let mut per_keycode = 0;
+    let keysyms = xlib::XGetKeyboardMapping(display, min as c_uchar, count, &mut per_keycode);
+    if keysyms.is_null() {
+        return None;
+    }
+    let per_keycode = per_keycode as usize;
+    let all = slice::from_raw_parts(keysyms, count as usize * per_keycode);
+    let spare = all
+        .chunks(per_keycode)
+        .enumerate()
+        .rev()
+        .find(|(_, syms)| syms.iter().all(|&sym| sym == NO_SYMBOL))
+        .map(|(index, _)| (min as usize + index) as c_uint);
+    xlib::XFree(keysyms as *mut _);
+    spare
+}
+
+/// Latin-1 characters have their own keysyms, everything else uses the
+/// Unicode keysym range.
+fn char_keysym(chr: char) -> xlib::KeySym {
+    let ordinal = chr as u32;
+    if ordinal < 0x100 {
+        ordinal as xlib::KeySym
+    } else {
+        (ordinal | 0x0100_0000) as xlib::KeySym
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn chars_map_to_latin1_or_unicode_keysyms() {
+        assert_eq!(char_keysym('a'), keysym::XK_a as xlib::KeySym);
+        assert_eq!(char_keysym('é'), keysym::XK_eacute as xlib::KeySym);
+        assert_eq!(char_keysym('€'), 0x0100_20ac);
+        assert_eq!(char_keysym('😀'), 0x0101_f600);
+    }
+
+    #[test]
+    fn modifiers_follow_the_mask() {
+        let shift = xlib::ShiftMask;
+        let mod5 = xlib::Mod5Mask;
+        let mut keys = [None; 8];
+        keys[0] = Some(50);
+        keys[7] = Some(92);
+        assert_eq!(stroke_modifiers(0, &keys), Vec::<c_uint>::new());
+        assert_eq!(stroke_modifiers(shift | mod5, &keys), [50, 92]);
+        // a modifier without a key is left out
+        assert_eq!(stroke_modifiers(xlib::ControlMask | shift, &keys), [50]);
+    }
+
+    #[test]
+    fn levels_take_the_mods_of_their_active_entry() {
+        let entry = |active, level, mask| XkbKTMapEntryRec {
+            active,
+            level,
+            mods: XkbModsRec {
+                mask,
+                real_mods: mask,
+                vmods: 0,
+            },
+        };
+        let entries = [
+            entry(FALSE, 1, xlib::LockMask as c_uchar),
+            entry(TRUE, 1, xlib::ShiftMask as c_uchar),
+            entry(TRUE, 2, xlib::Mod5Mask as c_uchar),
+        ];
+        assert_eq!(level_mods(&entries, 0), Some(0));
+        assert_eq!(level_mods(&entries, 1), Some(xlib::ShiftMask));
+        assert_eq!(level_mods(&entries, 2), Some(xlib::Mod5Mask));
+        assert_eq!(level_mods(&entries, 3), None);
+    }
+}
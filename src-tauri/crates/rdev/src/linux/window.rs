@@ -0,0 +1,306 @@
+use crate::linux::common::FALSE;
+use crate::rdev::{ActiveWindow, DisplayError};
+use std::convert::TryInto;
+use std::ffi::CStr;
+use std::mem::{size_of, zeroed};
+use std::os::raw::{c_char, c_int, c_long, c_uchar, c_ulong};
+use std::ptr::{null, null_mut};
+use std::sync::{Mutex, Once, OnceLock};
+use std::thread;
+use x11::xlib;
+
+// Connections of the watchers below, whose errors are expected.
+static WATCHER_DISPLAYS: Mutex<Vec<usize>> = Mutex::new(Vec::new());
+static INSTALL_HANDLER: Once = Once::new();
+// Handler that was installed before ours, for every other connection.
+static PREVIOUS_HANDLER: OnceLock<ErrorHandler> = OnceLock::new();
+/// How often the grab watcher looks up the focus without any events.
+const FOCUS_CHECK_MS: c_int = 1000;
+
+type ErrorHandler =
+    Option<unsafe extern "C" fn(*mut xlib::Display, *mut xlib::XErrorEvent) -> c_int>;
+
+/// Windows can be gone by the time we query them, which Xlib reports as
+/// `BadWindow`. The default handler exits the process on those, so they're
+/// dropped for the watcher connection and passed on for any other.
+unsafe extern "C" fn error_handler(
+    display: *mut xlib::Display,
+    error: *mut xlib::XErrorEvent,
+) -> c_int {
+    let watched = WATCHER_DISPLAYS
+        .lock()
+        .is_ok_and(|displays| displays.contains(&(display as usize)));
+    if watched {
+        return 0;
+    }
+    match PREVIOUS_HANDLER.get() {
+        Some(Some(handler)) => handler(display, error),
+        _ => 0,
+    }
+}
+
+struct Atoms {
+    net_active_window: xlib::Atom,
+    net_wm_name: xlib::Atom,
+    utf8_string: xlib::Atom,
+}
+
+impl Atoms {
+    unsafe fn new(display: *mut xlib::Display) -> Atoms {
+        let intern =
+            |name: &[u8]| xlib::XInternAtom(display, name.as_ptr() as *const c_char, FALSE);
+        Atoms {
+            net_active_window: intern(b"_NET_ACTIVE_WINDOW\0"),
+            net_wm_name: intern(b"_NET_WM_NAME\0"),
+            utf8_string: intern(b"UTF8_STRING\0"),
+        }
+    }
+}
+
+/// Returns the focused top level window, `None` when nothing has focus.
+pub fn active_window() -> Result<Option<ActiveWindow>, DisplayError> {
+    unsafe {
+        let display = xlib::XOpenDisplay(null());
+        if display.is_null() {
+            return Err(DisplayError::NoDisplay);
+        }
+        let atoms = Atoms::new(display);
+        let window = active_window_id(display, &atoms);
+        let active = describe(display, &atoms, window);
+        xlib::XCloseDisplay(display);
+        Ok(active)
+    }
+}
+
+/// Calls `callback` from a dedicated thread with the focused window, once
+/// right away and then whenever the focus or the focused window's title
+/// changes. Needs a window manager that sets `_NET_ACTIVE_WINDOW`.
+pub fn watch_active_window<F>(mut callback: F) -> Result<(), DisplayError>
+where
+    F: FnMut(Option<ActiveWindow>) + Send + 'static,
+{
+    let display = open_watcher_display()?;
+    thread::spawn(move || unsafe {
+        let display = display as *mut xlib::Display;
+        let atoms = Atoms::new(display);
+        let root = xlib::XDefaultRootWindow(display);
+        xlib::XSelectInput(display, root, xlib::PropertyChangeMask);
+
+        let mut window = active_window_id(display, &atoms);
+        if window != 0 {
+            xlib::XSelectInput(display, window, xlib::PropertyChangeMask);
+        }
+        let mut last = describe(display, &atoms, window);
+        callback(last.clone());
+
+        let mut event: xlib::XEvent = zeroed();
+        loop {
+            xlib::XNextEvent(display, &mut event);
+            if event.get_type() != xlib::PropertyNotify {
+                continue;
+            }
+            let property = event.property;
+            if property.window == root && property.atom == atoms.net_active_window {
+                let active = active_window_id(display, &atoms);
+                if active == window {
+                    continue;
+                }
+                if window != 0 {
+                    xlib::XSelectInput(display, window, xlib::NoEventMask);
+                }
+                if active != 0 {
+                    xlib::XSelectInput(display, active, xlib::PropertyChangeMask);
+                }
+                window = active;
+            } else if property.window != window
+                || (property.atom != atoms.net_wm_name && property.atom != xlib::XA_WM_NAME)
+            {
+                continue;
+            }
+
+            let current = describe(display, &atoms, window);
+            if current != last {
+                callback(current.clone());
+                last = current;
+            }
+        }
+    });
+    Ok(())
+}
+
+/// Calls `callback` from a dedicated thread whenever another client takes or
+/// lets go of a keyboard grab, as password prompts and screen lockers do
+/// while they read a secret. Listening still sees the keys typed into them.
+///
+/// Grabs are seen in the focus events they cause, without grabbing anything
+/// ourselves. A client grabbing the keyboard for the window that already
+/// has focus moves no focus, so that grab goes unnoticed.
+pub fn watch_keyboard_grabs<F>(mut callback: F) -> Result<(), DisplayError>
+where
+    F: FnMut(bool) + Send + 'static,
+{
+    let display = open_watcher_display()?;
+    thread::spawn(move || unsafe {
+        let display = display as *mut xlib::Display;
+        let root = xlib::XDefaultRootWindow(display);
+        // while focus follows the pointer its events go to the root window
+        xlib::XSelectInput(display, root, xlib::FocusChangeMask);
+        let fd = xlib::XConnectionNumber(display);
+
+        let mut focus = root;
+        let mut grabbed = false;
+        let mut event: xlib::XEvent = zeroed();
+        loop {
+            // focus can move without telling us, e.g. when its window is
+            // destroyed, so it's looked up again on every wake up
+            let current = input_focus(display);
+            if current != focus {
+                if focus != root {
+                    xlib::XSelectInput(display, focus, xlib::NoEventMask);
+                }
+                if current != root {
+                    xlib::XSelectInput(display, current, xlib::FocusChangeMask);
+                }
+                focus = current;
+            }
+            while xlib::XPending(display) > 0 {
+                xlib::XNextEvent(display, &mut event);
+                let kind = event.get_type();
+                if kind != xlib::FocusIn && kind != xlib::FocusOut {
+                    continue;
+                }
+                let grab = match event.focus_change.mode {
+                    xlib::NotifyGrab => true,
+                    xlib::NotifyUngrab => false,
+                    _ => grabbed,
+                };
+                if grab != grabbed {
+                    grabbed = grab;
+                    callback(grabbed);
+                }
+            }
+            let mut pollfd = libc::pollfd {
+                fd,
+                events: libc::POLLIN,
+                revents: 0,
+            };
+            libc::poll(&mut pollfd, 1, FOCUS_CHECK_MS);
+        }
+    });
+    Ok(())
+}
+
+/// Opens a connection for a watcher thread, with errors on it ignored.
+/// Returned as an address, raw pointers aren't Send and the display is only
+/// used by that thread.
+fn open_watcher_display() -> Result<usize, DisplayError> {
+    let display = unsafe { xlib::XOpenDisplay(null()) };
+    if display.is_null() {
+        return Err(DisplayError::NoDisplay);
+    }
+    if let Ok(mut displays) = WATCHER_DISPLAYS.lock() {
+        displays.push(display as usize);
+    }
+    INSTALL_HANDLER.call_once(|| unsafe {
+        let previous = xlib::XSetErrorHandler(Some(error_handler));
+        let _ = PREVIOUS_HANDLER.set(previous);
+    });
+    Ok(display as usize)
+}
+
+/// The window with the keyboard focus, the root window for `PointerRoot`
+/// or no focus at all.
+unsafe fn input_focus(display: *mut xlib::Display) -> xlib::Window {
+    let root = xlib::XDefaultRootWindow(display);
+    let mut focus = 0;
+    let mut revert_to = 0;
+    xlib::XGetInputFocus(display, &mut focus, &mut revert_to);
+    if focus == xlib::PointerRoot as xlib::Window || focus == 0 {
+        root
+    } else {
+        focus
+    }
+}
+
+unsafe fn active_window_id(display: *mut xlib::Display, atoms: &Atoms) -> xlib::Window {
+    let root = xlib::XDefaultRootWindow(display);
+    get_property(display, root, atoms.net_active_window, xlib::XA_WINDOW, 1)
+        // format 32 properties come as longs
+        .filter(|(format, data)| *format == 32 && data.len() >= size_of::<c_ulong>())
+        .map(|(_, data)| c_ulong::from_ne_bytes(data[..size_of::<c_ulong>()].try_into().unwrap()))
+        .unwrap_or(0)
+}
+
+unsafe fn describe(
+    display: *mut xlib::Display,
+    atoms: &Atoms,
+    window: xlib::Window,
+) -> Option<ActiveWindow> {
+    if window == 0 {
+        return None;
+    }
+    let mut active = ActiveWindow::default();
+    let mut hint: xlib::XClassHint = zeroed();
+    if xlib::XGetClassHint(display, window, &mut hint) != 0 {
+        for (field, ptr) in [
+            (&mut active.instance, hint.res_name),
+            (&mut active.class, hint.res_class),
+        ] {
+            if !ptr.is_null() {
+                *field = CStr::from_ptr(ptr).to_string_lossy().into_owned();
+                xlib::XFree(ptr as *mut _);
+            }
+        }
+    }
+    // the legacy WM_NAME is latin-1 at best, prefer the EWMH one
+    active.title = get_property(display, window, atoms.net_wm_name, atoms.utf8_string, 1024)
+        .or_else(|| get_property(display, window, xlib::XA_WM_NAME, xlib::XA_STRING, 1024))
+        .map(|(_, data)| String::from_utf8_lossy(&data).into_owned())
+        .unwrap_or_default();
+    Some(active)
+}
+
+/// Reads up to `length` 32 bit units of a window property, returning its
+/// format and raw data.
+unsafe fn get_property(
+    display: *mut xlib::Display,
+    window: xlib::Window,
+    property: xlib::Atom,
+    kind: xlib::Atom,
+    length: c_long,
+) -> Option<(c_int, Vec<u8>)> {
+    let mut actual_kind = 0;
+    let mut format = 0;
+    let mut items: c_ulong = 0;
+    let mut remaining: c_ulong = 0;
+    let mut data: *mut c_uchar = null_mut();
+    let status = xlib::XGetWindowProperty(
+        display,
+        window,
+        property,
+        0,
+        length,
+        FALSE,
+        kind,
+        &mut actual_kind,
+        &mut format,
+        &mut items,
+        &mut remaining,
+        &mut data,
+    );
+    if status != xlib::Success as c_int || data.is_null() {
+        return None;
+    }
+    let unit = match format {
+        8 => 1,
+        16 => size_of::<std::os::raw::c_short>(),
+        32 => size_of::<c_long>(),
+        _ => 0,
+    };
+    let bytes = std::slice::from_raw_parts(data, items as usize * unit).to_vec();
+    xlib::XFree(data as *mut _);
+    if actual_kind != kind {
+        return None;
+    }
+    Some((format, bytes))
+}
@@ -0,0 +1,533 @@
+use crate::linux::common::FALSE;
+use crate::rdev::{EventType, GesturePhase, PenState, TouchPhase};
+use std::collections::HashMap;
+use std::convert::TryInto;
+use std::ffi::CStr;
+use std::mem::zeroed;
+use std::os::raw::{c_int, c_long, c_uchar};
+use std::ptr::{null, null_mut};
+use std::slice;
+use x11::{xinput2, xlib};
+
+// XInput 2.4 gesture events, not exported by the x11 crate.
+// Updates come in between, as 28 and 31.
+const XI_GESTURE_PINCH_BEGIN: c_int = 27;
+const XI_GESTURE_PINCH_END: c_int = 29;
+const XI_GESTURE_SWIPE_BEGIN: c_int = 30;
+const XI_GESTURE_SWIPE_END: c_int = 32;
+const XI_GESTURE_CANCELLED: u32 = 1 << 0;
+
+// Valuator labels set by the evdev, libinput and wacom drivers.
+const ABS_X: &[&str] = &["Abs MT Position X", "Abs X"];
+const ABS_Y: &[&str] = &["Abs MT Position Y", "Abs Y"];
+const ABS_PRESSURE: &[&str] = &["Abs Pressure"];
+const ABS_TILT_X: &[&str] = &["Abs Tilt X"];
+const ABS_TILT_Y: &[&str] = &["Abs Tilt Y"];
+
+// Offsets into the gesture events as sent to clients, see xXIGesturePinchEvent
+// and xXIGestureSwipeEvent in XI2proto.h. Both start out the same.
+const GESTURE_EVTYPE: usize = 8;
+const GESTURE_DETAIL: usize = 16;
+const GESTURE_DELTA_X: usize = 48;
+const GESTURE_DELTA_Y: usize = 52;
+const PINCH_SCALE: usize = 64;
+const PINCH_DELTA_ANGLE: usize = 68;
+const PINCH_FLAGS: usize = 96;
+const SWIPE_FLAGS: usize = 88;
+
+/// An absolute valuator of a device and its range.
+#[derive(Debug, Copy, Clone)]
+struct Axis {
+    number: c_int,
+    min: f64,
+    max: f64,
+}
+
+impl Axis {
+    /// Maps a raw value into 0.0..=1.0.
+    fn normalize(&self, value: f64) -> f64 {
+        if self.max > self.min {
+            ((value - self.min) / (self.max - self.min)).clamp(0.0, 1.0)
+        } else {
+            0.0
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+enum DeviceKind {
+    Touchscreen,
+    Pen { eraser: bool },
+}
+
+#[derive(Debug)]
+struct Device {
+    kind: DeviceKind,
+    x: Option<Axis>,
+    y: Option<Axis>,
+    pressure: Option<Axis>,
+    tilt_x: Option<Axis>,
+    tilt_y: Option<Axis>,
+    // "Coordinate Transformation Matrix", row major
+    transform: [f64; 9],
+}
+
+impl Device {
+    /// Maps a normalized device position to pixels. The matrix set with
+    /// `xinput map-to-output` turns device coordinates into the part of the
+    /// X screen the device covers, both from 0.0 to 1.0.
+    fn to_screen(&self, (x, y): (f64, f64), (width, height): (f64, f64)) -> (f64, f64) {
+        let m = &self.transform;
+        let w = m[6] * x + m[7] * y + m[8];
+        let w = if w == 0.0 { 1.0 } else { w };
+        (
+            (m[0] * x + m[1] * y + m[2]) / w * width,
+            (m[3] * x + m[4] * y + m[5]) / w * height,
+        )
+    }
+}
+
+/// Receives XInput2 events the core protocol (and so XRecord) doesn't
+/// carry, on its own display connection.
+/// Touchpad gestures are only selected on the root window for the server to
+/// send them somewhere when no other client wants them, the listen loop
+/// records them from whichever client gets them with `recorded_gesture`.
+/// Touch and pen positions come from the raw device axes, placed on the X
+/// screen by the device's coordinate transformation matrix. Changes to the
+/// matrix only apply once the devices change.
+pub struct XInputListener {
+    display: *mut xlib::Display,
+    opcode: c_int,
+    screen_size: (f64, f64),
+    devices: HashMap<c_int, Device>,
+    pens: HashMap<c_int, PenState>,
+    // normalized device positions, of each pen and each touch
+    pen_positions: HashMap<c_int, (f64, f64)>,
+    touches: HashMap<u32, (f64, f64)>,
+}
+
+impl XInputListener {
+    pub fn new() -> Option<XInputListener> {
+        unsafe {
+            let display = xlib::XOpenDisplay(null());
+            if display.is_null() {
+                return None;
+            }
+            let screen = xlib::XDefaultScreen(display);
+            let mut listener = XInputListener {
+                display,
+                opcode: 0,
+                screen_size: (
+                    xlib::XDisplayWidth(display, screen) as f64,
+                    xlib::XDisplayHeight(display, screen) as f64,
+                ),
+                devices: HashMap::new(),
+                pens: HashMap::new(),
+                pen_positions: HashMap::new(),
+                touches: HashMap::new(),
+            };
+            listener.opcode = listener.select_events()?;
+            listener.query_devices();
+            Some(listener)
+        }
+    }
+
+    /// Selects raw pointer, touch and gesture events on the root window,
+    /// as far as the server supports them, and returns the extension opcode.
+    unsafe fn select_events(&self) -> Option<c_int> {
+        let name = CStr::from_bytes_with_nul(b"XInputExtension\0").ok()?;
+        let mut opcode = 0;
+        let mut event = 0;
+        let mut error = 0;
+        if xlib::XQueryExtension(
+            self.display,
+            name.as_ptr(),
+            &mut opcode,
+            &mut event,
+            &mut error,
+        ) == FALSE
+        {
+            return None;
+        }
+        let mut major = 2;
+        let mut minor = 4;
+        if xinput2::XIQueryVersion(self.display, &mut major, &mut minor) != xlib::Success as c_int
+            || major < 2
+        {
+            log::debug!("XInput {}.{} is too old", major, minor);
+            return None;
+        }
+
+        let mut mask = [0 as c_uchar; (XI_GESTURE_SWIPE_END as usize >> 3) + 1];
+        let mut evtypes = vec![
+            xinput2::XI_RawButtonPress,
+            xinput2::XI_RawButtonRelease,
+            xinput2::XI_RawMotion,
+        ];
+        // touch needs XInput 2.2, gestures 2.4
+        if minor >= 2 {
+            evtypes.extend(xinput2::XI_RawTouchBegin..=xinput2::XI_RawTouchEnd);
+        }
+        if minor >= 4 {
+            evtypes.extend(XI_GESTURE_PINCH_BEGIN..=XI_GESTURE_SWIPE_END);
+        }
+        for evtype in evtypes {
+            set_mask_bit(&mut mask, evtype);
+        }
+        // device changes are only reported for all devices
+        let mut hierarchy_mask = [0 as c_uchar; (xinput2::XI_HierarchyChanged as usize >> 3) + 1];
+        set_mask_bit(&mut hierarchy_mask, xinput2::XI_HierarchyChanged);
+
+        let mut event_masks = [
+            xinput2::XIEventMask {
+                deviceid: xinput2::XIAllMasterDevices,
+                mask_len: mask.len() as c_int,
+                mask: mask.as_mut_ptr(),
+            },
+            xinput2::XIEventMask {
+                deviceid: xinput2::XIAllDevices,
+                mask_len: hierarchy_mask.len() as c_int,
+                mask: hierarchy_mask.as_mut_ptr(),
+            },
+        ];
+        let root = xlib::XDefaultRootWindow(self.display);
+        xinput2::XISelectEvents(
+            self.display,
+            root,
+            event_masks.as_mut_ptr(),
+            event_masks.len() as c_int,
+        );
+        xlib::XFlush(self.display);
+        Some(opcode)
+    }
+
+    /// Looks up the touchscreens and pens among the attached pointer devices.
+    unsafe fn query_devices(&mut self) {
+        self.devices.clear();
+        let mut count = 0;
+        let infos = xinput2::XIQueryDevice(self.display, xinput2::XIAllDevices, &mut count);
+        if infos.is_null() {
+            return;
+        }
+        for info in slice::from_raw_parts(infos, count as usize) {
+            if info._use != xinput2::XISlavePointer {
+                continue;
+            }
+            if let Some(device) = self.describe_device(info) {
+                log::debug!("XInput device {} is a {:?}", info.deviceid, device.kind);
+                self.devices.insert(info.deviceid, device);
+            }
+        }
+        xinput2::XIFreeDeviceInfo(infos);
+    }
+
+    unsafe fn describe_device(&self, info: &xinput2::XIDeviceInfo) -> Option<Device> {
+        let mut direct_touch = false;
+        let mut axes = vec![];
+        for &class in slice::from_raw_parts(info.classes, info.num_classes as usize) {
+            match (*class)._type {
+                xinput2::XITouchClass => {
+                    let touch = &*(class as *const xinput2::XITouchClassInfo);
+                    direct_touch |= touch.mode == xinput2::XIDirectTouch;
+                }
+                xinput2::XIValuatorClass => {
+                    let valuator = &*(class as *const xinput2::XIValuatorClassInfo);
+                    if let Some(label) = self.atom_name(valuator.label) {
+                        let axis = Axis {
+                            number: valuator.number,
+                            min: valuator.min,
+                            max: valuator.max,
+                        };
+                        axes.push((label, axis));
+                    }
+                }
+                _ => {}
+            }
+        }
+        let find_axis = |labels: &[&str]| {
+            labels
+                .iter()
+                .find_map(|label| axes.iter().find(|(name, _)| name == label))
+                .map(|(_, axis)| *axis)
+        };
+        let x = find_axis(ABS_X);
+        let pressure = find_axis(ABS_PRESSURE);
+        let kind = if direct_touch {
+            DeviceKind::Touchscreen
+        } else if x.is_some() && pressure.is_some() {
+            // tablet drivers expose the eraser as its own device
+            let name = CStr::from_ptr(info.name).to_string_lossy().to_lowercase();
+            DeviceKind::Pen {
+                eraser: name.contains("eraser"),
+            }
+        } else {
+            return None;
+        };
+        Some(Device {
+            kind,
+            x,
+            y: find_axis(ABS_Y),
+            pressure,
+            tilt_x: find_axis(ABS_TILT_X),
+            tilt_y: find_axis(ABS_TILT_Y),
+            transform: self.transform(info.deviceid),
+        })
+    }
+
+    /// The device's coordinate transformation matrix, identity if unset.
+    unsafe fn transform(&self, deviceid: c_int) -> [f64; 9] {
+        let mut transform = [1.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0];
+        let name = b"Coordinate Transformation Matrix\0";
+        let property = xlib::XInternAtom(self.display, name.as_ptr() as *const _, xlib::True);
+        let float = xlib::XInternAtom(self.display, b"FLOAT\0".as_ptr() as *const _, xlib::True);
+        if property == 0 || float == 0 {
+            return transform;
+        }
+        let mut type_ = 0;
+        let mut format = 0;
+        let mut count = 0;
+        let mut bytes_after = 0;
+        let mut data = null_mut();
+        let status = xinput2::XIGetProperty(
+            self.display,
+            deviceid,
+            property,
+            0,
+            transform.len() as c_long,
+            FALSE,
+            float,
+            &mut type_,
+            &mut format,
+            &mut count,
+            &mut bytes_after,
+            &mut data,
+        );
+        if status != xlib::Success as c_int || data.is_null() {
+            return transform;
+        }
+        // 32 bit items come packed, unlike with XGetWindowProperty
+        if type_ == float && format == 32 && count as usize == transform.len() {
+            let values = slice::from_raw_parts(data as *const f32, transform.len());
+            for (entry, value) in transform.iter_mut().zip(values) {
+                *entry = f64::from(*value);
+            }
+        }
+        xlib::XFree(data as *mut _);
+        transform
+    }
+
+    unsafe fn atom_name(&self, atom: xlib::Atom) -> Option<String> {
+        if atom == 0 {
+            return None;
+        }
+        let ptr = xlib::XGetAtomName(self.display, atom);
+        if ptr.is_null() {
+            return None;
+        }
+        let name = CStr::from_ptr(ptr).to_string_lossy().into_owned();
+        xlib::XFree(ptr as *mut _);
+        Some(name)
+    }
+
+    /// Major opcode of the XInput extension.
+    pub fn opcode(&self) -> c_int {
+        self.opcode
+    }
+
+    /// File descriptor to poll for new events.
+    pub fn fd(&self) -> c_int {
+        unsafe { xlib::XConnectionNumber(self.display) }
+    }
+
+    /// Reads all queued events without blocking.
+    pub fn pending_events(&mut self) -> Vec<EventType> {
+        let mut events = vec![];
+        unsafe {
+            while xlib::XPending(self.display) > 0 {
+                let mut event: xlib::XEvent = zeroed();
+                xlib::XNextEvent(self.display, &mut event);
+                let mut cookie = event.generic_event_cookie;
+                if cookie.type_ != xlib::GenericEvent
+                    || cookie.extension != self.opcode
+                    || xlib::XGetEventData(self.display, &mut cookie) == FALSE
+                {
+                    continue;
+                }
+                if let Some(event_type) = self.convert_cookie(&cookie) {
+                    events.push(event_type);
+                }
+                xlib::XFreeEventData(self.display, &mut cookie);
+            }
+        }
+        events
+    }
+
+    unsafe fn convert_cookie(&mut self, cookie: &xlib::XGenericEventCookie) -> Option<EventType> {
+        match cookie.evtype {
+            xinput2::XI_HierarchyChanged => {
+                self.query_devices();
+                None
+            }
+            xinput2::XI_RawButtonPress | xinput2::XI_RawButtonRelease | xinput2::XI_RawMotion => {
+                self.convert_pen(&*(cookie.data as *const xinput2::XIRawEvent))
+            }
+            xinput2::XI_RawTouchBegin..=xinput2::XI_RawTouchEnd => {
+                self.convert_touch(&*(cookie.data as *const xinput2::XIRawEvent))
+            }
+            // recorded as delivered instead, this copy is one of those
+            _ => None,
+        }
+    }
+
+    unsafe fn convert_touch(&mut self, raw: &xinput2::XIRawEvent) -> Option<EventType> {
+        let device = self.devices.get(&raw.sourceid)?;
+        if device.kind != DeviceKind::Touchscreen {
+            return None;
+        }
+        let id = raw.detail as u32;
+        let (last_x, last_y) = self.touches.get(&id).copied().unwrap_or_default();
+        let position = (
+            axis_value(&raw.valuators, device.x).unwrap_or(last_x),
+            axis_value(&raw.valuators, device.y).unwrap_or(last_y),
+        );
+        let (x, y) = device.to_screen(position, self.screen_size);
+        let phase = match raw.evtype {
+            xinput2::XI_RawTouchBegin => TouchPhase::Down,
+            xinput2::XI_RawTouchEnd => TouchPhase::Up,
+            _ => TouchPhase::Move,
+        };
+        if phase == TouchPhase::Up {
+            self.touches.remove(&id);
+        } else {
+            self.touches.insert(id, position);
+        }
+        Some(EventType::Touch { id, phase, x, y })
+    }
+
+    unsafe fn convert_pen(&mut self, raw: &xinput2::XIRawEvent) -> Option<EventType> {
+        let device = self.devices.get(&raw.sourceid)?;
+        let eraser = match device.kind {
+            DeviceKind::Pen { eraser } => eraser,
+            DeviceKind::Touchscreen => return None,
+        };
+        let pen = self.pens.entry(raw.sourceid).or_default();
+        pen.eraser = eraser;
+        let position = self.pen_positions.entry(raw.sourceid).or_default();
+        if let Some(x) = axis_value(&raw.valuators, device.x) {
+            position.0 = x;
+        }
+        if let Some(y) = axis_value(&raw.valuators, device.y) {
+            position.1 = y;
+        }
+        (pen.x, pen.y) = device.to_screen(*position, self.screen_size);
+        if let Some(pressure) = axis_value(&raw.valuators, device.pressure) {
+            pen.pressure = pressure;
+        }
+        if let Some(tilt_x) = axis_value(&raw.valuators, device.tilt_x) {
+            pen.tilt_x = tilt_x * 2.0 - 1.0;
+        }
+        if let Some(tilt_y) = axis_value(&raw.valuators, device.tilt_y) {
+            pen.tilt_y = tilt_y * 2.0 - 1.0;
+        }
+        if raw.evtype != xinput2::XI_RawMotion {
+            let pressed = raw.evtype == xinput2::XI_RawButtonPress;
+            // button 1 is the tip, 2 and 3 the barrel buttons, the rest scroll
+            match raw.detail {
+                1 => pen.contact = pressed,
+                2 | 3 => {
+                    let bit = 1 << (raw.detail - 2);
+                    if pressed {
+                        pen.barrel_buttons |= bit;
+                    } else {
+                        pen.barrel_buttons &= !bit;
+                    }
+                }
+                _ => return None,
+            }
+        }
+        Some(EventType::PenChanged(*pen))
+    }
+}
+
+impl Drop for XInputListener {
+    fn drop(&mut self) {
+        unsafe {
+            xlib::XCloseDisplay(self.display);
+        }
+    }
+}
+
+fn set_mask_bit(mask: &mut [c_uchar], evtype: c_int) {
+    mask[evtype as usize >> 3] |= 1 << (evtype & 7);
+}
+
+/// Normalized value of `axis` if the event carries it. Only the valuators
+/// set in the mask are stored, in order.
+unsafe fn axis_value(valuators: &xinput2::XIValuatorState, axis: Option<Axis>) -> Option<f64> {
+    let axis = axis?;
+    let mask = slice::from_raw_parts(valuators.mask, valuators.mask_len as usize);
+    let is_set = |number: c_int| {
+        mask.get(number as usize >> 3)
+            .is_some_and(|byte| byte & (1 << (number & 7)) != 0)
+    };
+    if !is_set(axis.number) {
+        return None;
+    }
+    let index = (0..axis.number).filter(|&number| is_set(number)).count();
+    Some(axis.normalize(*valuators.values.add(index)))
+}
+
+fn gesture_phase(evtype: c_int, flags: u32) -> GesturePhase {
+    match evtype {
+        XI_GESTURE_PINCH_BEGIN | XI_GESTURE_SWIPE_BEGIN => GesturePhase::Begin,
+        XI_GESTURE_PINCH_END | XI_GESTURE_SWIPE_END if flags & XI_GESTURE_CANCELLED != 0 => {
+            GesturePhase::Cancel
+        }
+        XI_GESTURE_PINCH_END | XI_GESTURE_SWIPE_END => GesturePhase::End,
+        _ => GesturePhase::Update,
+    }
+}
+
+/// Converts a gesture event the way XRecord captured it on its way to a
+/// client, `opcode` being the XInput extension's. Recording the delivery
+/// sees gestures whichever window selected them, where a selection of our
+/// own only gets those no other client takes.
+pub fn recorded_gesture(opcode: c_int, data: &[u8]) -> Option<EventType> {
+    let u16_at = |offset: usize| {
+        Some(u16::from_ne_bytes(
+            data.get(offset..offset + 2)?.try_into().ok()?,
+        ))
+    };
+    let u32_at = |offset: usize| {
+        Some(u32::from_ne_bytes(
+            data.get(offset..offset + 4)?.try_into().ok()?,
+        ))
+    };
+    // 16.16 fixed point
+    let fixed_at = |offset: usize| Some(u32_at(offset)? as i32 as f64 / 65536.0);
+
+    if c_int::from(*data.get(1)?) != opcode {
+        return None;
+    }
+    let evtype = c_int::from(u16_at(GESTURE_EVTYPE)?);
+    let fingers = u32_at(GESTURE_DETAIL)?;
+    let delta_x = fixed_at(GESTURE_DELTA_X)?;
+    let delta_y = fixed_at(GESTURE_DELTA_Y)?;
+    match evtype {
+        XI_GESTURE_PINCH_BEGIN..=XI_GESTURE_PINCH_END => Some(EventType::GesturePinch {
+            phase: gesture_phase(evtype, u32_at(PINCH_FLAGS)?),
+            fingers,
+            delta_x,
+            delta_y,
+            scale: fixed_at(PINCH_SCALE)?,
+            rotation: fixed_at(PINCH_DELTA_ANGLE)?,
+        }),
+        XI_GESTURE_SWIPE_BEGIN..=XI_GESTURE_SWIPE_END => Some(EventType::GestureSwipe {
+            phase: gesture_phase(evtype, u32_at(SWIPE_FLAGS)?),
+            fingers,
+            delta_x,
+            delta_y,
+        }),
+        _ => None,
+    }
+}
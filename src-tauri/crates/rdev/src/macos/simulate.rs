@@ -150,6 +150,14 @@ unsafe fn convert_native_with_source(
             )
             .ok()
         }
+        // notifications, gestures, touch and pen input, nothing to send
+        EventType::LockStateChanged(_)
+        | EventType::LayoutChanged { .. }
+        | EventType::GestureSwipe { .. }
+        | EventType::GesturePinch { .. }
+        | EventType::GestureHold { .. }
+        | EventType::Touch { .. }
+        | EventType::PenChanged(_) => None,
     }
 }
 
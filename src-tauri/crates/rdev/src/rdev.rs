@@ -1,6 +1,6 @@
 #[cfg(feature = "serialize")]
 use serde::{Deserialize, Serialize};
-use std::time::SystemTime;
+use std::time::{Duration, SystemTime};
 use std::{fmt, fmt::Display};
 
 // /// Callback type to send to listen function.
@@ -80,6 +80,14 @@ pub enum DisplayError {
     ConversionError,
 }
 
+/// Errors that occur when trying to query which keys are held down.
+#[non_exhaustive]
+#[derive(Debug)]
+pub enum KeyStateError {
+    /// Linux
+    MissingDisplayError,
+}
+
 impl From<SimulateError> for GrabError {
     fn from(_: SimulateError) -> GrabError {
         GrabError::SimulateError
@@ -297,6 +305,85 @@ pub enum Button {
     Unknown(u8),
 }
 
+/// State of the keyboard lock keys, as shown by the keyboard LEDs.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
+pub struct LockState {
+    pub caps_lock: bool,
+    pub num_lock: bool,
+    pub scroll_lock: bool,
+}
+
+/// The focused top level window, as the window manager reports it.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
+pub struct ActiveWindow {
+    /// Class from `WM_CLASS`, e.g. "firefox".
+    pub class: String,
+    /// Instance name from `WM_CLASS`, e.g. "Navigator".
+    pub instance: String,
+    /// `_NET_WM_NAME`, or `WM_NAME` for clients without it.
+    pub title: String,
+}
+
+/// Progress of a touchpad gesture. Every gesture starts with `Begin`, then gets
+/// any number of `Update`s and finishes with either `End` or `Cancel`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
+pub enum GesturePhase {
+    Begin,
+    Update,
+    End,
+    Cancel,
+}
+
+/// Phase of a single touch contact on a touchscreen.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
+pub enum TouchPhase {
+    Down,
+    Move,
+    Up,
+}
+
+/// State of a stylus on a graphics tablet or pen display.
+#[derive(Debug, Copy, Clone, PartialEq, Default)]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
+pub struct PenState {
+    /// Position in pixels, like `EventType::MouseMove`.
+    pub x: f64,
+    pub y: f64,
+    /// Tip pressure, from 0.0 to 1.0.
+    pub pressure: f64,
+    /// Tilt along each axis, from -1.0 to 1.0 with 0.0 being upright.
+    pub tilt_x: f64,
+    pub tilt_y: f64,
+    /// Whether the tip touches the surface.
+    pub contact: bool,
+    /// Whether the eraser end is in use.
+    pub eraser: bool,
+    /// Held barrel buttons, the lowest bit being the first button.
+    pub barrel_buttons: u8,
+}
+
+/// Timing used by `type_text`, per typed character.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct TypeTextOptions {
+    /// How long each key is held down.
+    pub hold: Duration,
+    /// Pause after releasing a key, before the next character.
+    pub delay: Duration,
+}
+
+impl Default for TypeTextOptions {
+    fn default() -> Self {
+        TypeTextOptions {
+            hold: Duration::from_millis(10),
+            delay: Duration::from_millis(30),
+        }
+    }
+}
+
 /// In order to manage different OSs, the current EventType choices are a mix and
 /// match to account for all possible events.
 #[derive(Debug, Copy, Clone, PartialEq)]
@@ -324,10 +411,78 @@ pub enum EventType {
         delta_x: i64,
         delta_y: i64,
     },
+    /// Sent after a lock key changed the lock state, following the key event itself.
+    /// Emitted on Linux and Windows, and can't be simulated.
+    LockStateChanged(LockState),
+    /// Sent when the active keyboard layout changed, either by switching groups
+    /// or by loading a new keymap. `layout` identifies the layout for
+    /// [`layout_name`](crate::layout_name) to look up its display name.
+    /// Only emitted on Linux for now, and can't be simulated.
+    LayoutChanged {
+        layout: usize,
+    },
+    /// Multi-finger touchpad swipe. Deltas are in pixels since the previous update.
+    /// Reported whichever client the gesture goes to.
+    /// Only emitted on Linux (XInput 2.4) for now, and can't be simulated.
+    GestureSwipe {
+        phase: GesturePhase,
+        fingers: u32,
+        delta_x: f64,
+        delta_y: f64,
+    },
+    /// Touchpad pinch and rotate. `scale` is relative to the start of the gesture,
+    /// `rotation` is in degrees since the previous update.
+    /// Reported whichever client the gesture goes to.
+    /// Only emitted on Linux (XInput 2.4) for now, and can't be simulated.
+    GesturePinch {
+        phase: GesturePhase,
+        fingers: u32,
+        delta_x: f64,
+        delta_y: f64,
+        scale: f64,
+        rotation: f64,
+    },
+    /// Two or more fingers resting on the touchpad. Ends with `Cancel` when
+    /// they start moving, usually into a swipe or pinch.
+    /// Only emitted on Linux for now, read from the touchpad's evdev device,
+    /// which takes read access to `/dev/input`. Can't be simulated.
+    GestureHold {
+        phase: GesturePhase,
+        fingers: u32,
+    },
+    /// A touchscreen contact. `id` stays the same from `Down` to `Up` and is
+    /// reused afterwards. Position in pixels, like `MouseMove`.
+    /// Only emitted on Linux (XInput 2.2) for now, and can't be simulated.
+    Touch {
+        id: u32,
+        phase: TouchPhase,
+        x: f64,
+        y: f64,
+    },
+    /// Sent whenever a stylus moves or one of its values changes.
+    /// Only emitted on Linux for now, and can't be simulated.
+    PenChanged(PenState),
+}
+
+impl EventType {
+    /// Whether `simulate` can send this event, the others only report what
+    /// happened and are refused on every platform.
+    pub fn can_simulate(&self) -> bool {
+        matches!(
+            self,
+            EventType::KeyPress(_)
+                | EventType::KeyRelease(_)
+                | EventType::ButtonPress(_)
+                | EventType::ButtonRelease(_)
+                | EventType::MouseMove { .. }
+                | EventType::Wheel { .. }
+        )
+    }
 }
 
 /// The Unicode information of input.
 #[derive(Debug, Clone, PartialEq, Default)]
+#[cfg_attr(feature = "serialize", derive(Serialize, Deserialize))]
 pub struct UnicodeInfo {
     pub name: Option<String>,
     pub unicode: Vec<u16>,
@@ -351,6 +506,11 @@ pub struct Event {
     pub platform_code: u32,
     pub position_code: u32,
     pub usb_hid: u32,
+    /// `true` for a `KeyPress` generated by the OS auto-repeating a key
+    /// that is held down, `false` for every other event. A press of a key
+    /// whose release got lost counts as a repeat only within the OS repeat
+    /// delay of its last press.
+    pub is_repeat: bool,
     #[cfg(target_os = "windows")]
     pub extra_data: winapi::shared::basetsd::ULONG_PTR,
     #[cfg(target_os = "macos")]
@@ -0,0 +1,91 @@
+use std::time::{Duration, Instant};
+
+/// Longest auto-repeat delay the usual settings allow, used until the
+/// platform tells us the real one.
+const DEFAULT_REPEAT_DELAY: Duration = Duration::from_millis(1000);
+/// Leeway on top of the repeat delay for a busy event queue.
+const REPEAT_SLACK: Duration = Duration::from_millis(150);
+
+/// Keeps track of which keycodes are currently held down, so that a press
+/// for a key that never got released can be flagged as an auto-repeat.
+///
+/// A held key only repeats if its last press came within the repeat delay.
+/// Pressed again later, its release got lost and this is a real press.
+pub(crate) struct KeyRepeatTracker {
+    // time of the latest press or repeat of each held keycode
+    down: [Option<Instant>; 256],
+    repeat_delay: Duration,
+}
+
+impl KeyRepeatTracker {
+    pub const fn new() -> KeyRepeatTracker {
+        KeyRepeatTracker {
+            down: [None; 256],
+            repeat_delay: DEFAULT_REPEAT_DELAY,
+        }
+    }
+
+    /// Sets the delay before a held key starts repeating, as the OS has it.
+    pub fn set_repeat_delay(&mut self, delay: Duration) {
+        self.repeat_delay = delay;
+    }
+
+    /// Marks `code` as held down and returns `true` if this press is an
+    /// auto-repeat of it.
+    pub fn press(&mut self, code: u32) -> bool {
+        self.press_at(code, Instant::now())
+    }
+
+    fn press_at(&mut self, code: u32, now: Instant) -> bool {
+        let timeout = self.repeat_delay + REPEAT_SLACK;
+        match self.down.get_mut(code as usize) {
+            Some(down) => down
+                .replace(now)
+                .is_some_and(|last| now.saturating_duration_since(last) <= timeout),
+            None => false,
+        }
+    }
+
+    pub fn release(&mut self, code: u32) {
+        if let Some(down) = self.down.get_mut(code as usize) {
+            *down = None;
+        }
+    }
+
+    /// Replaces the tracked state with the keycodes the OS reports as held,
+    /// dropping keys whose release we never saw.
+    pub fn sync(&mut self, held: &[u32]) {
+        let now = Instant::now();
+        let mut down = [None; 256];
+        for code in held {
+            if let Some(slot) = down.get_mut(*code as usize) {
+                // keep when it was pressed, the next repeat is still due
+                *slot = self.down[*code as usize].or(Some(now));
+            }
+        }
+        self.down = down;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn presses_repeat_only_within_the_delay() {
+        let mut tracker = KeyRepeatTracker::new();
+        tracker.set_repeat_delay(Duration::from_millis(500));
+        let start = Instant::now();
+        let at = |ms| start + Duration::from_millis(ms);
+
+        assert!(!tracker.press_at(38, at(0)));
+        assert!(tracker.press_at(38, at(500)));
+        assert!(tracker.press_at(38, at(530)));
+        // another key isn't affected
+        assert!(!tracker.press_at(40, at(540)));
+        tracker.release(38);
+        assert!(!tracker.press_at(38, at(600)));
+        // the release got lost, much later it's a new press
+        assert!(!tracker.press_at(38, at(5_000)));
+    }
+}
@@ -0,0 +1,320 @@
+//! Plays back events captured with `listen`, for example after a round trip
+//! through serde with the `serialize` feature.
+//!
+//! ```no_run
+//! use rdev::replay::{replay, ReplayOptions};
+//! use rdev::Event;
+//!
+//! fn rehearse(events: Vec<Event>) {
+//!     let options = ReplayOptions {
+//!         speed: 2.0,
+//!         ..Default::default()
+//!     };
+//!     let handle = replay(events, options);
+//!     // handle.pause(), handle.resume() and handle.abort() from anywhere
+//!     if let Err(error) = handle.join() {
+//!         println!("Replay failed: {:?}", error);
+//!     }
+//! }
+//! ```
+use crate::rdev::{Button, Event, EventType, Key, SimulateError};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant, SystemTime};
+
+/// Where recorded mouse moves end up.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum MousePositioning {
+    /// Moves to the recorded coordinates.
+    Absolute,
+    /// Shifts every move so the first recorded position lands on `x`, `y`.
+    Relative { x: f64, y: f64 },
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct ReplayOptions {
+    /// Playback speed, 1.0 keeps the original timing and 2.0 plays twice
+    /// as fast. Zero or below plays without any delay.
+    pub speed: f64,
+    pub mouse: MousePositioning,
+}
+
+impl Default for ReplayOptions {
+    fn default() -> Self {
+        ReplayOptions {
+            speed: 1.0,
+            mouse: MousePositioning::Absolute,
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum PlayState {
+    Playing,
+    Paused,
+    Aborted,
+}
+
+struct Control {
+    state: Mutex<PlayState>,
+    changed: Condvar,
+}
+
+impl Control {
+    fn set(&self, new_state: PlayState) {
+        let mut state = self.state.lock().unwrap();
+        // an aborted replay stays aborted
+        if *state != PlayState::Aborted {
+            *state = new_state;
+        }
+        self.changed.notify_all();
+    }
+
+    /// Sleeps for `duration` of playing time, not counting pauses.
+    /// Returns false once the replay is aborted.
+    fn wait(&self, duration: Duration) -> bool {
+        let mut remaining = duration;
+        let mut state = self.state.lock().unwrap();
+        loop {
+            match *state {
+                PlayState::Aborted => return false,
+                PlayState::Paused => state = self.changed.wait(state).unwrap(),
+                PlayState::Playing if remaining == Duration::from_secs(0) => return true,
+                PlayState::Playing => {
+                    let started = Instant::now();
+                    state = self.changed.wait_timeout(state, remaining).unwrap().0;
+                    remaining = remaining.saturating_sub(started.elapsed());
+                }
+            }
+        }
+    }
+}
+
+/// Controls a replay running on its own thread.
+pub struct ReplayHandle {
+    control: Arc<Control>,
+    thread: JoinHandle<Result<(), SimulateError>>,
+}
+
+impl ReplayHandle {
+    /// Holds playback before the next event, keys pressed so far stay pressed.
+    pub fn pause(&self) {
+        self.control.set(PlayState::Paused);
+    }
+
+    pub fn resume(&self) {
+        self.control.set(PlayState::Playing);
+    }
+
+    /// Stops playback and releases every key and button the replay still holds.
+    pub fn abort(&self) {
+        self.control.set(PlayState::Aborted);
+    }
+
+    pub fn is_paused(&self) -> bool {
+        *self.control.state.lock().unwrap() == PlayState::Paused
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.thread.is_finished()
+    }
+
+    /// Waits for the replay to end. Fails if an event could not be simulated.
+    pub fn join(self) -> Result<(), SimulateError> {
+        self.thread.join().unwrap_or(Err(SimulateError))
+    }
+}
+
+/// Replays `events` through `simulate`, keeping the gaps between their
+/// timestamps. Auto-repeated key presses are skipped, the OS repeats held
+/// keys on its own, and so are events that can't be simulated.
+#[cfg(not(any(target_os = "android", target_os = "ios")))]
+pub fn replay(events: Vec<Event>, options: ReplayOptions) -> ReplayHandle {
+    replay_with(events, options, crate::simulate)
+}
+
+/// Like `replay`, but hands every event to `send` instead of simulating it.
+pub fn replay_with<F>(events: Vec<Event>, options: ReplayOptions, send: F) -> ReplayHandle
+where
+    F: FnMut(&EventType) -> Result<(), SimulateError> + Send + 'static,
+{
+    let control = Arc::new(Control {
+        state: Mutex::new(PlayState::Playing),
+        changed: Condvar::new(),
+    });
+    let thread_control = control.clone();
+    let thread = thread::spawn(move || play(events, options, &thread_control, send));
+    ReplayHandle { control, thread }
+}
+
+/// Keys and buttons pressed by the replay and not released yet.
+#[derive(Default)]
+struct Held {
+    keys: Vec<Key>,
+    buttons: Vec<Button>,
+}
+
+impl Held {
+    fn track(&mut self, event_type: &EventType) {
+        match *event_type {
+            EventType::KeyPress(key) if !self.keys.contains(&key) => self.keys.push(key),
+            EventType::KeyRelease(key) => self.keys.retain(|k| *k != key),
+            EventType::ButtonPress(button) if !self.buttons.contains(&button) => {
+                self.buttons.push(button)
+            }
+            EventType::ButtonRelease(button) => self.buttons.retain(|b| *b != button),
+            _ => {}
+        }
+    }
+
+    fn release<F>(&mut self, send: &mut F)
+    where
+        F: FnMut(&EventType) -> Result<(), SimulateError>,
+    {
+        for key in self.keys.drain(..).rev() {
+            let _ = send(&EventType::KeyRelease(key));
+        }
+        for button in self.buttons.drain(..).rev() {
+            let _ = send(&EventType::ButtonRelease(button));
+        }
+    }
+}
+
+fn play<F>(
+    events: Vec<Event>,
+    options: ReplayOptions,
+    control: &Control,
+    mut send: F,
+) -> Result<(), SimulateError>
+where
+    F: FnMut(&EventType) -> Result<(), SimulateError>,
+{
+    let mut held = Held::default();
+    let mut offset = None;
+    let mut previous: Option<SystemTime> = None;
+
+    for event in events {
+        if event.is_repeat || !event.event_type.can_simulate() {
+            continue;
+        }
+        let gap = previous
+            .and_then(|previous| event.time.duration_since(previous).ok())
+            .unwrap_or_default();
+        previous = Some(event.time);
+        let delay = if options.speed > 0.0 {
+            gap.div_f64(options.speed)
+        } else {
+            Duration::from_secs(0)
+        };
+        if !control.wait(delay) {
+            break;
+        }
+
+        let event_type = match (event.event_type, options.mouse) {
+            (EventType::MouseMove { x, y }, MousePositioning::Relative { x: to_x, y: to_y }) => {
+                let (dx, dy) = *offset.get_or_insert((to_x - x, to_y - y));
+                EventType::MouseMove {
+                    x: x + dx,
+                    y: y + dy,
+                }
+            }
+            (event_type, _) => event_type,
+        };
+        if let Err(error) = send(&event_type) {
+            held.release(&mut send);
+            return Err(error);
+        }
+        held.track(&event_type);
+    }
+
+    // aborted, or the recording stopped while something was held
+    held.release(&mut send);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn event(event_type: EventType, millis: u64) -> Event {
+        Event {
+            time: SystemTime::UNIX_EPOCH + Duration::from_millis(millis),
+            unicode: None,
+            event_type,
+            platform_code: 0,
+            position_code: 0,
+            usb_hid: 0,
+            is_repeat: false,
+            #[cfg(target_os = "windows")]
+            extra_data: 0,
+            #[cfg(target_os = "macos")]
+            extra_data: 0,
+        }
+    }
+
+    fn capture(
+        events: Vec<Event>,
+        options: ReplayOptions,
+    ) -> (ReplayHandle, Arc<Mutex<Vec<EventType>>>) {
+        let sent = Arc::new(Mutex::new(vec![]));
+        let sink = sent.clone();
+        let handle = replay_with(events, options, move |event_type| {
+            sink.lock().unwrap().push(event_type);
+            Ok(())
+        });
+        (handle, sent)
+    }
+
+    #[test]
+    fn test_relative_mouse_and_skipped_events() {
+        let mut repeat = event(EventType::KeyPress(Key::KeyA), 2);
+        repeat.is_repeat = true;
+        let events = vec![
+            event(EventType::MouseMove { x: 100.0, y: 100.0 }, 0),
+            event(EventType::KeyPress(Key::KeyA), 1),
+            repeat,
+            event(EventType::LayoutChanged { layout: 0 }, 3),
+            event(EventType::KeyRelease(Key::KeyA), 4),
+            event(EventType::MouseMove { x: 110.0, y: 90.0 }, 5),
+        ];
+        let options = ReplayOptions {
+            speed: 0.0,
+            mouse: MousePositioning::Relative { x: 10.0, y: 20.0 },
+        };
+        let (handle, sent) = capture(events, options);
+        handle.join().unwrap();
+        assert_eq!(
+            *sent.lock().unwrap(),
+            vec![
+                EventType::MouseMove { x: 10.0, y: 20.0 },
+                EventType::KeyPress(Key::KeyA),
+                EventType::KeyRelease(Key::KeyA),
+                EventType::MouseMove { x: 20.0, y: 10.0 },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_abort_releases_held_keys() {
+        let events = vec![
+            event(EventType::KeyPress(Key::ShiftLeft), 0),
+            event(EventType::ButtonPress(Button::Left), 0),
+            event(EventType::KeyPress(Key::KeyB), 60_000),
+        ];
+        let (handle, sent) = capture(events, ReplayOptions::default());
+        thread::sleep(Duration::from_millis(50));
+        handle.pause();
+        assert!(handle.is_paused());
+        handle.abort();
+        handle.join().unwrap();
+        assert_eq!(
+            *sent.lock().unwrap(),
+            vec![
+                EventType::KeyPress(Key::ShiftLeft),
+                EventType::ButtonPress(Button::Left),
+                EventType::KeyRelease(Key::ShiftLeft),
+                EventType::ButtonRelease(Button::Left),
+            ]
+        );
+    }
+}
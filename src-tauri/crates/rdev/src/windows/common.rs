@@ -1,6 +1,8 @@
-use crate::rdev::{Button, EventType, Key};
+use crate::rdev::{Button, EventType, Key, LockState};
+use crate::windows::key_state::lock_state;
 use crate::windows::keyboard::Keyboard;
 use crate::keycodes::windows::key_from_code;
+use crate::repeat::KeyRepeatTracker;
 use lazy_static::lazy_static;
 use std::convert::TryInto;
 use std::os::raw::{c_int, c_short};
@@ -27,6 +29,45 @@ pub static mut MOUSE_HOOK: HHOOK = null_mut();
 lazy_static! {
     pub(crate) static ref KEYBOARD: Mutex<Keyboard> = Mutex::new(Keyboard::new());
 }
+// Low-level hooks don't flag auto-repeat, so keep track of held virtual keys.
+pub(crate) static KEYS_DOWN: Mutex<KeyRepeatTracker> = Mutex::new(KeyRepeatTracker::new());
+
+/// Updates the held-down virtual keys and returns whether this event is an
+/// auto-repeated press of a key that was never released.
+pub fn track_repeat(event_type: &EventType, code: u16) -> bool {
+    let Ok(mut keys_down) = KEYS_DOWN.lock() else {
+        return false;
+    };
+    match event_type {
+        EventType::KeyPress(_) => keys_down.press(code.into()),
+        EventType::KeyRelease(_) => {
+            keys_down.release(code.into());
+            false
+        }
+        _ => false,
+    }
+}
+
+// Last lock state we reported, to only send actual changes.
+static LAST_LOCK_STATE: Mutex<Option<LockState>> = Mutex::new(None);
+
+/// Checks the lock state after a lock key event and returns a
+/// `LockStateChanged` if it's different from before. The hook runs before
+/// the key toggles its lock, so the change shows with the release.
+pub fn lock_state_event(event_type: &EventType) -> Option<EventType> {
+    match event_type {
+        EventType::KeyPress(Key::CapsLock | Key::NumLock | Key::ScrollLock)
+        | EventType::KeyRelease(Key::CapsLock | Key::NumLock | Key::ScrollLock) => {}
+        _ => return None,
+    }
+    let lock_state = lock_state().ok()?;
+    let mut last = LAST_LOCK_STATE.lock().ok()?;
+    if *last == Some(lock_state) {
+        return None;
+    }
+    *last = Some(lock_state);
+    Some(EventType::LockStateChanged(lock_state))
+}
 
 pub fn set_modifier(key: Key, down: bool) {
     KEYBOARD.lock().unwrap().set_modifier(key, down);
@@ -1,6 +1,6 @@
 use crate::{
     rdev::{Event, EventType, GrabError},
-    windows::common::{convert, get_scan_code, HookError, KEYBOARD},
+    windows::common::{convert, get_scan_code, track_repeat, HookError, KEYBOARD},
 };
 use std::{io::Error, ptr::null_mut, sync::Mutex, time::SystemTime};
 use winapi::{
@@ -60,6 +60,7 @@ unsafe fn raw_callback(
             } else {
                 None
             };
+            let is_repeat = track_repeat(&event_type, code);
             let event = Event {
                 event_type,
                 time: SystemTime::now(),
@@ -67,6 +68,7 @@ unsafe fn raw_callback(
                 platform_code: code as _,
                 position_code: get_scan_code(lpdata),
                 usb_hid: 0,
+                is_repeat,
                 extra_data: f_get_extra_data(lpdata),
             };
             if let Some(callback) = &mut GLOBAL_CALLBACK {
@@ -0,0 +1,55 @@
+use crate::keycodes::windows::key_from_code;
+use crate::rdev::{Key, KeyStateError, LockState};
+use crate::windows::common::KEYS_DOWN;
+use winapi::um::winuser::{
+    GetAsyncKeyState, GetKeyState, VK_CAPITAL, VK_CONTROL, VK_LBUTTON, VK_MBUTTON, VK_MENU,
+    VK_NUMLOCK, VK_RBUTTON, VK_SCROLL, VK_SHIFT, VK_XBUTTON1, VK_XBUTTON2,
+};
+
+/// Queries the key state, kept for polling like on Linux. Windows needs no
+/// connection, so this holds nothing.
+pub struct KeyStateReader;
+
+impl KeyStateReader {
+    pub fn new() -> Result<KeyStateReader, KeyStateError> {
+        Ok(KeyStateReader)
+    }
+
+    pub fn pressed_keys(&self) -> Vec<Key> {
+        pressed_keys().unwrap_or_default()
+    }
+
+    pub fn lock_state(&self) -> LockState {
+        lock_state().unwrap_or_default()
+    }
+}
+
+pub fn pressed_keys() -> Result<Vec<Key>, KeyStateError> {
+    let mut keycodes = vec![];
+    for vk in 1..=254 {
+        match vk {
+            // mouse buttons
+            VK_LBUTTON | VK_RBUTTON | VK_MBUTTON | VK_XBUTTON1 | VK_XBUTTON2 => continue,
+            // side agnostic modifiers, the sided ones are reported as well
+            VK_SHIFT | VK_CONTROL | VK_MENU => continue,
+            _ => {}
+        }
+        if unsafe { GetAsyncKeyState(vk) } as u16 & 0x8000 != 0 {
+            keycodes.push(vk as u32);
+        }
+    }
+    if let Ok(mut keys_down) = KEYS_DOWN.lock() {
+        keys_down.sync(&keycodes);
+    }
+    Ok(keycodes.into_iter().map(key_from_code).collect())
+}
+
+pub fn lock_state() -> Result<LockState, KeyStateError> {
+    // the low bit of GetKeyState is the toggle state
+    let toggled = |vk| unsafe { GetKeyState(vk) } & 1 != 0;
+    Ok(LockState {
+        caps_lock: toggled(VK_CAPITAL),
+        num_lock: toggled(VK_NUMLOCK),
+        scroll_lock: toggled(VK_SCROLL),
+    })
+}
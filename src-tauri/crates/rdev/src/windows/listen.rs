@@ -1,6 +1,9 @@
 use crate::{
     rdev::{Event, ListenError},
-    windows::common::{convert, get_scan_code, set_key_hook, set_mouse_hook, HookError},
+    windows::common::{
+        convert, get_scan_code, lock_state_event, set_key_hook, set_mouse_hook, track_repeat,
+        HookError,
+    },
 };
 use std::{os::raw::c_int, ptr::null_mut, time::SystemTime};
 use winapi::{
@@ -31,6 +34,17 @@ unsafe fn raw_callback(
     if code == HC_ACTION {
         let (opt, code) = convert(param, lpdata);
         if let Some(event_type) = opt {
+            let is_repeat = track_repeat(&event_type, code);
+            let lock_event = lock_state_event(&event_type).map(|event_type| Event {
+                event_type,
+                time: SystemTime::now(),
+                unicode: None,
+                platform_code: 0,
+                position_code: 0,
+                usb_hid: 0,
+                is_repeat: false,
+                extra_data: 0,
+            });
             let event = Event {
                 event_type,
                 time: SystemTime::now(),
@@ -38,10 +52,15 @@ unsafe fn raw_callback(
                 platform_code: code as _,
                 position_code: get_scan_code(lpdata),
                 usb_hid: 0,
+                is_repeat,
                 extra_data: f_get_extra_data(lpdata),
             };
             if let Some(callback) = &mut GLOBAL_CALLBACK {
                 callback(event);
+                // follows the key event, as on Linux
+                if let Some(lock_event) = lock_event {
+                    callback(lock_event);
+                }
             }
         }
     }
@@ -3,6 +3,7 @@ extern crate winapi;
 mod common;
 mod display;
 mod grab;
+mod key_state;
 mod keyboard;
 mod listen;
 mod simulate;
@@ -11,6 +12,7 @@ mod simulate;
 pub use crate::windows::common::*;
 pub use crate::windows::display::display_size;
 pub use crate::windows::grab::{exit_grab, grab, is_grabbed, set_event_popup, set_get_key_unicode};
+pub use crate::windows::key_state::{lock_state, pressed_keys, KeyStateReader};
 pub use crate::windows::keyboard::Keyboard;
 pub use crate::windows::listen::listen;
 pub use crate::windows::simulate::*;
@@ -189,6 +189,14 @@ pub fn simulate(event_type: &EventType) -> Result<(), SimulateError> {
                 (*y as i32 + 1) * 65535 / height,
             )
         }
+        // notifications, gestures, touch and pen input, nothing to send
+        EventType::LockStateChanged(_)
+        | EventType::LayoutChanged { .. }
+        | EventType::GestureSwipe { .. }
+        | EventType::GesturePinch { .. }
+        | EventType::GestureHold { .. }
+        | EventType::Touch { .. }
+        | EventType::PenChanged(_) => Err(SimulateError),
     }
 }
 
@@ -0,0 +1,131 @@
+use std::sync::Mutex;
+
+use tauri::{AppHandle, Emitter, Manager, WebviewWindowBuilder};
+
+use crate::app::{
+    commands::set_main_window_monitor,
+    profile,
+    recorder::RecordingStatus,
+    state::{AppState, ListeningMenu},
+};
+
+/// What the tray menu, or a second `keyviz <action>` launch, asks of the
+/// running app.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Action {
+    Toggle,
+    Start,
+    Stop,
+    Settings,
+    Monitor(String),
+    Record(RecordAction),
+    Profile(String),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RecordAction {
+    Start,
+    Stop,
+    Toggle,
+    Pause,
+    Resume,
+    // pauses a running recording, resumes a paused one
+    TogglePause,
+}
+
+impl Action {
+    /// Parses the arguments after the binary, `None` when they name no action.
+    pub fn from_args(args: &[String]) -> Result<Option<Action>, String> {
+        let args: Vec<&str> = args.iter().skip(1).map(String::as_str).collect();
+        let action = match args.as_slice() {
+            ["toggle"] => Action::Toggle,
+            ["start"] => Action::Start,
+            ["stop"] => Action::Stop,
+            ["settings"] => Action::Settings,
+            ["monitor", name] => Action::Monitor(name.to_string()),
+            ["profile", name] => Action::Profile(name.to_string()),
+            ["record", action] => Action::Record(match *action {
+                "start" => RecordAction::Start,
+                "stop" => RecordAction::Stop,
+                "toggle" => RecordAction::Toggle,
+                "pause" => RecordAction::Pause,
+                "resume" => RecordAction::Resume,
+                other => return Err(format!("unknown record action {}", other)),
+            }),
+            ["toggle" | "start" | "stop" | "settings" | "monitor" | "record" | "profile", ..] => {
+                return Err(format!("invalid arguments for {}", args[0]))
+            }
+            _ => return Ok(None),
+        };
+        Ok(Some(action))
+    }
+}
+
+pub fn run(app: &AppHandle, action: Action) {
+    match action {
+        Action::Toggle => set_listening(app, None),
+        Action::Start => set_listening(app, Some(true)),
+        Action::Stop => set_listening(app, Some(false)),
+        Action::Settings => open_settings(app),
+        Action::Monitor(name) => set_main_window_monitor(app.clone(), name),
+        Action::Record(action) => record(app, action),
+        Action::Profile(name) => {
+            if let Err(err) = profile::switch(app, &name) {
+                eprintln!("Failed to switch profile: {}", err);
+            }
+        }
+    }
+}
+
+/// Flips listening, or sets it when `listening` is given.
+fn set_listening(app: &AppHandle, listening: Option<bool>) {
+    let state = app.state::<Mutex<AppState>>();
+    let mut app_state = state.lock().unwrap();
+    if listening.is_some_and(|listening| listening == app_state.listening) {
+        return;
+    }
+    let menu = app.state::<ListeningMenu>();
+    app_state.toggle_listener(app, &menu.toggle);
+}
+
+fn record(app: &AppHandle, action: RecordAction) {
+    let state = app.state::<Mutex<AppState>>();
+    let mut app_state = state.lock().unwrap();
+    let status = app_state.recorder.status();
+    let recording = status != RecordingStatus::Idle;
+    match action {
+        RecordAction::Start | RecordAction::Toggle if !recording => {
+            if let Err(err) = app_state.start_recording(app) {
+                eprintln!("Failed to start recording: {}", err);
+            }
+        }
+        RecordAction::Stop | RecordAction::Toggle if recording => app_state.stop_recording(app),
+        RecordAction::Pause if status == RecordingStatus::Recording => {
+            app_state.pause_recording(app)
+        }
+        RecordAction::Resume | RecordAction::TogglePause if status == RecordingStatus::Paused => {
+            app_state.resume_recording(app)
+        }
+        RecordAction::TogglePause => app_state.pause_recording(app),
+        // already where it was asked to be
+        _ => {}
+    }
+}
+
+fn open_settings(app: &AppHandle) {
+    if let Some(window) = app.get_webview_window("settings") {
+        let _ = window.set_focus();
+        return;
+    }
+    let webview_url = tauri::WebviewUrl::App("index.html#/settings".into());
+    WebviewWindowBuilder::new(app, "settings", webview_url.clone())
+        .title("Keyviz")
+        .inner_size(800.0, 640.0)
+        .min_inner_size(640.0, 480.0)
+        .max_inner_size(1000.0, 800.0)
+        .maximizable(false)
+        .build()
+        .unwrap();
+
+    app.emit_to("main", "settings-window", true).unwrap();
+}
@@ -0,0 +1,345 @@
+use std::{
+    fmt,
+    str::FromStr,
+    time::{Duration, Instant},
+};
+
+use serde::{Deserialize, Serialize, Serializer};
+
+/// Modifiers that come in a left and a right key, by the name that stands
+/// for either side.
+const SIDED_MODIFIERS: [(&str, [&str; 2]); 3] = [
+    ("Shift", ["ShiftLeft", "ShiftRight"]),
+    ("Control", ["ControlLeft", "ControlRight"]),
+    ("Meta", ["MetaLeft", "MetaRight"]),
+];
+/// Keys that only modify others, in chords and for the modifiers filter.
+pub const MODIFIERS: [&str; 9] = [
+    "ShiftLeft",
+    "ShiftRight",
+    "ControlLeft",
+    "ControlRight",
+    "Alt",
+    "AltGr",
+    "MetaLeft",
+    "MetaRight",
+    "Function",
+];
+/// Chords remembered for sequences, longer ones never match.
+const MAX_SEQUENCE: usize = 8;
+/// Most time between the chords of a sequence, a longer pause starts over.
+const SEQUENCE_TIMEOUT: Duration = Duration::from_millis(1500);
+
+/// The name standing for both sides of `key`, if it's a sided modifier.
+fn either_side(key: &str) -> Option<&'static str> {
+    SIDED_MODIFIERS
+        .iter()
+        .find(|(_, sides)| sides.contains(&key))
+        .map(|(either, _)| *either)
+}
+
+/// Whether held `key` satisfies `wanted`. A generic name like "Shift" takes
+/// either side, and with `any_side` so does "ShiftLeft".
+fn key_matches(wanted: &str, key: &str, any_side: bool) -> bool {
+    if wanted == key {
+        return true;
+    }
+    let Some(side) = either_side(key) else {
+        return false;
+    };
+    wanted == side || (any_side && either_side(wanted) == Some(side))
+}
+
+/// Whether exactly the keys of `chord` are held, in any order. Left and
+/// right modifiers are told apart unless `any_side` is set or the chord
+/// names the modifier without a side.
+pub fn chord_matches(chord: &[String], pressed_keys: &[String], any_side: bool) -> bool {
+    !chord.is_empty()
+        && chord.len() == pressed_keys.len()
+        && chord.iter().all(|wanted| {
+            pressed_keys
+                .iter()
+                .any(|key| key_matches(wanted, key, any_side))
+        })
+        && pressed_keys.iter().all(|key| {
+            chord
+                .iter()
+                .any(|wanted| key_matches(wanted, key, any_side))
+        })
+}
+
+/// One or more chords pressed in a row, like "Ctrl+K, Ctrl+V". Stored as
+/// that text, a single chord as a plain list of key names as the settings
+/// window keeps it.
+#[derive(Debug, Clone, PartialEq, Default, Deserialize)]
+#[serde(try_from = "SequenceDef")]
+pub struct KeySequence {
+    pub chords: Vec<Vec<String>>,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum SequenceDef {
+    Text(String),
+    Keys(Vec<String>),
+}
+
+impl TryFrom<SequenceDef> for KeySequence {
+    type Error = String;
+
+    fn try_from(def: SequenceDef) -> Result<Self, Self::Error> {
+        match def {
+            SequenceDef::Text(text) => text.parse(),
+            SequenceDef::Keys(keys) => Ok(KeySequence { chords: vec![keys] }),
+        }
+    }
+}
+
+impl Serialize for KeySequence {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self.chords.as_slice() {
+            [chord] => chord.serialize(serializer),
+            _ => serializer.collect_str(self),
+        }
+    }
+}
+
+impl FromStr for KeySequence {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let chords = text
+            .split(',')
+            .map(|chord| {
+                chord
+                    .split('+')
+                    .map(|key| match key.trim() {
+                        "" => Err(format!("missing key in {:?}", text)),
+                        key => Ok(key_name(key)),
+                    })
+                    .collect::<Result<Vec<_>, _>>()
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(KeySequence { chords })
+    }
+}
+
+impl fmt::Display for KeySequence {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let chords: Vec<String> = self.chords.iter().map(|chord| chord.join("+")).collect();
+        write!(f, "{}", chords.join(", "))
+    }
+}
+
+impl KeySequence {
+    /// A sequence of the one chord `keys`.
+    pub fn chord(keys: &[&str]) -> Self {
+        KeySequence {
+            chords: vec![keys.iter().map(|key| key.to_string()).collect()],
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.chords.iter().all(Vec::is_empty)
+    }
+
+    pub fn keys(&self) -> impl Iterator<Item = &String> {
+        self.chords.iter().flatten()
+    }
+}
+
+/// The key name for how it's commonly written, e.g. "Ctrl" or "k", other
+/// names are taken as they are.
+fn key_name(key: &str) -> String {
+    let name = match key.to_ascii_lowercase().as_str() {
+        "ctrl" | "control" => "Control",
+        "shift" => "Shift",
+        "alt" | "option" => "Alt",
+        "meta" | "super" | "win" | "cmd" | "command" => "Meta",
+        "enter" => "Return",
+        "esc" => "Escape",
+        "space" => "Space",
+        _ => {
+            let mut chars = key.chars();
+            return match (chars.next(), chars.next()) {
+                (Some(c), None) if c.is_ascii_alphabetic() => {
+                    format!("Key{}", c.to_ascii_uppercase())
+                }
+                (Some(c), None) if c.is_ascii_digit() => format!("Num{}", c),
+                _ => key.to_string(),
+            };
+        }
+    };
+    name.to_string()
+}
+
+/// Remembers the chords pressed lately, so sequences can match on their
+/// last chord.
+#[derive(Debug, Default)]
+pub struct ChordTracker {
+    // held keys at each press of a non-modifier key and when, oldest first
+    chords: Vec<(Instant, Vec<String>)>,
+}
+
+impl ChordTracker {
+    /// Notes a fresh press of `key` at `now`, with `pressed_keys` including it.
+    pub fn press(&mut self, key: &str, pressed_keys: &[String], now: Instant) {
+        if MODIFIERS.contains(&key) {
+            return;
+        }
+        if !self.continues(now) {
+            self.chords.clear();
+        }
+        self.chords.push((now, pressed_keys.to_vec()));
+        let excess = self.chords.len().saturating_sub(MAX_SEQUENCE);
+        self.chords.drain(..excess);
+    }
+
+    /// Whether `pressed_keys` at `now` completes `sequence`, its earlier
+    /// chords being the ones pressed right before.
+    pub fn matches(
+        &self,
+        sequence: &KeySequence,
+        pressed_keys: &[String],
+        any_side: bool,
+        now: Instant,
+    ) -> bool {
+        let Some((last, earlier)) = sequence.chords.split_last() else {
+            return false;
+        };
+        if !chord_matches(last, pressed_keys, any_side) {
+            return false;
+        }
+        if earlier.is_empty() {
+            return true;
+        }
+        if earlier.len() > self.chords.len() || !self.continues(now) {
+            return false;
+        }
+        let recent = &self.chords[self.chords.len() - earlier.len()..];
+        earlier
+            .iter()
+            .zip(recent)
+            .all(|(chord, (_, held))| chord_matches(chord, held, any_side))
+    }
+
+    /// Whether a chord at `now` follows the last one closely enough to
+    /// continue a sequence. Chords are only kept while they do, so each
+    /// one followed the one before in time.
+    fn continues(&self, now: Instant) -> bool {
+        self.chords
+            .last()
+            .is_some_and(|(at, _)| now.saturating_duration_since(*at) <= SEQUENCE_TIMEOUT)
+    }
+
+    /// Forgets the chords so far, once a sequence ran.
+    pub fn clear(&mut self) {
+        self.chords.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn keys(names: &[&str]) -> Vec<String> {
+        names.iter().map(|name| name.to_string()).collect()
+    }
+
+    #[test]
+    fn chords_match_in_any_order() {
+        let chord = keys(&["ShiftLeft", "F10"]);
+        assert!(chord_matches(&chord, &keys(&["F10", "ShiftLeft"]), false));
+        assert!(!chord_matches(&chord, &keys(&["ShiftLeft"]), false));
+        assert!(!chord_matches(
+            &chord,
+            &keys(&["ShiftLeft", "F10", "KeyA"]),
+            false
+        ));
+        assert!(!chord_matches(&[], &[], false));
+    }
+
+    #[test]
+    fn sides_are_equivalent_when_asked() {
+        let chord = keys(&["ShiftLeft", "F10"]);
+        let right = keys(&["ShiftRight", "F10"]);
+        assert!(!chord_matches(&chord, &right, false));
+        assert!(chord_matches(&chord, &right, true));
+        // a generic modifier takes either side regardless
+        assert!(chord_matches(&keys(&["Shift", "F10"]), &right, false));
+        assert!(!chord_matches(
+            &keys(&["Shift", "F10"]),
+            &keys(&["Alt", "F10"]),
+            true
+        ));
+    }
+
+    #[test]
+    fn sequences_parse_and_match_in_a_row() {
+        let sequence: KeySequence = "Ctrl+K, Ctrl+V".parse().unwrap();
+        assert_eq!(
+            sequence.chords,
+            [keys(&["Control", "KeyK"]), keys(&["Control", "KeyV"])]
+        );
+        assert_eq!(sequence.to_string(), "Control+KeyK, Control+KeyV");
+        assert!("Ctrl+, V".parse::<KeySequence>().is_err());
+
+        let mut tracker = ChordTracker::default();
+        let now = Instant::now();
+        let mut press = |held: &[&str]| {
+            let held = keys(held);
+            let matched = tracker.matches(&sequence, &held, false, now);
+            tracker.press(held.last().unwrap(), &held, now);
+            matched
+        };
+        assert!(!press(&["ControlLeft"]));
+        assert!(!press(&["ControlLeft", "KeyK"]));
+        // modifiers pressed again in between don't break the sequence
+        assert!(!press(&["ControlRight"]));
+        assert!(press(&["ControlRight", "KeyV"]));
+        assert!(!press(&["ControlLeft", "KeyX"]));
+        assert!(!press(&["ControlLeft", "KeyV"]));
+    }
+
+    #[test]
+    fn sequences_time_out_between_chords() {
+        let sequence: KeySequence = "Ctrl+K, Ctrl+V".parse().unwrap();
+        let start = Instant::now();
+        let at = |ms| start + Duration::from_millis(ms);
+        let first = keys(&["ControlLeft", "KeyK"]);
+        let second = keys(&["ControlLeft", "KeyV"]);
+
+        let mut tracker = ChordTracker::default();
+        tracker.press("KeyK", &first, at(0));
+        assert!(tracker.matches(&sequence, &second, false, at(1_000)));
+        assert!(!tracker.matches(&sequence, &second, false, at(120_000)));
+
+        // a late chord starts over instead of continuing
+        tracker.press("KeyK", &first, at(120_000));
+        tracker.press("KeyV", &second, at(120_500));
+        assert_eq!(tracker.chords.len(), 2);
+        tracker.press("KeyK", &first, at(125_000));
+        assert_eq!(tracker.chords.len(), 1);
+        // single chords don't depend on earlier ones
+        let single: KeySequence = "Ctrl+V".parse().unwrap();
+        assert!(tracker.matches(&single, &second, false, at(999_000)));
+    }
+
+    #[test]
+    fn key_lists_read_as_one_chord() {
+        let sequence: KeySequence = serde_json::from_str(r#"["ShiftLeft", "F9"]"#).unwrap();
+        assert_eq!(sequence.chords, [keys(&["ShiftLeft", "F9"])]);
+        let sequence: KeySequence = serde_json::from_str(r#""Meta+Shift+1""#).unwrap();
+        assert_eq!(sequence.chords, [keys(&["Meta", "Shift", "Num1"])]);
+        // and are written back as such, longer sequences as text
+        assert_eq!(
+            serde_json::to_string(&sequence).unwrap(),
+            r#"["Meta","Shift","Num1"]"#
+        );
+        let sequence: KeySequence = "Ctrl+K, V".parse().unwrap();
+        assert_eq!(
+            serde_json::to_string(&sequence).unwrap(),
+            r#""Control+KeyK, KeyV""#
+        );
+    }
+}
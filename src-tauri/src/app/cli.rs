@@ -0,0 +1,174 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use crate::app::{
+    actions::Action,
+    export::{export_subtitles, ChordOptions, SubtitleFormat},
+    headless::{self, OutputFormat},
+    render::{render_session, RenderFormat, RenderOptions},
+    state::KeyFilter,
+    style::KeyStyle,
+};
+
+const USAGE: &str = "usage:
+  keyviz --export-subtitles <session.jsonl> [--format srt|vtt|ass] [--output <file>]
+  keyviz --render <session.jsonl> [--format png|gif|apng] [--output <path>] [--fps <n>]
+  keyviz --headless [--format json|text]
+  keyviz toggle|start|stop|settings
+  keyviz monitor <name>
+  keyviz profile <name>
+  keyviz record start|stop|toggle|pause|resume
+common: [--filter none|modifiers] [--style <key_style.json>]";
+
+/// Handles the flags that run without the overlay. Returns the exit code
+/// when one of them was given, `None` to start the app as usual.
+pub fn run(args: &[String]) -> Option<i32> {
+    if args.iter().any(|arg| arg == "--headless") {
+        return Some(match listen(args) {
+            Ok(format) => headless::run(format),
+            Err(err) => {
+                eprintln!("{}\n{}", err, USAGE);
+                1
+            }
+        });
+    }
+    // actions are for the running instance, only their spelling is ours to check
+    if let Err(err) = Action::from_args(args) {
+        eprintln!("{}\n{}", err, USAGE);
+        return Some(1);
+    }
+    let result = if args.iter().any(|arg| arg == "--export-subtitles") {
+        export(args)
+    } else if args.iter().any(|arg| arg == "--render") {
+        render(args)
+    } else {
+        return None;
+    };
+    match result {
+        Ok(written) => {
+            println!("{}", written.display());
+            Some(0)
+        }
+        Err(err) => {
+            eprintln!("{}\n{}", err, USAGE);
+            Some(1)
+        }
+    }
+}
+
+/// Every flag takes a value, `--flag value`.
+fn flags(args: &[String]) -> Result<Vec<(&str, &str)>, String> {
+    let mut flags = vec![];
+    let mut args = args.iter().skip(1);
+    while let Some(flag) = args.next() {
+        if !flag.starts_with("--") {
+            return Err(format!("unexpected argument {}", flag));
+        }
+        let value = args
+            .next()
+            .ok_or_else(|| format!("missing value for {}", flag))?;
+        flags.push((flag.as_str(), value.as_str()));
+    }
+    Ok(flags)
+}
+
+/// Applies the flags shared by every command, returns false for unknown ones.
+fn common_flag(
+    (flag, value): (&str, &str),
+    options: &mut ChordOptions,
+    style: &mut KeyStyle,
+) -> Result<bool, String> {
+    match flag {
+        "--filter" => {
+            options.filter = match value {
+                "none" => KeyFilter::None,
+                "modifiers" => KeyFilter::Modifiers,
+                other => return Err(format!("unknown filter {}", other)),
+            }
+        }
+        "--style" => {
+            let json_str = fs::read_to_string(value).map_err(|e| format!("{}: {}", value, e))?;
+            *style = KeyStyle::from_json(&json_str).map_err(|e| format!("{}: {}", value, e))?;
+        }
+        _ => return Ok(false),
+    }
+    Ok(true)
+}
+
+fn export(args: &[String]) -> Result<PathBuf, String> {
+    let mut session = None;
+    let mut format = SubtitleFormat::Srt;
+    let mut output = None;
+    let mut options = ChordOptions::default();
+    let mut style = KeyStyle::default();
+
+    for (flag, value) in flags(args)? {
+        match flag {
+            "--export-subtitles" => session = Some(PathBuf::from(value)),
+            "--format" => format = value.parse()?,
+            "--output" => output = Some(PathBuf::from(value)),
+            _ => {
+                if !common_flag((flag, value), &mut options, &mut style)? {
+                    return Err(format!("unknown argument {}", flag));
+                }
+            }
+        }
+    }
+    let session = session.ok_or("missing session file")?;
+    export_subtitles(&session, format, output.as_deref(), &options, &style)
+        .map_err(|e| format!("{}: {}", Path::new(&session).display(), e))
+}
+
+fn render(args: &[String]) -> Result<PathBuf, String> {
+    let mut session = None;
+    let mut render_options = RenderOptions::default();
+    let mut output = None;
+    let mut options = ChordOptions::default();
+    let mut style = KeyStyle::default();
+
+    for (flag, value) in flags(args)? {
+        match flag {
+            "--render" => session = Some(PathBuf::from(value)),
+            "--format" => render_options.format = value.parse::<RenderFormat>()?,
+            "--output" => output = Some(PathBuf::from(value)),
+            "--fps" => {
+                render_options.fps = value
+                    .parse()
+                    .map_err(|_| format!("invalid fps {}", value))?
+            }
+            _ => {
+                if !common_flag((flag, value), &mut options, &mut style)? {
+                    return Err(format!("unknown argument {}", flag));
+                }
+            }
+        }
+    }
+    let session = session.ok_or("missing session file")?;
+    render_session(
+        &session,
+        output.as_deref(),
+        &render_options,
+        &options,
+        &style,
+    )
+    .map_err(|e| format!("{}: {}", Path::new(&session).display(), e))
+}
+
+fn listen(args: &[String]) -> Result<OutputFormat, String> {
+    // the only flag without a value
+    let args: Vec<String> = args
+        .iter()
+        .filter(|arg| *arg != "--headless")
+        .cloned()
+        .collect();
+    let mut format = OutputFormat::Json;
+    for (flag, value) in flags(&args)? {
+        match flag {
+            "--format" => format = value.parse()?,
+            _ => return Err(format!("unknown argument {}", flag)),
+        }
+    }
+    Ok(format)
+}
@@ -1,8 +1,23 @@
-use std::sync::Mutex;
+use std::{path::Path, sync::Mutex};
 
-use tauri::{Manager, PhysicalPosition, PhysicalSize};
+use serde_json::json;
+use tauri::{Emitter, Manager, PhysicalPosition, PhysicalSize};
 
+use crate::app::chord::KeySequence;
+#[cfg(not(target_os = "macos"))]
+use crate::app::event::map_lock_state;
+use crate::app::export::{export_subtitles, ChordOptions, SubtitleFormat};
+use crate::app::focus::FocusRule;
+use crate::app::hotkey::Hotkey;
+use crate::app::privacy::{PrivacyConfig, PrivacyPause};
+use crate::app::profile::{self, ImportReport, ProfileList};
+use crate::app::recorder::RecordingStatus;
+use crate::app::redact::RedactionConfig;
+use crate::app::render::{render_session, RenderFormat, RenderOptions};
+use crate::app::replay::{Replay, ReplayStatus};
+use crate::app::server::ServerConfig;
 use crate::app::state::AppState;
+use crate::app::style::KeyStyle;
 
 #[tauri::command]
 pub fn log(message: String) {
@@ -10,10 +25,205 @@ pub fn log(message: String) {
 }
 
 #[tauri::command]
-pub fn set_toggle_shortcut(app: tauri::AppHandle, shortcut: Vec<String>) {
+pub fn set_toggle_shortcut(app: tauri::AppHandle, shortcut: KeySequence) -> Result<(), String> {
     let state = app.state::<Mutex<AppState>>();
     let mut app_state = state.lock().unwrap();
-    app_state.toggle_shortcut = shortcut;
+    let changes = [("toggleShortcut".to_string(), json!(shortcut))];
+    app_state.update_key_event_state(&app, changes.into_iter().collect())?;
+    Ok(())
+}
+
+#[tauri::command]
+pub fn set_show_key_repeats(app: tauri::AppHandle, show: bool) -> Result<(), String> {
+    let state = app.state::<Mutex<AppState>>();
+    let mut app_state = state.lock().unwrap();
+    let changes = [("showKeyRepeats".to_string(), json!(show))];
+    app_state.update_key_event_state(&app, changes.into_iter().collect())?;
+    Ok(())
+}
+
+/// Sends the current lock key state to the overlay, so it can show the
+/// lock badges before any lock key is pressed.
+#[tauri::command]
+pub fn request_lock_state(app: tauri::AppHandle) {
+    #[cfg(not(target_os = "macos"))]
+    match rdev::lock_state() {
+        Ok(lock_state) => app
+            .emit_to("main", "input-event", map_lock_state(lock_state))
+            .unwrap(),
+        Err(err) => eprintln!("Failed to query lock state: {:?}", err),
+    }
+    #[cfg(target_os = "macos")]
+    let _ = app;
+}
+
+/// Starts recording input events and returns the session file path.
+#[tauri::command]
+pub fn start_recording(app: tauri::AppHandle) -> Result<String, String> {
+    let state = app.state::<Mutex<AppState>>();
+    let mut app_state = state.lock().unwrap();
+    let path = app_state.start_recording(&app)?;
+    Ok(path.display().to_string())
+}
+
+#[tauri::command]
+pub fn stop_recording(app: tauri::AppHandle) {
+    let state = app.state::<Mutex<AppState>>();
+    let mut app_state = state.lock().unwrap();
+    app_state.stop_recording(&app);
+}
+
+#[tauri::command]
+pub fn pause_recording(app: tauri::AppHandle) {
+    let state = app.state::<Mutex<AppState>>();
+    let mut app_state = state.lock().unwrap();
+    app_state.pause_recording(&app);
+}
+
+#[tauri::command]
+pub fn resume_recording(app: tauri::AppHandle) {
+    let state = app.state::<Mutex<AppState>>();
+    let mut app_state = state.lock().unwrap();
+    app_state.resume_recording(&app);
+}
+
+#[tauri::command]
+pub fn get_recording_status(app: tauri::AppHandle) -> RecordingStatus {
+    let state = app.state::<Mutex<AppState>>();
+    let app_state = state.lock().unwrap();
+    app_state.recorder.status()
+}
+
+/// The overlay settings and style sessions are exported with.
+fn current_chord_settings(app: &tauri::AppHandle) -> (ChordOptions, KeyStyle) {
+    let state = app.state::<Mutex<AppState>>();
+    let app_state = state.lock().unwrap();
+    let config = &app_state.config;
+    (
+        ChordOptions::from(&config.key_event),
+        config.key_style.clone(),
+    )
+}
+
+/// Writes a recorded session as subtitles, grouped with the current overlay
+/// settings, and returns the path of the file.
+#[tauri::command]
+pub fn export_session(
+    app: tauri::AppHandle,
+    path: String,
+    format: SubtitleFormat,
+    output: Option<String>,
+) -> Result<String, String> {
+    let (options, style) = current_chord_settings(&app);
+    let output = output.map(std::path::PathBuf::from);
+    export_subtitles(
+        std::path::Path::new(&path),
+        format,
+        output.as_deref(),
+        &options,
+        &style,
+    )
+    .map(|written| written.to_string_lossy().into_owned())
+    .map_err(|e| e.to_string())
+}
+
+/// Renders a recorded session's keycaps to a png sequence, gif or apng with
+/// the current style, and returns the path written.
+#[tauri::command]
+pub async fn render_session_frames(
+    app: tauri::AppHandle,
+    path: String,
+    format: RenderFormat,
+    output: Option<String>,
+    fps: Option<u32>,
+) -> Result<String, String> {
+    let (options, style) = current_chord_settings(&app);
+    let render_options = RenderOptions {
+        format,
+        fps: fps.unwrap_or(RenderOptions::default().fps),
+    };
+    let output = output.map(std::path::PathBuf::from);
+    render_session(
+        std::path::Path::new(&path),
+        output.as_deref(),
+        &render_options,
+        &options,
+        &style,
+    )
+    .map(|written| written.to_string_lossy().into_owned())
+    .map_err(|e| e.to_string())
+}
+
+/// Replays a recorded session through the overlay, muting live input until
+/// it ends or is stopped.
+#[tauri::command]
+pub fn start_replay(
+    app: tauri::AppHandle,
+    path: String,
+    speed: Option<f64>,
+) -> Result<ReplayStatus, String> {
+    let state = app.state::<Mutex<AppState>>();
+    let mut app_state = state.lock().unwrap();
+    if let Some(replay) = app_state.replay.take() {
+        replay.stop();
+    }
+    let replay = Replay::start(&app, std::path::Path::new(&path), speed.unwrap_or(1.0))?;
+    let status = replay.status();
+    app_state.replay = Some(replay);
+    Ok(status)
+}
+
+#[tauri::command]
+pub fn stop_replay(app: tauri::AppHandle) {
+    let state = app.state::<Mutex<AppState>>();
+    let mut app_state = state.lock().unwrap();
+    if let Some(replay) = app_state.replay.take() {
+        replay.stop();
+    }
+}
+
+#[tauri::command]
+pub fn pause_replay(app: tauri::AppHandle) {
+    let state = app.state::<Mutex<AppState>>();
+    let app_state = state.lock().unwrap();
+    if let Some(replay) = &app_state.replay {
+        replay.pause();
+    }
+}
+
+#[tauri::command]
+pub fn resume_replay(app: tauri::AppHandle) {
+    let state = app.state::<Mutex<AppState>>();
+    let app_state = state.lock().unwrap();
+    if let Some(replay) = &app_state.replay {
+        replay.resume();
+    }
+}
+
+#[tauri::command]
+pub fn seek_replay(app: tauri::AppHandle, position_ms: u64) {
+    let state = app.state::<Mutex<AppState>>();
+    let app_state = state.lock().unwrap();
+    if let Some(replay) = &app_state.replay {
+        replay.seek(position_ms);
+    }
+}
+
+#[tauri::command]
+pub fn set_replay_speed(app: tauri::AppHandle, speed: f64) -> Result<(), String> {
+    let state = app.state::<Mutex<AppState>>();
+    let app_state = state.lock().unwrap();
+    match &app_state.replay {
+        Some(replay) => replay.set_speed(speed),
+        None => Ok(()),
+    }
+}
+
+#[tauri::command]
+pub fn get_replay_status(app: tauri::AppHandle) -> Option<ReplayStatus> {
+    let state = app.state::<Mutex<AppState>>();
+    let app_state = state.lock().unwrap();
+    app_state.replay.as_ref().map(|replay| replay.status())
 }
 
 #[tauri::command]
@@ -38,6 +248,10 @@ pub fn set_main_window_monitor(app: tauri::AppHandle, monitor_name: String) {
             app_state.monitor_name = Some(monitor_name.clone());
             app_state.monitor_scale = scale;
             app_state.monitor_position = (position.x, position.y);
+            app_state.monitor_size = (size.width, size.height);
+            let monitor_info = app_state.monitor_info();
+            app_state.recorder.record_monitor(monitor_info.clone());
+            app.emit("monitor-changed", monitor_info).unwrap();
 
             // Update window
             window
@@ -55,3 +269,177 @@ pub fn set_main_window_monitor(app: tauri::AppHandle, monitor_name: String) {
         }
     }
 }
+
+#[tauri::command]
+pub fn get_server_config(app: tauri::AppHandle) -> ServerConfig {
+    ServerConfig::load(&app)
+}
+
+/// Restarts the websocket server with new settings, saving them once it
+/// runs. Returns the port it listens on, if enabled.
+#[tauri::command]
+pub fn set_server_config(
+    app: tauri::AppHandle,
+    config: ServerConfig,
+) -> Result<Option<u16>, String> {
+    let state = app.state::<Mutex<AppState>>();
+    let mut app_state = state.lock().unwrap();
+    // the server keeps running as it was when the port can't be bound
+    app_state
+        .server
+        .apply(&app, &config)
+        .map_err(|e| format!("port {}: {}", config.port, e))?;
+    config.save(&app)?;
+    Ok(app_state.server.port())
+}
+
+#[tauri::command]
+pub fn list_profiles(app: tauri::AppHandle) -> ProfileList {
+    profile::list(&app)
+}
+
+/// Saves the current settings as a profile, replacing one by that name.
+#[tauri::command]
+pub fn save_profile(app: tauri::AppHandle, name: String) -> Result<(), String> {
+    profile::save(&app, &name)
+}
+
+#[tauri::command]
+pub fn switch_profile(app: tauri::AppHandle, name: String) -> Result<(), String> {
+    profile::switch(&app, &name)
+}
+
+#[tauri::command]
+pub fn delete_profile(app: tauri::AppHandle, name: String) -> Result<(), String> {
+    profile::delete(&app, &name)
+}
+
+/// Writes a profile to a file picked in the settings window, TOML when the
+/// path ends in `.toml` and JSON otherwise.
+#[tauri::command]
+pub fn export_profile(app: tauri::AppHandle, name: String, path: String) -> Result<(), String> {
+    profile::export(&app, &name, Path::new(&path))
+}
+
+/// Adds the profile in a file, failing on a name clash unless `overwrite`.
+/// Reports what it was saved as and the values it had to reset.
+#[tauri::command]
+pub fn import_profile(
+    app: tauri::AppHandle,
+    path: String,
+    name: Option<String>,
+    overwrite: bool,
+) -> Result<ImportReport, String> {
+    profile::import(&app, Path::new(&path), name, overwrite)
+}
+
+#[tauri::command]
+pub fn get_focus_rules(app: tauri::AppHandle) -> Vec<FocusRule> {
+    let state = app.state::<Mutex<AppState>>();
+    let app_state = state.lock().unwrap();
+    app_state.config.focus_rules.clone()
+}
+
+/// Replaces the rules run as the focused window changes, checked in order.
+#[tauri::command]
+pub fn set_focus_rules(app: tauri::AppHandle, rules: Vec<FocusRule>) -> Result<(), String> {
+    let state = app.state::<Mutex<AppState>>();
+    let mut app_state = state.lock().unwrap();
+    let mut config = app_state.config.clone();
+    config.focus_rules = rules;
+    if let Some(problem) = config.validate().pop() {
+        return Err(problem);
+    }
+    app_state.config = config;
+    app_state.config.save(&app)
+}
+
+#[tauri::command]
+pub fn get_privacy_config(app: tauri::AppHandle) -> PrivacyConfig {
+    let state = app.state::<Mutex<AppState>>();
+    let app_state = state.lock().unwrap();
+    app_state.config.privacy.clone()
+}
+
+/// Saves when to hold back input events, applied to the focused window
+/// right away.
+#[tauri::command]
+pub fn set_privacy_config(app: tauri::AppHandle, privacy: PrivacyConfig) -> Result<(), String> {
+    let state = app.state::<Mutex<AppState>>();
+    let mut app_state = state.lock().unwrap();
+    let mut config = app_state.config.clone();
+    config.privacy = privacy;
+    if let Some(problem) = config.validate().pop() {
+        return Err(problem);
+    }
+    let privacy = &config.privacy;
+    let pause = PrivacyPause {
+        window: privacy.blocks(app_state.focused_window.as_ref()),
+        // the grab watch catches up on its next check
+        grab: app_state.privacy_pause.grab && privacy.enabled && privacy.detect_grabs,
+    };
+    app_state.config = config;
+    app_state.config.save(&app)?;
+    app_state.set_privacy_pause(&app, pause);
+    Ok(())
+}
+
+/// Why input events are held back for privacy, for the overlay's indicator.
+#[tauri::command]
+pub fn get_privacy_pause(app: tauri::AppHandle) -> PrivacyPause {
+    let state = app.state::<Mutex<AppState>>();
+    let app_state = state.lock().unwrap();
+    app_state.privacy_pause
+}
+
+#[tauri::command]
+pub fn get_redaction_config(app: tauri::AppHandle) -> RedactionConfig {
+    let state = app.state::<Mutex<AppState>>();
+    let app_state = state.lock().unwrap();
+    app_state.config.redaction.clone()
+}
+
+#[tauri::command]
+pub fn set_redaction_config(
+    app: tauri::AppHandle,
+    redaction: RedactionConfig,
+) -> Result<(), String> {
+    let state = app.state::<Mutex<AppState>>();
+    let mut app_state = state.lock().unwrap();
+    let mut config = app_state.config.clone();
+    config.redaction = redaction;
+    if let Some(problem) = config.validate().pop() {
+        return Err(problem);
+    }
+    app_state.config = config;
+    app_state.config.save(&app)
+}
+
+/// Masks typed keys until turned off or Enter is pressed.
+#[tauri::command]
+pub fn set_redacting(app: tauri::AppHandle, active: bool) {
+    let state = app.state::<Mutex<AppState>>();
+    let mut app_state = state.lock().unwrap();
+    app_state.set_redacting(&app, active);
+}
+
+#[tauri::command]
+pub fn get_hotkeys(app: tauri::AppHandle) -> Vec<Hotkey> {
+    let state = app.state::<Mutex<AppState>>();
+    let app_state = state.lock().unwrap();
+    app_state.config.hotkeys.clone()
+}
+
+/// Replaces the hotkeys, refusing any with unnamed keys or unknown profiles.
+#[tauri::command]
+pub fn set_hotkeys(app: tauri::AppHandle, hotkeys: Vec<Hotkey>) -> Result<(), String> {
+    let state = app.state::<Mutex<AppState>>();
+    let mut app_state = state.lock().unwrap();
+    let mut config = app_state.config.clone();
+    config.hotkeys = hotkeys;
+    if let Some(problem) = config.validate().pop() {
+        return Err(problem);
+    }
+    app_state.config = config;
+    app_state.config.save(&app)
+}
@@ -0,0 +1,474 @@
+use std::{
+    collections::BTreeMap,
+    sync::{mpsc, Mutex},
+    thread,
+};
+
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+use tauri::{AppHandle, Emitter, Listener, Manager};
+use tauri_plugin_store::StoreExt;
+
+use crate::app::{
+    focus::{FocusAction, FocusRule},
+    hotkey::{Hotkey, HotkeyAction},
+    privacy::PrivacyConfig,
+    redact::RedactionConfig,
+    state::{AppState, KeyEventState},
+    style::{parse_color, KeyStyle},
+};
+
+/// Version of the config document written to the store.
+pub const CONFIG_VERSION: u32 = 1;
+/// Upgrades a config document one version at a time, `MIGRATIONS[n]` takes
+/// version `n` to `n + 1`.
+const MIGRATIONS: [fn(Value) -> Value; CONFIG_VERSION as usize] = [from_webview_stores];
+
+// store keys the settings window persists zustand state under
+pub const KEY_EVENT_STORE: &str = "key_event_store";
+pub const KEY_STYLE_STORE: &str = "key_style_store";
+// the app data dir is named after the identifier in tauri.conf.json
+const APP_IDENTIFIER: &str = "org.keyviz";
+
+/// Every overlay setting, kept by the backend so it can act on them without
+/// the webview. Follows the settings window as it changes them.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct Config {
+    pub version: u32,
+    // filters, history, linger, mouse and shortcuts
+    pub key_event: KeyEventState,
+    // keycaps, mouse indicator and the monitor
+    pub key_style: KeyStyle,
+    // named snapshots of the settings above, by name
+    pub profiles: BTreeMap<String, Profile>,
+    // the profile last switched to, edits since aren't saved to it
+    pub active_profile: Option<String>,
+    // checked in order as the focused window changes, the first match applies
+    pub focus_rules: Vec<FocusRule>,
+    // windows and grabs that hold back input events
+    pub privacy: PrivacyConfig,
+    // masking typed keys, e.g. tokens on stream
+    pub redaction: RedactionConfig,
+    // chords bound to actions, besides the toggle shortcut
+    pub hotkeys: Vec<Hotkey>,
+    // shortcuts naming ShiftLeft match ShiftRight too, and so on
+    pub either_side_modifiers: bool,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            version: CONFIG_VERSION,
+            key_event: KeyEventState::default(),
+            key_style: KeyStyle::default(),
+            profiles: BTreeMap::new(),
+            active_profile: None,
+            focus_rules: vec![],
+            privacy: PrivacyConfig::default(),
+            redaction: RedactionConfig::default(),
+            hotkeys: vec![],
+            either_side_modifiers: true,
+        }
+    }
+}
+
+/// A named set of settings to switch between, e.g. for streaming or pairing.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct Profile {
+    pub key_event: KeyEventState,
+    pub key_style: KeyStyle,
+}
+
+impl Profile {
+    pub fn validate(&mut self) -> Vec<String> {
+        validate_settings(&mut self.key_event, &mut self.key_style)
+    }
+}
+
+impl Config {
+    /// Reads the config from the store, migrated and validated. Before there
+    /// was one, the settings only lived in the webview stores.
+    pub fn load(app: &AppHandle) -> Config {
+        let Ok(store) = app.store("store.json") else {
+            return Config::default();
+        };
+        let config = match Config::read(|key| store.get(key)) {
+            Ok(config) => config,
+            Err(err) => {
+                // left in the store, a newer build may still read it
+                eprintln!("Failed to read config, using defaults: {}", err);
+                return Config::default();
+            }
+        };
+        if let Err(err) = config.save(app) {
+            eprintln!("Failed to save config: {}", err);
+        }
+        config
+    }
+
+    /// Reads the config straight from the store file, for when there's no
+    /// app to open the store. Nothing is written back.
+    pub fn load_file() -> Config {
+        let Some(path) = dirs::data_dir().map(|dir| dir.join(APP_IDENTIFIER).join("store.json"))
+        else {
+            return Config::default();
+        };
+        // the settings were never changed
+        if !path.exists() {
+            return Config::default();
+        }
+        let store = std::fs::read_to_string(&path)
+            .map_err(|e| e.to_string())
+            .and_then(|text| {
+                serde_json::from_str::<Map<String, Value>>(&text).map_err(|e| e.to_string())
+            });
+        match store.and_then(|store| Config::read(|key| store.get(key).cloned())) {
+            Ok(config) => config,
+            Err(err) => {
+                eprintln!(
+                    "Failed to read config from {}, using defaults: {}",
+                    path.display(),
+                    err
+                );
+                Config::default()
+            }
+        }
+    }
+
+    /// Builds the config from the store's entries looked up by `get`.
+    fn read(get: impl Fn(&str) -> Option<Value>) -> Result<Config, String> {
+        let document = get("config").unwrap_or_else(|| {
+            json!({
+                "version": 0,
+                "keyEventStore": webview_store(get(KEY_EVENT_STORE)),
+                "keyStyleStore": webview_store(get(KEY_STYLE_STORE)),
+            })
+        });
+        let mut config = Config::from_document(document)?;
+        for problem in config.validate() {
+            eprintln!("Config: {}", problem);
+        }
+        Ok(config)
+    }
+
+    /// Parses a stored document of any version up to ours.
+    pub fn from_document(mut document: Value) -> Result<Config, String> {
+        let version = document
+            .get("version")
+            .and_then(Value::as_u64)
+            .ok_or("missing version")? as usize;
+        let migrations = MIGRATIONS
+            .get(version..)
+            .ok_or_else(|| format!("version {} is newer than this build", version))?;
+        for migrate in migrations {
+            document = migrate(document);
+        }
+        serde_json::from_value(document).map_err(|e| e.to_string())
+    }
+
+    pub fn save(&self, app: &AppHandle) -> Result<(), String> {
+        let store = app.store("store.json").map_err(|e| e.to_string())?;
+        let value = serde_json::to_value(self).map_err(|e| e.to_string())?;
+        store.set("config", value);
+        Ok(())
+    }
+
+    /// Puts values the backend can't work with back to their defaults,
+    /// returning what was changed.
+    pub fn validate(&mut self) -> Vec<String> {
+        let mut problems = validate_settings(&mut self.key_event, &mut self.key_style);
+        for (name, profile) in &mut self.profiles {
+            problems.extend(
+                profile
+                    .validate()
+                    .into_iter()
+                    .map(|problem| format!("profile {}: {}", name, problem)),
+            );
+        }
+        if let Some(name) = &self.active_profile {
+            if !self.profiles.contains_key(name) {
+                problems.push(format!("unknown active profile {}", name));
+                self.active_profile = None;
+            }
+        }
+        // same as the toggle shortcut, these would never match
+        let redaction = &mut self.redaction;
+        let known = |key: &String| !key.contains('(');
+        if !redaction.shortcut.iter().all(known) || !redaction.marker.iter().all(known) {
+            problems.push("redaction keys can't be unnamed".to_string());
+            redaction.shortcut.retain(known);
+            redaction.marker.retain(known);
+        }
+        let profiles = &self.profiles;
+        self.hotkeys.retain(|hotkey| match &hotkey.action {
+            _ if hotkey.keys.is_empty() || !hotkey.keys.keys().all(known) => {
+                problems.push(format!("hotkey for {:?} needs named keys", hotkey.action));
+                false
+            }
+            HotkeyAction::Profile(name) if !profiles.contains_key(name) => {
+                problems.push(format!("hotkey for unknown profile {}", name));
+                false
+            }
+            _ => true,
+        });
+        self.focus_rules.retain(|rule| match &rule.action {
+            FocusAction::Profile(name) if !profiles.contains_key(name) => {
+                problems.push(format!("focus rule for unknown profile {}", name));
+                false
+            }
+            _ => true,
+        });
+        // a pattern without either would block every window
+        self.privacy.blocklist.retain(|pattern| {
+            let empty = pattern.class.is_none() && pattern.title.is_none();
+            if empty {
+                problems.push("privacy patterns need a class or title".to_string());
+            }
+            !empty
+        });
+
+        self.version = CONFIG_VERSION;
+        problems
+    }
+
+    /// Applies a change the settings window synced, `{key, value}` for a
+    /// top level field of one of its stores.
+    fn apply(&mut self, store_name: &str, key: &str, value: Value) -> Result<(), String> {
+        match store_name {
+            KEY_EVENT_STORE => merge(&mut self.key_event, key, value),
+            KEY_STYLE_STORE => merge(&mut self.key_style, key, value),
+            _ => Ok(()),
+        }
+    }
+}
+
+fn validate_settings(key_event: &mut KeyEventState, style: &mut KeyStyle) -> Vec<String> {
+    let mut problems = vec![];
+    let key_event_defaults = KeyEventState::default();
+    // names like "Unknown(12)" are never tracked as pressed
+    let shortcut = &mut key_event.toggle_shortcut;
+    for chord in &mut shortcut.chords {
+        chord.retain(|key| !key.contains('('));
+    }
+    shortcut.chords.retain(|chord| !chord.is_empty());
+    if shortcut.is_empty() {
+        problems.push("toggle shortcut can't be empty".to_string());
+        key_event.toggle_shortcut = key_event_defaults.toggle_shortcut;
+    }
+    if key_event.max_history == 0 {
+        problems.push("max history must be at least 1".to_string());
+        key_event.max_history = key_event_defaults.max_history;
+    }
+
+    let style_defaults = KeyStyle::default();
+    if !(style.text.size.is_finite() && style.text.size > 0.0) {
+        problems.push(format!("invalid text size {}", style.text.size));
+        style.text.size = style_defaults.text.size;
+    }
+    if !(style.appearance.animation_duration.is_finite()
+        && style.appearance.animation_duration >= 0.0)
+    {
+        problems.push(format!(
+            "invalid animation duration {}",
+            style.appearance.animation_duration
+        ));
+        style.appearance.animation_duration = style_defaults.appearance.animation_duration;
+    }
+    let colors = [
+        (&mut style.color.color, style_defaults.color.color),
+        (
+            &mut style.color.secondary_color,
+            style_defaults.color.secondary_color,
+        ),
+        (&mut style.text.color, style_defaults.text.color),
+        (&mut style.border.color, style_defaults.border.color),
+        (&mut style.background.color, style_defaults.background.color),
+    ];
+    for (color, default) in colors {
+        if parse_color(color).is_none() {
+            problems.push(format!("invalid color {}", color));
+            *color = default;
+        }
+    }
+    problems
+}
+
+fn merge<T: Serialize + DeserializeOwned>(
+    current: &mut T,
+    key: &str,
+    value: Value,
+) -> Result<(), String> {
+    let mut fields: Map<String, Value> = serde_json::to_value(&*current)
+        .and_then(serde_json::from_value)
+        .map_err(|e| e.to_string())?;
+    // runtime state, like pressed keys, syncs through the same event
+    if !fields.contains_key(key) {
+        return Ok(());
+    }
+    fields.insert(key.to_string(), value);
+    *current = serde_json::from_value(fields.into()).map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+/// Parses the state zustand persisted under a store key, stored as a string.
+fn webview_store(value: Option<Value>) -> Value {
+    value
+        .and_then(|value| serde_json::from_str(value.as_str()?).ok())
+        .unwrap_or(Value::Null)
+}
+
+/// Hands settings changed by the backend to the webview, `fields` being top
+/// level fields of the zustand store `name`. The settings window reads them
+/// back from the store, the overlay follows the sync events.
+pub fn push_to_webview(
+    app: &AppHandle,
+    name: &str,
+    fields: &Map<String, Value>,
+) -> Result<(), String> {
+    let store = app.store("store.json").map_err(|e| e.to_string())?;
+    // keep the rest of what zustand persisted, like its version
+    let mut persisted = store
+        .get(name)
+        .and_then(|value| serde_json::from_str(value.as_str()?).ok())
+        .unwrap_or_else(|| json!({ "state": {}, "version": 0 }));
+    for (key, value) in fields {
+        persisted["state"][key] = value.clone();
+        app.emit(name, json!({ "key": key, "value": value }))
+            .map_err(|e| e.to_string())?;
+    }
+    store.set(name, persisted.to_string());
+    Ok(())
+}
+
+/// Version 0 is the two webview stores as zustand persisted them,
+/// `{"state": ..., "version": ...}` each.
+fn from_webview_stores(document: Value) -> Value {
+    let mut config = json!({ "version": 1 });
+    for (store, field) in [("keyEventStore", "keyEvent"), ("keyStyleStore", "keyStyle")] {
+        // never opened settings, nothing persisted
+        let state = &document[store]["state"];
+        if !state.is_null() {
+            config[field] = state.clone();
+        }
+    }
+    config
+}
+
+/// Keeps `AppState::config` in step with the settings window.
+pub fn start_sync(app: AppHandle) {
+    let (sender, receiver) = mpsc::channel::<(&str, String)>();
+    for store_name in [KEY_EVENT_STORE, KEY_STYLE_STORE] {
+        let sender = sender.clone();
+        app.listen_any(store_name, move |event| {
+            // emitted with the state locked at times, applied on our own thread
+            let _ = sender.send((store_name, event.payload().to_string()));
+        });
+    }
+
+    thread::spawn(move || {
+        for (store_name, payload) in receiver {
+            #[derive(Deserialize)]
+            struct Update {
+                key: String,
+                value: Value,
+            }
+            let Ok(Update { key, value }) = serde_json::from_str(&payload) else {
+                continue;
+            };
+
+            let state = app.state::<Mutex<AppState>>();
+            let mut app_state = state.lock().unwrap();
+            if let Err(err) = app_state.config.apply(store_name, &key, value) {
+                eprintln!("Failed to apply {} to config: {}", key, err);
+                continue;
+            }
+            for problem in app_state.config.validate() {
+                eprintln!("Config: {}", problem);
+            }
+            if let Err(err) = app_state.config.save(&app) {
+                eprintln!("Failed to save config: {}", err);
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::app::{chord::KeySequence, state::KeyFilter};
+
+    #[test]
+    fn migrates_the_webview_stores() {
+        let document = json!({
+            "version": 0,
+            "keyEventStore": {
+                "state": { "filter": "none", "maxHistory": 3, "listening": true },
+                "version": 0,
+            },
+            "keyStyleStore": Value::Null,
+        });
+        let config = Config::from_document(document).unwrap();
+        assert_eq!(config.version, CONFIG_VERSION);
+        assert_eq!(config.key_event.filter, KeyFilter::None);
+        assert_eq!(config.key_event.max_history, 3);
+        assert_eq!(
+            config.key_event.toggle_shortcut.to_string(),
+            "ShiftLeft+F10"
+        );
+        assert_eq!(config.key_style.text.size, 32.0);
+    }
+
+    #[test]
+    fn rejects_newer_versions() {
+        let document = json!({ "version": CONFIG_VERSION + 1 });
+        assert!(Config::from_document(document).is_err());
+    }
+
+    #[test]
+    fn validate_restores_unusable_values() {
+        let mut config = Config::default();
+        config.key_event.toggle_shortcut = KeySequence::chord(&["Unknown(12)"]);
+        config.key_style.text.size = 0.0;
+        config.key_style.border.color = "red".into();
+        assert_eq!(config.validate().len(), 3);
+        assert_eq!(
+            config.key_event.toggle_shortcut.to_string(),
+            "ShiftLeft+F10"
+        );
+        assert_eq!(config.key_style.text.size, 32.0);
+        assert_eq!(config.key_style.border.color, "#1a1a1a");
+        assert!(config.validate().is_empty());
+    }
+
+    #[test]
+    fn validate_checks_profiles() {
+        let mut config = Config::default();
+        let mut profile = Profile::default();
+        profile.key_event.max_history = 0;
+        config.profiles.insert("pairing".into(), profile);
+        config.active_profile = Some("livestream".into());
+        assert_eq!(
+            config.validate(),
+            [
+                "profile pairing: max history must be at least 1",
+                "unknown active profile livestream"
+            ]
+        );
+        assert_eq!(config.profiles["pairing"].key_event.max_history, 5);
+        assert_eq!(config.active_profile, None);
+    }
+
+    #[test]
+    fn validate_drops_empty_privacy_patterns() {
+        let mut config = Config::default();
+        let patterns = config.privacy.blocklist.len();
+        config.privacy.blocklist.push(Default::default());
+        assert_eq!(
+            config.validate(),
+            ["privacy patterns need a class or title"]
+        );
+        assert_eq!(config.privacy.blocklist.len(), patterns);
+    }
+}
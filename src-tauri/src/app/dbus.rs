@@ -0,0 +1,280 @@
+use std::{
+    sync::{mpsc, Mutex},
+    thread,
+};
+
+use tauri::{AppHandle, Listener, Manager};
+use zbus::{connection, interface, object_server::SignalEmitter, Connection};
+
+use crate::app::{
+    actions::{self, Action},
+    event::InputEvent,
+    state::AppState,
+};
+
+pub const NAME: &str = "org.keyviz.Keyviz";
+pub const PATH: &str = "/org/keyviz/Keyviz";
+
+/// What the D-Bus interface reads and drives, the running app outside of tests.
+pub trait Controller: Send + Sync + 'static {
+    fn run(&self, action: Action);
+    fn listening(&self) -> bool;
+    fn monitor(&self) -> Option<String>;
+}
+
+impl Controller for AppHandle {
+    fn run(&self, action: Action) {
+        actions::run(self, action);
+    }
+
+    fn listening(&self) -> bool {
+        self.state::<Mutex<AppState>>().lock().unwrap().listening
+    }
+
+    fn monitor(&self) -> Option<String> {
+        let state = self.state::<Mutex<AppState>>();
+        let app_state = state.lock().unwrap();
+        app_state.monitor_name.clone()
+    }
+}
+
+pub struct Keyviz<C> {
+    controller: C,
+    // every keystroke on the session bus is opt-in
+    key_events: bool,
+}
+
+#[interface(name = "org.keyviz.Keyviz")]
+impl<C: Controller> Keyviz<C> {
+    fn toggle(&self) {
+        self.controller.run(Action::Toggle);
+    }
+
+    fn start(&self) {
+        self.controller.run(Action::Start);
+    }
+
+    fn stop(&self) {
+        self.controller.run(Action::Stop);
+    }
+
+    fn set_monitor(&self, name: String) {
+        self.controller.run(Action::Monitor(name));
+    }
+
+    fn open_settings(&self) {
+        self.controller.run(Action::Settings);
+    }
+
+    #[zbus(property)]
+    fn listening(&self) -> bool {
+        self.controller.listening()
+    }
+
+    // empty until the overlay picked one
+    #[zbus(property)]
+    fn monitor(&self) -> String {
+        self.controller.monitor().unwrap_or_default()
+    }
+
+    /// Whether `KeyEvent` signals are sent, off until a client turns it on.
+    #[zbus(property)]
+    fn key_events(&self) -> bool {
+        self.key_events
+    }
+
+    #[zbus(property)]
+    fn set_key_events(&mut self, enabled: bool) {
+        self.key_events = enabled;
+    }
+
+    #[zbus(signal, name = "ListeningChanged")]
+    async fn emit_listening_changed(
+        emitter: &SignalEmitter<'_>,
+        listening: bool,
+    ) -> zbus::Result<()>;
+
+    #[zbus(signal, name = "KeyEvent")]
+    async fn emit_key_event(
+        emitter: &SignalEmitter<'_>,
+        name: &str,
+        pressed: bool,
+        repeat: u32,
+    ) -> zbus::Result<()>;
+}
+
+/// Serves the interface at `PATH` under the well-known `NAME` on the bus
+/// `builder` connects to.
+pub async fn serve<C: Controller>(
+    builder: connection::Builder<'_>,
+    controller: C,
+) -> zbus::Result<Connection> {
+    let keyviz = Keyviz {
+        controller,
+        key_events: false,
+    };
+    builder.name(NAME)?.serve_at(PATH, keyviz)?.build().await
+}
+
+enum Change {
+    Listening(bool),
+    Monitor,
+    Key(String, bool, u32),
+}
+
+/// Publishes the app on the session bus, mirroring its changes as signals.
+pub fn start(app: AppHandle) -> zbus::Result<()> {
+    let (connection, iface) = zbus::block_on(async {
+        let connection = serve(connection::Builder::session()?, app.clone()).await?;
+        let iface = connection
+            .object_server()
+            .interface::<_, Keyviz<AppHandle>>(PATH)
+            .await?;
+        zbus::Result::Ok((connection, iface))
+    })?;
+
+    // signals go out from their own thread, emitters shouldn't wait on the bus
+    let (sender, receiver) = mpsc::channel();
+    let listening_sender = sender.clone();
+    app.listen_any("listening-toggle", move |event| {
+        if let Ok(listening) = serde_json::from_str(event.payload()) {
+            let _ = listening_sender.send(Change::Listening(listening));
+        }
+    });
+    let monitor_sender = sender.clone();
+    app.listen_any("monitor-changed", move |_| {
+        let _ = monitor_sender.send(Change::Monitor);
+    });
+    app.listen_any("input-event", move |event| {
+        if let Ok(InputEvent::KeyEvent {
+            pressed,
+            name,
+            repeat,
+        }) = serde_json::from_str(event.payload())
+        {
+            let _ = sender.send(Change::Key(name, pressed, repeat));
+        }
+    });
+
+    thread::spawn(move || {
+        // keeps the name owned as long as we run
+        let _connection = connection;
+        let emitter = iface.signal_emitter();
+        for change in receiver {
+            let sent = zbus::block_on(async {
+                match change {
+                    Change::Listening(listening) => {
+                        iface.get().await.listening_changed(emitter).await?;
+                        Keyviz::<AppHandle>::emit_listening_changed(emitter, listening).await
+                    }
+                    Change::Monitor => iface.get().await.monitor_changed(emitter).await,
+                    Change::Key(name, pressed, repeat) => {
+                        if !iface.get().await.key_events {
+                            return Ok(());
+                        }
+                        Keyviz::<AppHandle>::emit_key_event(emitter, &name, pressed, repeat).await
+                    }
+                }
+            });
+            if let Err(err) = sent {
+                eprintln!("Failed to emit D-Bus signal: {}", err);
+            }
+        }
+    });
+    println!("🔌 D-Bus service {} on the session bus", NAME);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io::{BufRead, BufReader},
+        process::{Child, Command, Stdio},
+        sync::Arc,
+    };
+
+    use zbus::{blocking, proxy::CacheProperties};
+
+    use super::*;
+
+    #[derive(Default)]
+    struct FakeController {
+        actions: Mutex<Vec<Action>>,
+    }
+
+    impl Controller for Arc<FakeController> {
+        fn run(&self, action: Action) {
+            self.actions.lock().unwrap().push(action);
+        }
+
+        fn listening(&self) -> bool {
+            !self.actions.lock().unwrap().contains(&Action::Stop)
+        }
+
+        fn monitor(&self) -> Option<String> {
+            Some("HDMI-1".into())
+        }
+    }
+
+    // kills the bus even when an assertion fails
+    struct Daemon(Child);
+
+    impl Drop for Daemon {
+        fn drop(&mut self) {
+            let _ = self.0.kill();
+            let _ = self.0.wait();
+        }
+    }
+
+    #[test]
+    fn serves_on_a_private_bus() {
+        let Ok(child) = Command::new("dbus-daemon")
+            .args(["--session", "--nofork", "--print-address"])
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+        else {
+            eprintln!("dbus-daemon not installed, skipping");
+            return;
+        };
+        let mut daemon = Daemon(child);
+        let mut address = String::new();
+        BufReader::new(daemon.0.stdout.take().unwrap())
+            .read_line(&mut address)
+            .unwrap();
+        let address = address.trim();
+
+        let controller = Arc::new(FakeController::default());
+        let _service = zbus::block_on(serve(
+            connection::Builder::address(address).unwrap(),
+            controller.clone(),
+        ))
+        .unwrap();
+
+        let client = blocking::connection::Builder::address(address)
+            .unwrap()
+            .build()
+            .unwrap();
+        // properties are read fresh, we don't signal changes here
+        let proxy = blocking::proxy::Builder::<blocking::Proxy>::new(&client)
+            .destination(NAME)
+            .unwrap()
+            .path(PATH)
+            .unwrap()
+            .interface(NAME)
+            .unwrap()
+            .cache_properties(CacheProperties::No)
+            .build()
+            .unwrap();
+        assert!(proxy.get_property::<bool>("Listening").unwrap());
+        proxy.call_method("Stop", &()).unwrap();
+        proxy.call_method("SetMonitor", &("DP-2",)).unwrap();
+        assert!(!proxy.get_property::<bool>("Listening").unwrap());
+        assert_eq!(proxy.get_property::<String>("Monitor").unwrap(), "HDMI-1");
+        assert!(!proxy.get_property::<bool>("KeyEvents").unwrap());
+        assert_eq!(
+            *controller.actions.lock().unwrap(),
+            [Action::Stop, Action::Monitor("DP-2".into())]
+        );
+    }
+}
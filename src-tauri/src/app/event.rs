@@ -1,21 +1,73 @@
-use std::{sync::Mutex, thread};
+use std::{
+    sync::Mutex,
+    thread,
+    time::{Duration, Instant},
+};
 
-use rdev::{listen, Button, EventType};
-use serde::Serialize;
+use rdev::{listen, Button, EventType, LockState, PenState};
+use serde::{Deserialize, Serialize};
 use tauri::{menu::MenuItem, AppHandle, Emitter, Manager, Wry};
 
-use crate::app::state::AppState;
+use crate::app::{
+    hotkey::{self, HotkeyAction},
+    state::AppState,
+};
 
-#[derive(Debug, Clone, Serialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(tag = "type")]
 pub enum InputEvent {
-    KeyEvent { pressed: bool, name: String },
-    MouseButtonEvent { pressed: bool, button: MouseButton },
-    MouseMoveEvent { x: f64, y: f64 },
-    MouseWheelEvent { delta_x: i64, delta_y: i64 },
+    KeyEvent {
+        pressed: bool,
+        name: String,
+        repeat: u32,
+    },
+    MouseButtonEvent {
+        pressed: bool,
+        button: MouseButton,
+    },
+    MouseMoveEvent {
+        x: f64,
+        y: f64,
+    },
+    MouseWheelEvent {
+        delta_x: i64,
+        delta_y: i64,
+    },
+    LockStateEvent {
+        caps_lock: bool,
+        num_lock: bool,
+        scroll_lock: bool,
+    },
+    LayoutChangeEvent {
+        name: String,
+    },
+    GestureEvent {
+        gesture: Gesture,
+        phase: GesturePhase,
+        fingers: u32,
+        delta_x: f64,
+        delta_y: f64,
+        scale: f64,
+    },
+    TouchEvent {
+        id: u32,
+        phase: TouchPhase,
+        x: f64,
+        y: f64,
+    },
+    PenEvent {
+        x: f64,
+        y: f64,
+        pressure: f64,
+        tilt_x: f64,
+        tilt_y: f64,
+        contact: bool,
+        eraser: bool,
+        barrel_buttons: u8,
+    },
 }
 
-#[derive(Debug, Clone, Serialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum MouseButton {
     Left,
     Right,
@@ -23,6 +75,45 @@ pub enum MouseButton {
     Other,
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum Gesture {
+    Swipe,
+    Pinch,
+    Hold,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum GesturePhase {
+    Begin,
+    Update,
+    End,
+    Cancel,
+}
+
+pub fn map_gesture_phase(phase: rdev::GesturePhase) -> GesturePhase {
+    match phase {
+        rdev::GesturePhase::Begin => GesturePhase::Begin,
+        rdev::GesturePhase::Update => GesturePhase::Update,
+        rdev::GesturePhase::End => GesturePhase::End,
+        rdev::GesturePhase::Cancel => GesturePhase::Cancel,
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum TouchPhase {
+    Down,
+    Move,
+    Up,
+}
+
+pub fn map_touch_phase(phase: rdev::TouchPhase) -> TouchPhase {
+    match phase {
+        rdev::TouchPhase::Down => TouchPhase::Down,
+        rdev::TouchPhase::Move => TouchPhase::Move,
+        rdev::TouchPhase::Up => TouchPhase::Up,
+    }
+}
+
 pub fn map_mouse_button(button: Button) -> MouseButton {
     match button {
         Button::Left => MouseButton::Left,
@@ -32,106 +123,395 @@ pub fn map_mouse_button(button: Button) -> MouseButton {
     }
 }
 
+pub fn map_lock_state(lock_state: LockState) -> InputEvent {
+    InputEvent::LockStateEvent {
+        caps_lock: lock_state.caps_lock,
+        num_lock: lock_state.num_lock,
+        scroll_lock: lock_state.scroll_lock,
+    }
+}
+
+pub fn map_pen_state(pen: PenState, (x, y): (f64, f64)) -> InputEvent {
+    InputEvent::PenEvent {
+        x,
+        y,
+        pressure: pen.pressure,
+        tilt_x: pen.tilt_x,
+        tilt_y: pen.tilt_y,
+        contact: pen.contact,
+        eraser: pen.eraser,
+        barrel_buttons: pen.barrel_buttons,
+    }
+}
+
+/// Converts a physical screen position to the main window's logical coordinates.
+fn to_logical(app_state: &AppState, x: f64, y: f64) -> (f64, f64) {
+    #[cfg(target_os = "macos")]
+    return (
+        x - app_state.monitor_position.0 as f64,
+        y - app_state.monitor_position.1 as f64,
+    );
+
+    #[cfg(not(target_os = "macos"))]
+    {
+        let scale = app_state.monitor_scale;
+        let (offset_x, offset_y) = app_state.monitor_position;
+        ((x - offset_x as f64) / scale, (y - offset_y as f64) / scale)
+    }
+}
+
+/// Tracks pressed keys and the toggle shortcut for a raw event, then maps it
+/// to what the overlay should get, if anything. `on_stuck_key` runs when a key
+/// we think is held is pressed again, `on_toggle` when the shortcut completes
+/// and `on_hotkey` when a hotkey's chord does.
+pub fn process_event(
+    app_state: &mut AppState,
+    event: rdev::Event,
+    mut on_stuck_key: impl FnMut(&mut AppState),
+    mut on_toggle: impl FnMut(&mut AppState),
+    mut on_hotkey: impl FnMut(&mut AppState, HotkeyAction),
+) -> Option<InputEvent> {
+    // track pressed keys
+    if let EventType::KeyPress(key) = event.event_type {
+        let key_name = format!("{:?}", key);
+        // If the name contains parenthesis (like "RawKey(123)", "Unknown()"), ignore it.
+        if key_name.contains('(') {
+            return None;
+        }
+        // auto-repeat of a held key, only shown as a counter if enabled
+        if event.is_repeat {
+            if app_state.config.key_event.show_key_repeats && app_state.emitting() {
+                let count = app_state.repeat_counts.entry(key_name.clone()).or_default();
+                *count += 1;
+                return Some(InputEvent::KeyEvent {
+                    pressed: true,
+                    name: key_name,
+                    repeat: *count,
+                });
+            }
+            return None;
+        }
+        app_state.repeat_counts.remove(&key_name);
+        // a fresh press of a key we think is held means a release got lost
+        if app_state.pressed_keys.contains(&key_name) {
+            on_stuck_key(app_state);
+        }
+        // record key as pressed
+        if !app_state.pressed_keys.contains(&key_name) {
+            app_state.pressed_keys.push(key_name.clone());
+        }
+        // check if toggle shortcut is pressed, in any order
+        let any_side = app_state.config.either_side_modifiers;
+        let now = Instant::now();
+        let toggled = app_state.chords.matches(
+            &app_state.config.key_event.toggle_shortcut,
+            &app_state.pressed_keys,
+            any_side,
+            now,
+        );
+        if toggled {
+            on_toggle(app_state);
+        }
+        // keys taken by the toggle shortcut don't run hotkeys as well
+        let hotkeys: Vec<HotkeyAction> = app_state
+            .config
+            .hotkeys
+            .iter()
+            .filter(|hotkey| {
+                !toggled
+                    && app_state.chords.matches(
+                        &hotkey.keys,
+                        &app_state.pressed_keys,
+                        any_side,
+                        now,
+                    )
+            })
+            .map(|hotkey| hotkey.action.clone())
+            .collect();
+        if !toggled && hotkeys.is_empty() {
+            app_state
+                .chords
+                .press(&key_name, &app_state.pressed_keys, now);
+        } else {
+            // a sequence doesn't run again on its last chord alone
+            app_state.chords.clear();
+        }
+        for action in hotkeys {
+            on_hotkey(app_state, action);
+        }
+    } else if let EventType::KeyRelease(key) = event.event_type {
+        let key_name = format!("{:?}", key);
+        if key_name.contains('(') {
+            return None;
+        }
+        // already released by the key state sync, don't release it twice
+        if !app_state.pressed_keys.contains(&key_name) {
+            return None;
+        }
+        // remove key from pressed keys
+        app_state.pressed_keys.retain(|k| k != &key_name);
+        app_state.repeat_counts.remove(&key_name);
+    }
+
+    // emit event if listening, no replay owns the overlay and no secret is typed
+    if !app_state.emitting() {
+        return None;
+    }
+    match event.event_type {
+        EventType::KeyPress(key) => Some(InputEvent::KeyEvent {
+            pressed: true,
+            name: format!("{:?}", key),
+            repeat: 0,
+        }),
+        EventType::KeyRelease(key) => Some(InputEvent::KeyEvent {
+            pressed: false,
+            name: format!("{:?}", key),
+            repeat: 0,
+        }),
+        EventType::ButtonPress(button) => Some(InputEvent::MouseButtonEvent {
+            pressed: true,
+            button: map_mouse_button(button),
+        }),
+        EventType::ButtonRelease(button) => Some(InputEvent::MouseButtonEvent {
+            button: map_mouse_button(button),
+            pressed: false,
+        }),
+        EventType::MouseMove { x, y } => {
+            // Convert Physical -> Logical
+            let (logical_x, logical_y) = to_logical(app_state, x, y);
+            Some(InputEvent::MouseMoveEvent {
+                x: logical_x,
+                y: logical_y,
+            })
+        }
+        EventType::Wheel { delta_x, delta_y } => {
+            Some(InputEvent::MouseWheelEvent { delta_x, delta_y })
+        }
+        EventType::LockStateChanged(lock_state) => Some(map_lock_state(lock_state)),
+        EventType::LayoutChanged { layout } => {
+            rdev::layout_name(layout).map(|name| InputEvent::LayoutChangeEvent { name })
+        }
+        EventType::GestureSwipe {
+            phase,
+            fingers,
+            delta_x,
+            delta_y,
+        } => Some(InputEvent::GestureEvent {
+            gesture: Gesture::Swipe,
+            phase: map_gesture_phase(phase),
+            fingers,
+            delta_x,
+            delta_y,
+            scale: 1.0,
+        }),
+        EventType::GesturePinch {
+            phase,
+            fingers,
+            delta_x,
+            delta_y,
+            scale,
+            ..
+        } => Some(InputEvent::GestureEvent {
+            gesture: Gesture::Pinch,
+            phase: map_gesture_phase(phase),
+            fingers,
+            delta_x,
+            delta_y,
+            scale,
+        }),
+        EventType::GestureHold { phase, fingers } => Some(InputEvent::GestureEvent {
+            gesture: Gesture::Hold,
+            phase: map_gesture_phase(phase),
+            fingers,
+            delta_x: 0.0,
+            delta_y: 0.0,
+            scale: 1.0,
+        }),
+        EventType::Touch { id, phase, x, y } => {
+            let (x, y) = to_logical(app_state, x, y);
+            Some(InputEvent::TouchEvent {
+                id,
+                phase: map_touch_phase(phase),
+                x,
+                y,
+            })
+        }
+        EventType::PenChanged(pen) => Some(map_pen_state(pen, to_logical(app_state, pen.x, pen.y))),
+    }
+}
+
 pub fn start_listener(app_handle: AppHandle, toggle_menu_item: MenuItem<Wry>) {
     thread::spawn(move || {
         println!("Starting global input listener...");
+        #[cfg(not(target_os = "macos"))]
+        let key_state = key_state_reader();
 
         if let Err(err) = listen(move |event| {
             // get app state
             let state = app_handle.state::<Mutex<AppState>>();
             let mut app_state = state.lock().unwrap();
 
-            // track pressed keys
-            if let EventType::KeyPress(key) = event.event_type {
-                let key_name = format!("{:?}", key);
-                // If the name contains parenthesis (like "RawKey(123)", "Unknown()"), ignore it.
-                if key_name.contains('(') {
-                    return;
-                }
-                // if key is already marked as pressed, ignore repeat
-                if app_state.pressed_keys.contains(&key_name) {
-                    return;
+            let toggle = |app_state: &mut AppState| {
+                app_state.toggle_listener(&app_handle, &toggle_menu_item);
+
+                if !app_state.listening {
+                    // emit key releases for all pressed keys
+                    let pressed_keys = app_state.pressed_keys.clone();
+                    app_state.emit_releases(&app_handle, pressed_keys);
                 }
-                // record key as pressed
-                app_state.pressed_keys.push(key_name);
-                // check if toggle shortcut is pressed
-                if app_state.toggle_shortcut == app_state.pressed_keys {
-                    app_state.toggle_listener(&app_handle, &toggle_menu_item);
-
-                    if !app_state.listening {
-                        // emit key releases for all pressed keys
-                        for key_name in &app_state.pressed_keys {
-                            app_handle
-                                .emit_to(
-                                    "main",
-                                    "input-event",
-                                    InputEvent::KeyEvent {
-                                        pressed: false,
-                                        name: key_name.clone(),
-                                    },
-                                )
-                                .unwrap()
-                        }
+            };
+            let input_event = process_event(
+                &mut app_state,
+                event,
+                |_app_state| {
+                    #[cfg(not(target_os = "macos"))]
+                    if let Some(key_state) = &key_state {
+                        _app_state.sync_pressed_keys(&app_handle, key_state);
                     }
+                },
+                toggle,
+                |app_state, action| match action {
+                    HotkeyAction::ToggleListening => toggle(app_state),
+                    // the others take the state themselves, off this thread
+                    action => {
+                        let app_handle = app_handle.clone();
+                        thread::spawn(move || hotkey::run(&app_handle, action));
+                    }
+                },
+            );
+
+            if let Some(input_event) = input_event {
+                // the shortcuts above saw the real keys, clients may not
+                let shown = app_state.redact(&app_handle, input_event.clone());
+                if app_state.config.redaction.redact_recordings {
+                    app_state.recorder.record(&shown);
+                } else {
+                    app_state.recorder.record(&input_event);
                 }
-            } else if let EventType::KeyRelease(key) = event.event_type {
-                let key_name = format!("{:?}", key);
-                if key_name.contains('(') {
-                    return;
-                }
-                // remove key from pressed keys
-                app_state.pressed_keys.retain(|k| k != &key_name);
+                app_handle.emit("input-event", shown).unwrap();
             }
+        }) {
+            eprintln!("rdev listen failed: {:?}", err);
+        }
+    });
+}
 
-            // emit event if listening
-            if !app_state.listening {
+/// How often pressed keys are checked against the OS key state.
+#[cfg(not(target_os = "macos"))]
+const KEY_STATE_SYNC_INTERVAL: Duration = Duration::from_secs(1);
+
+#[cfg(not(target_os = "macos"))]
+pub fn start_key_state_sync(app_handle: AppHandle) {
+    thread::spawn(move || {
+        let key_state = match rdev::KeyStateReader::new() {
+            Ok(key_state) => key_state,
+            Err(err) => {
+                eprintln!("Key state sync disabled: {:?}", err);
                 return;
             }
-            let input_event = match event.event_type {
-                EventType::KeyPress(key) => Some(InputEvent::KeyEvent {
-                    pressed: true,
-                    name: format!("{:?}", key),
-                }),
-                EventType::KeyRelease(key) => Some(InputEvent::KeyEvent {
-                    pressed: false,
-                    name: format!("{:?}", key),
-                }),
-                EventType::ButtonPress(button) => Some(InputEvent::MouseButtonEvent {
-                    pressed: true,
-                    button: map_mouse_button(button),
-                }),
-                EventType::ButtonRelease(button) => Some(InputEvent::MouseButtonEvent {
-                    button: map_mouse_button(button),
-                    pressed: false,
-                }),
-                EventType::MouseMove { x, y } => {
-                    // Convert Physical -> Logical
-                    #[cfg(target_os = "macos")]
-                    let (logical_x, logical_y) = (
-                        x - app_state.monitor_position.0 as f64,
-                        y - app_state.monitor_position.1 as f64,
-                    );
+        };
+        loop {
+            thread::sleep(KEY_STATE_SYNC_INTERVAL);
 
-                    #[cfg(not(target_os = "macos"))]
-                    let (logical_x, logical_y) = {
-                        let scale = app_state.monitor_scale;
-                        let (offset_x, offset_y) = app_state.monitor_position;
-                        ((x - offset_x as f64) / scale, (y - offset_y as f64) / scale)
-                    };
-
-                    Some(InputEvent::MouseMoveEvent {
-                        x: logical_x,
-                        y: logical_y,
-                    })
-                }
-                EventType::Wheel { delta_x, delta_y } => {
-                    Some(InputEvent::MouseWheelEvent { delta_x, delta_y })
-                }
-            };
-
-            app_handle.emit("input-event", input_event).unwrap();
-        }) {
-            eprintln!("rdev listen failed: {:?}", err);
+            let state = app_handle.state::<Mutex<AppState>>();
+            let mut app_state = state.lock().unwrap();
+            // nothing can be stuck
+            if app_state.pressed_keys.is_empty() {
+                continue;
+            }
+            app_state.sync_pressed_keys(&app_handle, &key_state);
         }
     });
 }
+
+/// Connects to the key state for the listener thread, to check on stuck
+/// keys without reconnecting.
+#[cfg(not(target_os = "macos"))]
+pub fn key_state_reader() -> Option<rdev::KeyStateReader> {
+    rdev::KeyStateReader::new()
+        .map_err(|err| eprintln!("Failed to query pressed keys: {:?}", err))
+        .ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::SystemTime;
+
+    use rdev::Key;
+
+    use super::*;
+    use crate::app::{config::Config, hotkey::Hotkey};
+
+    fn event(event_type: EventType) -> rdev::Event {
+        rdev::Event {
+            time: SystemTime::now(),
+            unicode: None,
+            event_type,
+            platform_code: 0,
+            position_code: 0,
+            usb_hid: 0,
+            is_repeat: false,
+            #[cfg(target_os = "windows")]
+            extra_data: 0,
+            #[cfg(target_os = "macos")]
+            extra_data: 0,
+        }
+    }
+
+    /// Presses `chord` and lets go of it, returning how often it toggled
+    /// listening and which hotkeys ran.
+    fn press(app_state: &mut AppState, chord: &[Key]) -> (u32, Vec<HotkeyAction>) {
+        let mut toggles = 0;
+        let mut hotkeys = vec![];
+        let events = chord
+            .iter()
+            .map(|&key| EventType::KeyPress(key))
+            .chain(chord.iter().rev().map(|&key| EventType::KeyRelease(key)));
+        for event_type in events {
+            process_event(
+                app_state,
+                event(event_type),
+                |_| {},
+                |_| toggles += 1,
+                |_, action| hotkeys.push(action),
+            );
+        }
+        (toggles, hotkeys)
+    }
+
+    #[test]
+    fn toggle_shortcut_can_be_a_sequence() {
+        let mut config = Config::default();
+        config.key_event.toggle_shortcut = "Ctrl+K, Ctrl+V".parse().unwrap();
+        let mut app_state = AppState::detached(config);
+
+        assert_eq!(press(&mut app_state, &[Key::ControlLeft, Key::KeyV]).0, 0);
+        assert_eq!(press(&mut app_state, &[Key::ControlLeft, Key::KeyK]).0, 0);
+        assert_eq!(press(&mut app_state, &[Key::ControlLeft, Key::KeyV]).0, 1);
+        // the last chord alone doesn't toggle again
+        assert_eq!(press(&mut app_state, &[Key::ControlLeft, Key::KeyV]).0, 0);
+    }
+
+    #[test]
+    fn toggle_shortcut_wins_over_a_hotkey_on_the_same_keys() {
+        let config = Config {
+            hotkeys: vec![
+                Hotkey {
+                    keys: "ShiftLeft+F10".parse().unwrap(),
+                    action: HotkeyAction::ToggleListening,
+                },
+                Hotkey {
+                    keys: "ShiftLeft+F9".parse().unwrap(),
+                    action: HotkeyAction::ToggleListening,
+                },
+            ],
+            ..Config::default()
+        };
+        let mut app_state = AppState::detached(config);
+
+        let (toggles, hotkeys) = press(&mut app_state, &[Key::ShiftLeft, Key::F10]);
+        assert_eq!((toggles, hotkeys), (1, vec![]));
+        let (toggles, hotkeys) = press(&mut app_state, &[Key::ShiftLeft, Key::F9]);
+        assert_eq!((toggles, hotkeys), (0, vec![HotkeyAction::ToggleListening]));
+    }
+}
@@ -0,0 +1,534 @@
+use std::{
+    fmt::Write as _,
+    fs, io,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use serde::Deserialize;
+
+use crate::app::{
+    chord::MODIFIERS,
+    event::InputEvent,
+    recorder::{read_session, SessionLine},
+    state::{KeyEventState, KeyFilter},
+    style::{parse_color, KeyStyle},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SubtitleFormat {
+    Srt,
+    Vtt,
+    Ass,
+}
+
+impl SubtitleFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            SubtitleFormat::Srt => "srt",
+            SubtitleFormat::Vtt => "vtt",
+            SubtitleFormat::Ass => "ass",
+        }
+    }
+}
+
+impl FromStr for SubtitleFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "srt" => Ok(SubtitleFormat::Srt),
+            "vtt" | "webvtt" => Ok(SubtitleFormat::Vtt),
+            "ass" => Ok(SubtitleFormat::Ass),
+            _ => Err(format!("unknown subtitle format {}", s)),
+        }
+    }
+}
+
+/// The overlay settings that decide which keys end up in a chord.
+#[derive(Debug, Clone)]
+pub struct ChordOptions {
+    pub filter: KeyFilter,
+    pub allowed_keys: Vec<String>,
+    pub linger_ms: u64,
+    pub show_mouse_events: bool,
+}
+
+impl Default for ChordOptions {
+    fn default() -> Self {
+        ChordOptions::from(&KeyEventState::default())
+    }
+}
+
+impl From<&KeyEventState> for ChordOptions {
+    fn from(state: &KeyEventState) -> Self {
+        ChordOptions {
+            filter: state.filter,
+            allowed_keys: state.allowed_keys.clone(),
+            linger_ms: state.linger_duration_ms,
+            show_mouse_events: state.show_mouse_events,
+        }
+    }
+}
+
+/// A shortcut as the overlay shows it, between `start_ms` and `end_ms`
+/// of the session.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Chord {
+    pub start_ms: u64,
+    pub end_ms: u64,
+    // key labels, with their press count past the first
+    pub keys: Vec<String>,
+}
+
+impl Chord {
+    pub fn text(&self) -> String {
+        self.keys.join(" + ")
+    }
+}
+
+struct GroupKey {
+    name: String,
+    presses: u32,
+}
+
+/// Mirrors the history mode grouping of the overlay's key event store.
+struct Grouper<'a> {
+    options: &'a ChordOptions,
+    pressed_keys: Vec<String>,
+    groups: Vec<Vec<GroupKey>>,
+    chords: Vec<Chord>,
+}
+
+impl Grouper<'_> {
+    fn ignore(&self, name: &str) -> bool {
+        let first = if self.pressed_keys.len() == 1 {
+            name
+        } else {
+            &self.pressed_keys[0]
+        };
+        match self.options.filter {
+            KeyFilter::None => false,
+            KeyFilter::Modifiers => !MODIFIERS.contains(&first),
+            KeyFilter::Custom => !self.options.allowed_keys.iter().any(|key| key == first),
+        }
+    }
+
+    fn last_has(&self, name: &str) -> bool {
+        self.groups
+            .last()
+            .is_some_and(|group| group.iter().any(|key| key.name == name))
+    }
+
+    fn press(&mut self, t: u64, name: &str) {
+        self.pressed_keys.push(name.to_string());
+        if self.ignore(name) {
+            return;
+        }
+
+        let pressed = &self.pressed_keys;
+        if self.last_has(name) {
+            let last = self.groups.last_mut().unwrap();
+            if last.len() > 1 {
+                // pressed again within a combination, starts over
+                let keys = last
+                    .iter()
+                    .filter(|key| pressed.contains(&key.name))
+                    .map(|key| GroupKey {
+                        name: key.name.clone(),
+                        presses: 1,
+                    })
+                    .collect();
+                self.groups.push(keys);
+            } else {
+                last.retain(|key| key.name == name || pressed.contains(&key.name));
+                for key in last.iter_mut().filter(|key| key.name == name) {
+                    key.presses += 1;
+                }
+            }
+        } else {
+            let key = GroupKey {
+                name: name.to_string(),
+                presses: 1,
+            };
+            match self.groups.last_mut() {
+                Some(last) if pressed.len() > 1 => {
+                    if last.iter().any(|key| !pressed.contains(&key.name)) {
+                        // partial combination
+                        let mut keys: Vec<GroupKey> = last
+                            .iter()
+                            .filter(|key| pressed.contains(&key.name))
+                            .map(|key| GroupKey {
+                                name: key.name.clone(),
+                                presses: key.presses,
+                            })
+                            .collect();
+                        keys.push(key);
+                        self.groups.push(keys);
+                    } else {
+                        last.push(key);
+                    }
+                }
+                _ => self.groups.push(vec![key]),
+            }
+        }
+        self.show(t);
+    }
+
+    fn repeat(&mut self, t: u64, name: &str) {
+        if let Some(last) = self.groups.last_mut() {
+            if let Some(key) = last.iter_mut().find(|key| key.name == name) {
+                key.presses += 1;
+                self.show(t);
+            }
+        }
+    }
+
+    fn release(&mut self, t: u64, name: &str) {
+        self.pressed_keys.retain(|key| key != name);
+        if self.last_has(name) {
+            // the group stays up while held and lingers from its last release
+            if let Some(chord) = self.chords.last_mut() {
+                chord.end_ms = chord.end_ms.max(t + self.options.linger_ms);
+            }
+        }
+    }
+
+    /// Starts a cue for the last group, cutting the one on screen short.
+    fn show(&mut self, t: u64) {
+        let Some(last) = self.groups.last() else {
+            return;
+        };
+        let keys = last
+            .iter()
+            .map(|key| match key.presses {
+                1 => key_label(&key.name),
+                presses => format!("{} ×{}", key_label(&key.name), presses),
+            })
+            .collect();
+        if let Some(chord) = self.chords.last_mut() {
+            chord.end_ms = chord.end_ms.min(t);
+            if chord.end_ms == chord.start_ms {
+                self.chords.pop();
+            }
+        }
+        self.chords.push(Chord {
+            start_ms: t,
+            end_ms: t + self.options.linger_ms,
+            keys,
+        });
+    }
+}
+
+/// Groups the events of a session into the chords the overlay would show
+/// in history mode, one at a time.
+pub fn group_chords(events: &[(u64, InputEvent)], options: &ChordOptions) -> Vec<Chord> {
+    let mut grouper = Grouper {
+        options,
+        pressed_keys: vec![],
+        groups: vec![],
+        chords: vec![],
+    };
+    for (t, event) in events {
+        match event {
+            InputEvent::KeyEvent {
+                pressed: true,
+                name,
+                repeat: 0,
+            } => grouper.press(*t, name),
+            InputEvent::KeyEvent {
+                pressed: true,
+                name,
+                ..
+            } => grouper.repeat(*t, name),
+            InputEvent::KeyEvent {
+                pressed: false,
+                name,
+                ..
+            } => grouper.release(*t, name),
+            InputEvent::MouseButtonEvent { pressed, button } if options.show_mouse_events => {
+                let name = format!("{:?}", button);
+                if *pressed {
+                    grouper.press(*t, &name);
+                } else {
+                    grouper.release(*t, &name);
+                }
+            }
+            _ => {}
+        }
+    }
+    grouper.chords
+}
+
+/// Exports the session at `path` as subtitles, next to it unless `output`
+/// is given, and returns the path written.
+pub fn export_subtitles(
+    path: &Path,
+    format: SubtitleFormat,
+    output: Option<&Path>,
+    options: &ChordOptions,
+    style: &KeyStyle,
+) -> io::Result<PathBuf> {
+    let mut events = vec![];
+    let mut screen = (0, 0);
+    for line in read_session(path)? {
+        match line {
+            SessionLine::Header { monitor, .. } if screen == (0, 0) => screen = monitor.size,
+            SessionLine::Event { t, event } => events.push((t, event)),
+            _ => {}
+        }
+    }
+    let chords = group_chords(&events, options);
+    let contents = match format {
+        SubtitleFormat::Srt => to_srt(&chords),
+        SubtitleFormat::Vtt => to_vtt(&chords),
+        SubtitleFormat::Ass => to_ass(&chords, style, screen),
+    };
+    let output = output
+        .map(Path::to_path_buf)
+        .unwrap_or_else(|| path.with_extension(format.extension()));
+    fs::write(&output, contents)?;
+    Ok(output)
+}
+
+fn to_srt(chords: &[Chord]) -> String {
+    let mut srt = String::new();
+    for (index, chord) in chords.iter().enumerate() {
+        let _ = writeln!(
+            srt,
+            "{}\n{} --> {}\n{}\n",
+            index + 1,
+            timestamp(chord.start_ms, ','),
+            timestamp(chord.end_ms, ','),
+            chord.text()
+        );
+    }
+    srt
+}
+
+fn to_vtt(chords: &[Chord]) -> String {
+    let mut vtt = String::from("WEBVTT\n\n");
+    for chord in chords {
+        let text = chord
+            .text()
+            .replace('&', "&amp;")
+            .replace('<', "&lt;")
+            .replace('>', "&gt;");
+        let _ = writeln!(
+            vtt,
+            "{} --> {}\n{}\n",
+            timestamp(chord.start_ms, '.'),
+            timestamp(chord.end_ms, '.'),
+            text
+        );
+    }
+    vtt
+}
+
+fn to_ass(chords: &[Chord], style: &KeyStyle, (width, height): (u32, u32)) -> String {
+    let (width, height) = if width == 0 || height == 0 {
+        (1920, 1080)
+    } else {
+        (width, height)
+    };
+    let text_color = ass_color(&style.text.color).unwrap_or_else(|| "&H00000000".into());
+    let background = ass_color(&style.color.color).unwrap_or_else(|| "&H00FFFFFF".into());
+    let mut ass = String::new();
+    let _ = writeln!(
+        ass,
+        "[Script Info]\nScriptType: v4.00+\nPlayResX: {}\nPlayResY: {}\nWrapStyle: 2\n",
+        width, height
+    );
+    let _ = writeln!(
+        ass,
+        "[V4+ Styles]\nFormat: Name, Fontname, Fontsize, PrimaryColour, SecondaryColour, \
+         OutlineColour, BackColour, Bold, Italic, Underline, StrikeOut, ScaleX, ScaleY, \
+         Spacing, Angle, BorderStyle, Outline, Shadow, Alignment, MarginL, MarginR, MarginV, \
+         Encoding\nStyle: Keys,Sans,{},{},{},{},{},-1,0,0,0,100,100,0,0,3,{},0,2,20,20,40,1\n",
+        style.text.size.round(),
+        text_color,
+        text_color,
+        background,
+        background,
+        (style.text.size / 4.0).round()
+    );
+    ass.push_str(
+        "[Events]\nFormat: Layer, Start, End, Style, Name, MarginL, MarginR, MarginV, Effect, Text\n",
+    );
+    for chord in chords {
+        let text = chord.text().replace('{', "\\{").replace('}', "\\}");
+        let _ = writeln!(
+            ass,
+            "Dialogue: 0,{},{},Keys,,0,0,0,,{}",
+            ass_timestamp(chord.start_ms),
+            ass_timestamp(chord.end_ms),
+            text
+        );
+    }
+    ass
+}
+
+// HH:MM:SS,mmm for srt and HH:MM:SS.mmm for vtt
+fn timestamp(ms: u64, separator: char) -> String {
+    format!(
+        "{:02}:{:02}:{:02}{}{:03}",
+        ms / 3_600_000,
+        ms / 60_000 % 60,
+        ms / 1000 % 60,
+        separator,
+        ms % 1000
+    )
+}
+
+// H:MM:SS.cc, ass counts centiseconds
+fn ass_timestamp(ms: u64) -> String {
+    format!(
+        "{}:{:02}:{:02}.{:02}",
+        ms / 3_600_000,
+        ms / 60_000 % 60,
+        ms / 1000 % 60,
+        ms % 1000 / 10
+    )
+}
+
+/// Css hex colors to ass' "&HAABBGGRR", where alpha counts up from opaque.
+fn ass_color(color: &str) -> Option<String> {
+    let [r, g, b, a] = parse_color(color)?;
+    Some(format!("&H{:02X}{:02X}{:02X}{:02X}", 255 - a, b, g, r))
+}
+
+/// Short caption for a key, following the labels of the overlay.
+fn key_label(name: &str) -> String {
+    let label = match name {
+        "ShiftLeft" | "ShiftRight" => "Shift",
+        "ControlLeft" | "ControlRight" => "Ctrl",
+        #[cfg(target_os = "macos")]
+        "Alt" => "Option",
+        #[cfg(not(target_os = "macos"))]
+        "Alt" => "Alt",
+        "AltGr" => "AltGr",
+        #[cfg(target_os = "macos")]
+        "MetaLeft" | "MetaRight" => "Cmd",
+        #[cfg(not(target_os = "macos"))]
+        "MetaLeft" | "MetaRight" => "Win",
+        "Function" => "Fn",
+        "Return" | "KpReturn" => "Enter",
+        "Escape" => "Esc",
+        "Backspace" => "Backspace",
+        "Delete" | "KpDelete" => "Del",
+        "CapsLock" => "Caps Lock",
+        "PrintScreen" => "PrtScn",
+        "PageUp" => "PgUp",
+        "PageDown" => "PgDn",
+        "UpArrow" => "↑",
+        "DownArrow" => "↓",
+        "LeftArrow" => "←",
+        "RightArrow" => "→",
+        "BackQuote" => "`",
+        "Minus" | "KpMinus" => "-",
+        "Equal" | "KpEqual" => "=",
+        "LeftBracket" => "[",
+        "RightBracket" => "]",
+        "SemiColon" => ";",
+        "Quote" => "'",
+        "BackSlash" | "IntlBackslash" => "\\",
+        "Comma" | "KpComma" => ",",
+        "Dot" => ".",
+        "Slash" | "KpDivide" => "/",
+        "KpMultiply" => "*",
+        "KpPlus" => "+",
+        "Left" => "Left Click",
+        "Right" => "Right Click",
+        "Middle" => "Middle Click",
+        "Other" => "Click",
+        _ => {
+            if let Some(letter) = name.strip_prefix("Key") {
+                return letter.to_string();
+            }
+            if let Some(digit) = name.strip_prefix("Num").or(name.strip_prefix("Kp")) {
+                if digit.len() == 1 {
+                    return digit.to_string();
+                }
+            }
+            name
+        }
+    };
+    label.to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(t: u64, name: &str, pressed: bool, repeat: u32) -> (u64, InputEvent) {
+        let name = name.to_string();
+        (
+            t,
+            InputEvent::KeyEvent {
+                pressed,
+                name,
+                repeat,
+            },
+        )
+    }
+
+    fn chord(start_ms: u64, end_ms: u64, keys: &[&str]) -> Chord {
+        Chord {
+            start_ms,
+            end_ms,
+            keys: keys.iter().map(|key| key.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn chords_split_where_the_overlay_regroups() {
+        let events = [
+            key(0, "ControlLeft", true, 0),
+            key(100, "KeyC", true, 0),
+            key(150, "KeyC", true, 1),
+            key(200, "KeyC", false, 0),
+            key(300, "ControlLeft", false, 0),
+            // plain keys don't pass the modifier filter
+            key(1_000, "KeyA", true, 0),
+            key(1_100, "KeyA", false, 0),
+            // a chord replaced at once never shows
+            key(9_000, "ShiftLeft", true, 0),
+            key(9_000, "KeyB", true, 0),
+            key(9_100, "KeyB", false, 0),
+            key(9_200, "ShiftLeft", false, 0),
+        ];
+        let chords = group_chords(&events, &ChordOptions::default());
+        assert_eq!(
+            chords,
+            [
+                chord(0, 100, &["Ctrl"]),
+                chord(100, 150, &["Ctrl", "C"]),
+                // lingers from the last release of its keys
+                chord(150, 5_300, &["Ctrl", "C ×2"]),
+                chord(9_000, 14_200, &["Shift", "B"]),
+            ]
+        );
+    }
+
+    #[test]
+    fn timestamps_follow_each_format() {
+        assert_eq!(timestamp(3_723_045, ','), "01:02:03,045");
+        assert_eq!(timestamp(59_999, '.'), "00:00:59.999");
+        assert_eq!(ass_timestamp(3_723_045), "1:02:03.04");
+        assert_eq!(ass_timestamp(999), "0:00:00.99");
+
+        let chords = [chord(100, 5_300, &["Ctrl", "<"])];
+        assert_eq!(
+            to_srt(&chords),
+            "1\n00:00:00,100 --> 00:00:05,300\nCtrl + <\n\n"
+        );
+        assert_eq!(
+            to_vtt(&chords),
+            "WEBVTT\n\n00:00:00.100 --> 00:00:05.300\nCtrl + &lt;\n\n"
+        );
+        let ass = to_ass(&chords, &KeyStyle::default(), (0, 0));
+        assert!(ass.contains("PlayResX: 1920\nPlayResY: 1080\n"));
+        assert!(ass.ends_with("Dialogue: 0,0:00:00.10,0:00:05.30,Keys,,0,0,0,,Ctrl + <\n"));
+    }
+}
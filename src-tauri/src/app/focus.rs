@@ -0,0 +1,178 @@
+#[cfg(target_os = "linux")]
+use std::sync::Mutex;
+
+use serde::{Deserialize, Serialize};
+#[cfg(target_os = "linux")]
+use tauri::{AppHandle, Emitter, Manager};
+
+#[cfg(target_os = "linux")]
+use crate::app::{actions, privacy::PrivacyPause, state::AppState};
+
+/// The focused top level window, as sent with `focus-changed`.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FocusedWindow {
+    // WM_CLASS, e.g. "firefox" and "Navigator"
+    pub class: String,
+    pub instance: String,
+    pub title: String,
+}
+
+#[cfg(target_os = "linux")]
+impl From<rdev::ActiveWindow> for FocusedWindow {
+    fn from(window: rdev::ActiveWindow) -> Self {
+        FocusedWindow {
+            class: window.class,
+            instance: window.instance,
+            title: window.title,
+        }
+    }
+}
+
+/// What a focus rule does once a matching window gets focus.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", content = "name", rename_all = "camelCase")]
+pub enum FocusAction {
+    Profile(String),
+    Pause,
+    Resume,
+}
+
+/// Picks out windows by their class and title. Patterns are matched
+/// case-insensitively, with `*` for any run of characters and `?` for a
+/// single one, an absent pattern matches every window.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WindowPattern {
+    // checked against both the WM_CLASS class and instance
+    #[serde(default)]
+    pub class: Option<String>,
+    #[serde(default)]
+    pub title: Option<String>,
+}
+
+impl WindowPattern {
+    pub fn class(pattern: &str) -> Self {
+        WindowPattern {
+            class: Some(pattern.to_string()),
+            title: None,
+        }
+    }
+
+    pub fn title(pattern: &str) -> Self {
+        WindowPattern {
+            class: None,
+            title: Some(pattern.to_string()),
+        }
+    }
+
+    pub fn matches(&self, window: &FocusedWindow) -> bool {
+        let class = self.class.as_deref().is_none_or(|pattern| {
+            glob_match(pattern, &window.class) || glob_match(pattern, &window.instance)
+        });
+        let title = self
+            .title
+            .as_deref()
+            .is_none_or(|pattern| glob_match(pattern, &window.title));
+        class && title
+    }
+}
+
+/// Maps the windows matching a pattern to an action.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FocusRule {
+    #[serde(flatten)]
+    pub window: WindowPattern,
+    pub action: FocusAction,
+}
+
+/// Matches `text` against a `*` and `?` wildcard pattern, ignoring case.
+pub fn glob_match(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.to_lowercase().chars().collect();
+    let text: Vec<char> = text.to_lowercase().chars().collect();
+    let (mut p, mut t) = (0, 0);
+    // where the last `*` was, and the text position it's matched up to
+    let mut backtrack = None;
+    while t < text.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                backtrack = Some((p, t));
+                p += 1;
+            }
+            Some(&c) if c == '?' || c == text[t] => {
+                p += 1;
+                t += 1;
+            }
+            _ => match backtrack {
+                // let the `*` take one more character
+                Some((star, matched)) => {
+                    p = star + 1;
+                    t = matched + 1;
+                    backtrack = Some((star, matched + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+/// Follows the focused window on X11, emitting `focus-changed`, pausing for
+/// blocklisted windows and running what the focus rules ask for.
+#[cfg(target_os = "linux")]
+pub fn start(app: AppHandle) -> Result<(), rdev::DisplayError> {
+    rdev::watch_active_window(move |window| {
+        let window = window.map(FocusedWindow::from);
+        app.emit("focus-changed", &window).unwrap();
+        let focus_actions = {
+            let state = app.state::<Mutex<AppState>>();
+            let mut app_state = state.lock().unwrap();
+            let pause = PrivacyPause {
+                window: app_state.config.privacy.blocks(window.as_ref()),
+                ..app_state.privacy_pause
+            };
+            app_state.set_privacy_pause(&app, pause);
+            app_state.focus_changed(window)
+        };
+        // these take the state themselves
+        for action in focus_actions {
+            actions::run(&app, action);
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn globs_match_ignoring_case() {
+        assert!(glob_match(
+            "*Private Browsing*",
+            "Inbox — Mozilla Firefox Private Browsing"
+        ));
+        assert!(glob_match("code", "Code"));
+        assert!(glob_match("term?nal", "terminal"));
+        assert!(glob_match("*", ""));
+        assert!(!glob_match("code", "vscode"));
+        assert!(!glob_match("*.rs", "main.rs - keyviz"));
+    }
+
+    #[test]
+    fn patterns_match_class_or_instance_and_title() {
+        let window = FocusedWindow {
+            class: "firefox".into(),
+            instance: "Navigator".into(),
+            title: "Slides - Mozilla Firefox".into(),
+        };
+        let pattern = |class: Option<&str>, title: Option<&str>| WindowPattern {
+            class: class.map(String::from),
+            title: title.map(String::from),
+        };
+        assert!(pattern(Some("navigator"), None).matches(&window));
+        assert!(pattern(Some("Firefox"), Some("slides*")).matches(&window));
+        assert!(pattern(None, None).matches(&window));
+        assert!(!pattern(Some("firefox"), Some("*terminal*")).matches(&window));
+    }
+}
@@ -0,0 +1,167 @@
+use std::{
+    io::{self, Write},
+    str::FromStr,
+};
+
+use rdev::listen;
+
+#[cfg(not(target_os = "macos"))]
+use crate::app::event::key_state_reader;
+use crate::app::{
+    config::Config,
+    event::{process_event, InputEvent},
+    hotkey::HotkeyAction,
+    state::AppState,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum OutputFormat {
+    // one InputEvent per line, as the overlay receives it
+    Json,
+    Text,
+}
+
+impl FromStr for OutputFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "json" => Ok(OutputFormat::Json),
+            "text" => Ok(OutputFormat::Text),
+            _ => Err(format!("unknown output format {}", s)),
+        }
+    }
+}
+
+/// Runs the input listener without any window or tray, printing the events
+/// the overlay would get to stdout. Returns the exit code.
+pub fn run(format: OutputFormat) -> i32 {
+    // no monitor to map to, positions stay physical
+    let mut app_state = AppState::detached(Config::load_file());
+    eprintln!(
+        "Listening for input, {} pauses and resumes",
+        app_state.config.key_event.toggle_shortcut
+    );
+
+    #[cfg(not(target_os = "macos"))]
+    let key_state = key_state_reader();
+    let result = listen(move |event| {
+        let was_listening = app_state.listening;
+        let mut released = vec![];
+        let toggle = |app_state: &mut AppState| {
+            app_state.listening = !app_state.listening;
+            if app_state.listening {
+                eprintln!("🟢 Listening enabled");
+            } else {
+                eprintln!("🔴 Listening disabled");
+            }
+        };
+        let input_event = process_event(
+            &mut app_state,
+            event,
+            |_app_state| {
+                #[cfg(not(target_os = "macos"))]
+                if let Some(key_state) = &key_state {
+                    released.extend(_app_state.drop_released_keys(key_state));
+                }
+            },
+            toggle,
+            // the rest need the overlay
+            |app_state, action| {
+                if action == HotkeyAction::ToggleListening {
+                    toggle(app_state);
+                }
+            },
+        );
+        // keys held when pausing would otherwise stay down for the reader
+        if was_listening && !app_state.listening {
+            released.extend(app_state.pressed_keys.iter().cloned());
+        }
+        let released = released.into_iter().map(|name| InputEvent::KeyEvent {
+            pressed: false,
+            name,
+            repeat: 0,
+        });
+
+        let mut stdout = io::stdout().lock();
+        for input_event in released.chain(input_event) {
+            let written = match format {
+                OutputFormat::Json => serde_json::to_string(&input_event)
+                    .map_err(io::Error::other)
+                    .and_then(|line| writeln!(stdout, "{}", line)),
+                OutputFormat::Text => writeln!(stdout, "{}", describe(&input_event)),
+            };
+            // the reader went away, e.g. `keyviz --headless | head`
+            if written.and_then(|_| stdout.flush()).is_err() {
+                std::process::exit(0);
+            }
+        }
+    });
+    match result {
+        Ok(()) => 0,
+        Err(err) => {
+            eprintln!("rdev listen failed: {:?}", err);
+            1
+        }
+    }
+}
+
+/// One line per event, meant for reading rather than parsing.
+fn describe(input_event: &InputEvent) -> String {
+    match input_event {
+        InputEvent::KeyEvent {
+            pressed: true,
+            name,
+            repeat: 0,
+        } => format!("key press {}", name),
+        InputEvent::KeyEvent {
+            pressed: true,
+            name,
+            repeat,
+        } => format!("key repeat {} x{}", name, repeat),
+        InputEvent::KeyEvent { name, .. } => format!("key release {}", name),
+        InputEvent::MouseButtonEvent { pressed, button } => format!(
+            "button {} {:?}",
+            if *pressed { "press" } else { "release" },
+            button
+        ),
+        InputEvent::MouseMoveEvent { x, y } => format!("move {} {}", x, y),
+        InputEvent::MouseWheelEvent { delta_x, delta_y } => {
+            format!("wheel {} {}", delta_x, delta_y)
+        }
+        InputEvent::LockStateEvent {
+            caps_lock,
+            num_lock,
+            scroll_lock,
+        } => format!(
+            "lock caps={} num={} scroll={}",
+            caps_lock, num_lock, scroll_lock
+        ),
+        InputEvent::LayoutChangeEvent { name } => format!("layout {}", name),
+        InputEvent::GestureEvent {
+            gesture,
+            phase,
+            fingers,
+            delta_x,
+            delta_y,
+            scale,
+        } => format!(
+            "gesture {:?} {:?} fingers={} delta={},{} scale={}",
+            gesture, phase, fingers, delta_x, delta_y, scale
+        ),
+        InputEvent::TouchEvent { id, phase, x, y } => {
+            format!("touch {} {:?} {} {}", id, phase, x, y)
+        }
+        InputEvent::PenEvent {
+            x,
+            y,
+            pressure,
+            contact,
+            eraser,
+            ..
+        } => format!(
+            "pen {} {} pressure={} contact={} eraser={}",
+            x, y, pressure, contact, eraser
+        ),
+    }
+}
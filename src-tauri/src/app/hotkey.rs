@@ -0,0 +1,86 @@
+use std::sync::Mutex;
+
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use tauri::{AppHandle, Emitter, Manager};
+
+use crate::app::{
+    actions::{self, Action, RecordAction},
+    chord::KeySequence,
+    commands::set_main_window_monitor,
+    state::AppState,
+};
+
+/// What a hotkey does once its chord is pressed.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", content = "name", rename_all = "camelCase")]
+pub enum HotkeyAction {
+    ToggleListening,
+    // drops the key groups the overlay still shows
+    ClearHistory,
+    ToggleMouseEvents,
+    // moves the overlay to the next monitor
+    CycleMonitor,
+    Profile(String),
+    ToggleRecording,
+    ToggleRedaction,
+}
+
+/// Binds a chord or a sequence of them, e.g. "Ctrl+Shift+K" or
+/// "Ctrl+K, Ctrl+V", to an action.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Hotkey {
+    pub keys: KeySequence,
+    pub action: HotkeyAction,
+}
+
+/// Runs a hotkey's action, taking the state itself.
+pub fn run(app: &AppHandle, action: HotkeyAction) {
+    match action {
+        HotkeyAction::ToggleListening => actions::run(app, Action::Toggle),
+        HotkeyAction::ClearHistory => app.emit_to("main", "clear-history", ()).unwrap(),
+        HotkeyAction::ToggleMouseEvents => {
+            let state = app.state::<Mutex<AppState>>();
+            let mut app_state = state.lock().unwrap();
+            let show = !app_state.config.key_event.show_mouse_events;
+            let changes = json!({ "showMouseEvents": show });
+            let changes = changes.as_object().cloned().unwrap_or_default();
+            if let Err(err) = app_state.update_key_event_state(app, changes) {
+                eprintln!("Failed to toggle mouse events: {}", err);
+            }
+        }
+        HotkeyAction::CycleMonitor => cycle_monitor(app),
+        HotkeyAction::Profile(name) => actions::run(app, Action::Profile(name)),
+        HotkeyAction::ToggleRecording => actions::run(app, Action::Record(RecordAction::Toggle)),
+        HotkeyAction::ToggleRedaction => {
+            let state = app.state::<Mutex<AppState>>();
+            let mut app_state = state.lock().unwrap();
+            let active = !app_state.redaction.active;
+            app_state.set_redacting(app, active);
+        }
+    }
+}
+
+fn cycle_monitor(app: &AppHandle) {
+    let Some(window) = app.get_webview_window("main") else {
+        return;
+    };
+    let names: Vec<String> = window
+        .available_monitors()
+        .unwrap_or_default()
+        .iter()
+        .filter_map(|monitor| monitor.name().cloned())
+        .collect();
+    let current = {
+        let state = app.state::<Mutex<AppState>>();
+        let app_state = state.lock().unwrap();
+        app_state.monitor_name.clone()
+    };
+    let next = current
+        .and_then(|current| names.iter().position(|name| *name == current))
+        .map_or(0, |index| (index + 1) % names.len().max(1));
+    if let Some(name) = names.get(next) {
+        set_main_window_monitor(app.clone(), name.clone());
+    }
+}
@@ -1,4 +1,24 @@
+pub mod actions;
+pub mod chord;
+pub mod cli;
 pub mod commands;
+pub mod config;
+#[cfg(target_os = "linux")]
+pub mod dbus;
 pub mod event;
+pub mod export;
+pub mod focus;
+pub mod headless;
+pub mod hotkey;
+pub mod privacy;
+pub mod profile;
+pub mod recorder;
+pub mod redact;
+pub mod render;
+pub mod replay;
+#[cfg(unix)]
+pub mod rpc;
+pub mod server;
 pub mod state;
+pub mod style;
 pub mod window;
@@ -0,0 +1,118 @@
+#[cfg(target_os = "linux")]
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    thread,
+    time::Duration,
+};
+
+use serde::{Deserialize, Serialize};
+#[cfg(target_os = "linux")]
+use tauri::{AppHandle, Manager};
+
+use crate::app::focus::{FocusedWindow, WindowPattern};
+#[cfg(target_os = "linux")]
+use crate::app::state::AppState;
+
+/// How often a grab is checked against the settings and listening.
+#[cfg(target_os = "linux")]
+const GRAB_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// When to hold back input events, so secrets typed elsewhere never reach
+/// the overlay, recordings or clients.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct PrivacyConfig {
+    pub enabled: bool,
+    // windows to pause in, e.g. password managers
+    pub blocklist: Vec<WindowPattern>,
+    // pause while another client grabs the keyboard, as prompts do
+    pub detect_grabs: bool,
+}
+
+impl Default for PrivacyConfig {
+    fn default() -> Self {
+        PrivacyConfig {
+            enabled: true,
+            blocklist: vec![
+                WindowPattern::class("keepassxc"),
+                WindowPattern::class("1password"),
+                WindowPattern::class("bitwarden"),
+                WindowPattern::class("*pinentry*"),
+                // gnome keyring and polkit prompts
+                WindowPattern::class("gcr-prompter"),
+                WindowPattern::class("*polkit*"),
+                WindowPattern::title("*Private Browsing*"),
+                WindowPattern::title("*Incognito*"),
+                // terminals titled after the running command
+                WindowPattern::title("sudo *"),
+                WindowPattern::title("*: sudo *"),
+                WindowPattern::title("doas *"),
+            ],
+            detect_grabs: true,
+        }
+    }
+}
+
+impl PrivacyConfig {
+    pub fn blocks(&self, window: Option<&FocusedWindow>) -> bool {
+        self.enabled
+            && window
+                .is_some_and(|window| self.blocklist.iter().any(|pattern| pattern.matches(window)))
+    }
+}
+
+/// Why input events are held back for privacy, emitting resumes once both
+/// are clear again.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PrivacyPause {
+    // the focused window is on the blocklist
+    pub window: bool,
+    // another client grabbed the keyboard
+    pub grab: bool,
+}
+
+impl PrivacyPause {
+    pub fn paused(&self) -> bool {
+        self.window || self.grab
+    }
+}
+
+/// Watches for keyboard grabs by other clients, pausing while one is held
+/// and we listen. Grabs for the window that already has focus go unnoticed,
+/// the blocklist covers the prompts that do that.
+#[cfg(target_os = "linux")]
+pub fn start_grab_watch(app: AppHandle) {
+    let grabbed = Arc::new(AtomicBool::new(false));
+    let watched = (app.clone(), grabbed.clone());
+    let result = rdev::watch_keyboard_grabs(move |grab| {
+        let (app, grabbed) = &watched;
+        grabbed.store(grab, Ordering::Relaxed);
+        update_grab_pause(app, grab);
+    });
+    if let Err(err) = result {
+        eprintln!("Failed to watch for keyboard grabs: {:?}", err);
+        return;
+    }
+    // listening and the settings change without a grab, catch up with them
+    thread::spawn(move || loop {
+        thread::sleep(GRAB_POLL_INTERVAL);
+        update_grab_pause(&app, grabbed.load(Ordering::Relaxed));
+    });
+}
+
+#[cfg(target_os = "linux")]
+fn update_grab_pause(app: &AppHandle, grabbed: bool) {
+    let state = app.state::<Mutex<AppState>>();
+    let mut app_state = state.lock().unwrap();
+    let privacy = &app_state.config.privacy;
+    let guarding = app_state.listening && privacy.enabled && privacy.detect_grabs;
+    let pause = PrivacyPause {
+        grab: guarding && grabbed,
+        ..app_state.privacy_pause
+    };
+    app_state.set_privacy_pause(app, pause);
+}
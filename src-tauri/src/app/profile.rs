@@ -0,0 +1,321 @@
+use std::{collections::BTreeMap, fs, path::Path, sync::Mutex};
+
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use tauri::{
+    menu::{CheckMenuItem, IsMenuItem, MenuItem, Submenu},
+    AppHandle, Emitter, Manager, Wry,
+};
+
+use crate::app::{
+    config::{push_to_webview, Profile, CONFIG_VERSION, KEY_EVENT_STORE, KEY_STYLE_STORE},
+    state::AppState,
+};
+
+/// Prefix of the tray menu ids switching to a profile, followed by its name.
+pub const MENU_ID_PREFIX: &str = "profile:";
+
+/// The tray submenu listing the profiles, managed as app state.
+pub struct ProfileMenu {
+    pub submenu: Submenu<Wry>,
+}
+
+/// The saved profiles, and the one last switched to.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProfileList {
+    pub names: Vec<String>,
+    pub active: Option<String>,
+}
+
+/// What importing a profile file did, for the settings window to report.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ImportReport {
+    pub name: String,
+    // a profile by that name existed and was overwritten
+    pub replaced: bool,
+    // values reset to their defaults as they were unusable
+    pub problems: Vec<String>,
+}
+
+/// A profile as a standalone file, written by export and read by import.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ProfileFile {
+    version: u32,
+    #[serde(default)]
+    name: String,
+    #[serde(flatten)]
+    profile: Profile,
+}
+
+pub fn list(app: &AppHandle) -> ProfileList {
+    let state = app.state::<Mutex<AppState>>();
+    let app_state = state.lock().unwrap();
+    ProfileList {
+        names: app_state.config.profiles.keys().cloned().collect(),
+        active: app_state.config.active_profile.clone(),
+    }
+}
+
+/// Saves the current settings as `name`, replacing a profile by that name.
+pub fn save(app: &AppHandle, name: &str) -> Result<(), String> {
+    let name = check_name(name)?;
+    {
+        let state = app.state::<Mutex<AppState>>();
+        let mut app_state = state.lock().unwrap();
+        let config = &mut app_state.config;
+        let profile = Profile {
+            key_event: config.key_event.clone(),
+            key_style: config.key_style.clone(),
+        };
+        config.profiles.insert(name.clone(), profile);
+        config.active_profile = Some(name);
+        config.save(app)?;
+    }
+    refresh_menu(app);
+    Ok(())
+}
+
+/// Makes the settings of profile `name` the current ones, in the backend and
+/// both webviews.
+pub fn switch(app: &AppHandle, name: &str) -> Result<(), String> {
+    {
+        let state = app.state::<Mutex<AppState>>();
+        let mut app_state = state.lock().unwrap();
+        let profile = app_state
+            .config
+            .profiles
+            .get(name)
+            .cloned()
+            .ok_or_else(|| format!("no profile named {}", name))?;
+
+        push_to_webview(app, KEY_EVENT_STORE, &fields(&profile.key_event)?)?;
+        push_to_webview(app, KEY_STYLE_STORE, &fields(&profile.key_style)?)?;
+        let config = &mut app_state.config;
+        config.key_event = profile.key_event;
+        config.key_style = profile.key_style;
+        config.active_profile = Some(name.to_string());
+        config.save(app)?;
+    }
+    // the settings window reloads its stores on this
+    app.emit("profile-changed", name).unwrap();
+    refresh_menu(app);
+    println!("🎛️ Switched to profile {}", name);
+    Ok(())
+}
+
+pub fn delete(app: &AppHandle, name: &str) -> Result<(), String> {
+    {
+        let state = app.state::<Mutex<AppState>>();
+        let mut app_state = state.lock().unwrap();
+        let config = &mut app_state.config;
+        if config.profiles.remove(name).is_none() {
+            return Err(format!("no profile named {}", name));
+        }
+        if config.active_profile.as_deref() == Some(name) {
+            config.active_profile = None;
+        }
+        config.save(app)?;
+    }
+    refresh_menu(app);
+    Ok(())
+}
+
+/// Writes profile `name` to `path`, as TOML for a `.toml` extension and JSON
+/// otherwise.
+pub fn export(app: &AppHandle, name: &str, path: &Path) -> Result<(), String> {
+    let profile = {
+        let state = app.state::<Mutex<AppState>>();
+        let app_state = state.lock().unwrap();
+        app_state
+            .config
+            .profiles
+            .get(name)
+            .cloned()
+            .ok_or_else(|| format!("no profile named {}", name))?
+    };
+    let file = ProfileFile {
+        version: CONFIG_VERSION,
+        name: name.to_string(),
+        profile,
+    };
+    fs::write(path, serialize(&file, is_toml(path))?).map_err(|e| e.to_string())
+}
+
+/// Reads a profile file written by `export`. It's saved under `name`, else
+/// the name in the file, else the file name. An existing profile by that
+/// name is only replaced with `overwrite`.
+pub fn import(
+    app: &AppHandle,
+    path: &Path,
+    name: Option<String>,
+    overwrite: bool,
+) -> Result<ImportReport, String> {
+    let content = fs::read_to_string(path).map_err(|e| e.to_string())?;
+    let (file_name, mut profile) = parse(&content, is_toml(path))?;
+    let name = name
+        .or(Some(file_name).filter(|name| !name.trim().is_empty()))
+        .or_else(|| Some(path.file_stem()?.to_string_lossy().into_owned()))
+        .unwrap_or_default();
+    let name = check_name(&name)?;
+    let problems = profile.validate();
+
+    let replaced = {
+        let state = app.state::<Mutex<AppState>>();
+        let mut app_state = state.lock().unwrap();
+        let config = &mut app_state.config;
+        let replaced = insert(&mut config.profiles, &name, profile, overwrite)?;
+        config.save(app)?;
+        replaced
+    };
+    refresh_menu(app);
+    Ok(ImportReport {
+        name,
+        replaced,
+        problems,
+    })
+}
+
+/// Adds `profile` as `name`, returning whether it replaced one. Refuses to
+/// unless `overwrite`.
+fn insert(
+    profiles: &mut BTreeMap<String, Profile>,
+    name: &str,
+    profile: Profile,
+    overwrite: bool,
+) -> Result<bool, String> {
+    let replaced = profiles.contains_key(name);
+    if replaced && !overwrite {
+        return Err(format!("a profile named {} already exists", name));
+    }
+    profiles.insert(name.to_string(), profile);
+    Ok(replaced)
+}
+
+fn serialize(file: &ProfileFile, toml: bool) -> Result<String, String> {
+    if toml {
+        toml::to_string_pretty(file).map_err(|e| e.to_string())
+    } else {
+        serde_json::to_string_pretty(file).map_err(|e| e.to_string())
+    }
+}
+
+/// Parses a profile file, returning the name it was exported under.
+fn parse(content: &str, toml: bool) -> Result<(String, Profile), String> {
+    let document: Value = if toml {
+        toml::from_str(content).map_err(|e| e.to_string())?
+    } else {
+        serde_json::from_str(content).map_err(|e| e.to_string())?
+    };
+    let version = document
+        .get("version")
+        .and_then(Value::as_u64)
+        .ok_or("not a keyviz profile, missing version")?;
+    if version > CONFIG_VERSION as u64 {
+        return Err(format!(
+            "profile version {} is newer than this build",
+            version
+        ));
+    }
+    let file: ProfileFile = serde_json::from_value(document).map_err(|e| e.to_string())?;
+    Ok((file.name, file.profile))
+}
+
+fn is_toml(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|extension| extension.eq_ignore_ascii_case("toml"))
+}
+
+fn check_name(name: &str) -> Result<String, String> {
+    let name = name.trim();
+    if name.is_empty() {
+        return Err("profile name can't be empty".to_string());
+    }
+    Ok(name.to_string())
+}
+
+/// Top level fields of a store's state, as the webview syncs them.
+fn fields<T: Serialize>(state: &T) -> Result<Map<String, Value>, String> {
+    serde_json::to_value(state)
+        .and_then(serde_json::from_value)
+        .map_err(|e| e.to_string())
+}
+
+/// Rebuilds the tray submenu from the saved profiles, checking the active one.
+pub fn refresh_menu(app: &AppHandle) {
+    let Some(menu) = app.try_state::<ProfileMenu>() else {
+        return;
+    };
+    let ProfileList { names, active } = list(app);
+    let result = (|| {
+        for item in menu.submenu.items()? {
+            menu.submenu.remove(&item)?;
+        }
+        if names.is_empty() {
+            let empty = MenuItem::new(app, "No profiles", false, None::<&str>)?;
+            return menu.submenu.append(&empty);
+        }
+        let items = names
+            .iter()
+            .map(|name| {
+                let id = format!("{}{}", MENU_ID_PREFIX, name);
+                let checked = active.as_ref() == Some(name);
+                CheckMenuItem::with_id(app, id, name, true, checked, None::<&str>)
+            })
+            .collect::<tauri::Result<Vec<_>>>()?;
+        let items: Vec<&dyn IsMenuItem<Wry>> = items
+            .iter()
+            .map(|item| item as &dyn IsMenuItem<Wry>)
+            .collect();
+        menu.submenu.append_items(&items)
+    })();
+    if let Err(err) = result {
+        eprintln!("Failed to update profile menu: {}", err);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn default_profile_round_trips() {
+        for toml in [false, true] {
+            let file = ProfileFile {
+                version: CONFIG_VERSION,
+                name: "livestream".to_string(),
+                profile: Profile::default(),
+            };
+            let content = serialize(&file, toml).unwrap();
+            let (name, profile) = parse(&content, toml).unwrap();
+            assert_eq!(name, "livestream");
+            assert_eq!(
+                serde_json::to_value(&profile).unwrap(),
+                serde_json::to_value(&file.profile).unwrap(),
+            );
+        }
+    }
+
+    #[test]
+    fn reports_conflicts_and_problems() {
+        let mut profiles = BTreeMap::new();
+        assert_eq!(
+            insert(&mut profiles, "pairing", Profile::default(), false),
+            Ok(false)
+        );
+        assert!(insert(&mut profiles, "pairing", Profile::default(), false).is_err());
+        assert_eq!(
+            insert(&mut profiles, "pairing", Profile::default(), true),
+            Ok(true)
+        );
+
+        let content = r#"{ "version": 1, "keyEvent": { "maxHistory": 0 } }"#;
+        let (name, mut profile) = parse(content, false).unwrap();
+        assert_eq!(name, "");
+        assert_eq!(profile.validate(), ["max history must be at least 1"]);
+        assert!(parse(r#"{ "keyEvent": {} }"#, false).is_err());
+        assert!(parse(r#"{ "version": 99 }"#, false).is_err());
+    }
+}
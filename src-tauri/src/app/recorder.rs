@@ -0,0 +1,458 @@
+use std::{
+    fs::{self, File},
+    io::{self, BufWriter, Write},
+    path::{Path, PathBuf},
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use serde::{Deserialize, Serialize};
+use tauri::{menu::MenuItem, Wry};
+
+use crate::app::event::InputEvent;
+
+/// Bumped whenever the layout of a session line changes.
+pub const SESSION_FORMAT_VERSION: u32 = 1;
+/// A session continues in a new part file past this size.
+const MAX_PART_BYTES: u64 = 8 * 1024 * 1024;
+/// Oldest sessions are deleted beyond this count, with all their parts.
+const MAX_SESSIONS: usize = 100;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MonitorInfo {
+    pub name: Option<String>,
+    pub scale: f64,
+    pub position: (i32, i32),
+    pub size: (u32, u32),
+}
+
+/// One line of a session file. Every part file starts with a header.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum SessionLine {
+    Header {
+        version: u32,
+        // unix time in ms
+        started_at: u64,
+        part: u32,
+        monitor: MonitorInfo,
+    },
+    // `t` is in ms since the session started, not counting pauses
+    Event {
+        t: u64,
+        event: InputEvent,
+    },
+    Monitor {
+        t: u64,
+        monitor: MonitorInfo,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub enum RecordingStatus {
+    Idle,
+    Recording,
+    Paused,
+}
+
+struct Session {
+    dir: PathBuf,
+    started_at: u64,
+    started: Instant,
+    paused_at: Option<Instant>,
+    paused_for: Duration,
+    part: u32,
+    part_bytes: u64,
+    max_part_bytes: u64,
+    monitor: MonitorInfo,
+    writer: BufWriter<File>,
+}
+
+impl Session {
+    fn elapsed_ms(&self) -> u64 {
+        let paused = self.paused_for + self.paused_at.map_or(Duration::ZERO, |at| at.elapsed());
+        self.started.elapsed().saturating_sub(paused).as_millis() as u64
+    }
+
+    fn path(&self) -> PathBuf {
+        part_path(&self.dir, self.started_at, self.part)
+    }
+
+    fn write_line(&mut self, line: &SessionLine) -> io::Result<()> {
+        let mut json = serde_json::to_vec(line)?;
+        json.push(b'\n');
+        self.writer.write_all(&json)?;
+        self.part_bytes += json.len() as u64;
+        if self.part_bytes > self.max_part_bytes {
+            self.rotate()?;
+        }
+        Ok(())
+    }
+
+    fn rotate(&mut self) -> io::Result<()> {
+        self.writer.flush()?;
+        self.part += 1;
+        self.part_bytes = 0;
+        self.writer = BufWriter::new(File::create(self.path())?);
+        self.write_line(&SessionLine::Header {
+            version: SESSION_FORMAT_VERSION,
+            started_at: self.started_at,
+            part: self.part,
+            monitor: self.monitor.clone(),
+        })?;
+        prune_sessions(&self.dir, self.started_at);
+        Ok(())
+    }
+}
+
+/// Tray entries reflecting the recording status, managed as app state.
+pub struct RecordingMenu {
+    pub record: MenuItem<Wry>,
+    pub pause: MenuItem<Wry>,
+}
+
+/// Writes input events to versioned JSONL session files.
+#[derive(Default)]
+pub struct Recorder {
+    session: Option<Session>,
+}
+
+impl Recorder {
+    pub fn status(&self) -> RecordingStatus {
+        match &self.session {
+            None => RecordingStatus::Idle,
+            Some(session) if session.paused_at.is_some() => RecordingStatus::Paused,
+            Some(_) => RecordingStatus::Recording,
+        }
+    }
+
+    /// Starts a new session in `dir` and returns the path of its first file.
+    pub fn start(&mut self, dir: PathBuf, monitor: MonitorInfo) -> io::Result<PathBuf> {
+        self.stop();
+        fs::create_dir_all(&dir)?;
+        let started_at = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis() as u64;
+        let writer = BufWriter::new(File::create(part_path(&dir, started_at, 1))?);
+        let mut session = Session {
+            dir,
+            started_at,
+            started: Instant::now(),
+            paused_at: None,
+            paused_for: Duration::ZERO,
+            part: 1,
+            part_bytes: 0,
+            max_part_bytes: MAX_PART_BYTES,
+            monitor,
+            writer,
+        };
+        let monitor = session.monitor.clone();
+        session.write_line(&SessionLine::Header {
+            version: SESSION_FORMAT_VERSION,
+            started_at,
+            part: 1,
+            monitor,
+        })?;
+        prune_sessions(&session.dir, started_at);
+        let path = session.path();
+        self.session = Some(session);
+        Ok(path)
+    }
+
+    /// Ends the session, writing out what's still buffered. Needed before
+    /// the process exits, which skips destructors.
+    pub fn stop(&mut self) {
+        if let Some(mut session) = self.session.take() {
+            if let Err(err) = session.writer.flush() {
+                eprintln!("Failed to finish session file: {}", err);
+            }
+        }
+    }
+
+    pub fn pause(&mut self) {
+        if let Some(session) = &mut self.session {
+            if session.paused_at.is_none() {
+                session.paused_at = Some(Instant::now());
+                let _ = session.writer.flush();
+            }
+        }
+    }
+
+    pub fn resume(&mut self) {
+        if let Some(session) = &mut self.session {
+            if let Some(paused_at) = session.paused_at.take() {
+                session.paused_for += paused_at.elapsed();
+            }
+        }
+    }
+
+    pub fn record(&mut self, event: &InputEvent) {
+        let Some(session) = &mut self.session else {
+            return;
+        };
+        if session.paused_at.is_some() {
+            return;
+        }
+        let t = session.elapsed_ms();
+        let line = SessionLine::Event {
+            t,
+            event: event.clone(),
+        };
+        if let Err(err) = session.write_line(&line) {
+            eprintln!("Failed to record event, stopping: {}", err);
+            self.session = None;
+        }
+    }
+
+    /// Notes the overlay moving to another monitor, so positions stay meaningful.
+    pub fn record_monitor(&mut self, monitor: MonitorInfo) {
+        let Some(session) = &mut self.session else {
+            return;
+        };
+        let t = session.elapsed_ms();
+        session.monitor = monitor.clone();
+        let result = session.write_line(&SessionLine::Monitor { t, monitor });
+        if let Err(err) = result {
+            eprintln!("Failed to record monitor, stopping: {}", err);
+            self.session = None;
+        }
+    }
+}
+
+fn part_path(dir: &Path, started_at: u64, part: u32) -> PathBuf {
+    dir.join(format!("session-{}-{:03}.jsonl", started_at, part))
+}
+
+/// Reads every line of a session, following it through all of its part
+/// files when `path` is one of them.
+pub fn read_session(path: &Path) -> io::Result<Vec<SessionLine>> {
+    let mut parts = vec![path.to_path_buf()];
+    // files not named like our parts are read on their own
+    if let Some((started_at, _)) = parse_part(path) {
+        let dir = match path.parent() {
+            Some(dir) if !dir.as_os_str().is_empty() => dir,
+            _ => Path::new("."),
+        };
+        let mut siblings: Vec<(u32, PathBuf)> = fs::read_dir(dir)?
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter_map(|sibling| match parse_part(&sibling)? {
+                (start, part) if start == started_at => Some((part, sibling)),
+                _ => None,
+            })
+            .collect();
+        if !siblings.is_empty() {
+            siblings.sort();
+            parts = siblings.into_iter().map(|(_, sibling)| sibling).collect();
+        }
+    }
+
+    let mut lines = vec![];
+    for part in parts {
+        // lossy, a cut off write can end inside a character
+        let bytes = fs::read(&part)?;
+        let content = String::from_utf8_lossy(&bytes);
+        let complete = content.ends_with('\n');
+        let count = content.lines().count();
+        for (number, line) in content.lines().enumerate() {
+            if line.trim().is_empty() {
+                continue;
+            }
+            let parsed: SessionLine = match serde_json::from_str(line) {
+                Ok(parsed) => parsed,
+                // the app went away mid write, keep what came before
+                Err(_) if !complete && number + 1 == count => {
+                    eprintln!("Ignoring truncated last line of {}", part.display());
+                    break;
+                }
+                Err(err) => {
+                    let message = format!("{}:{}: {}", part.display(), number + 1, err);
+                    return Err(io::Error::new(io::ErrorKind::InvalidData, message));
+                }
+            };
+            if let SessionLine::Header { version, .. } = parsed {
+                if version > SESSION_FORMAT_VERSION {
+                    let message = format!("session format {} is newer than this app", version);
+                    return Err(io::Error::new(io::ErrorKind::InvalidData, message));
+                }
+            }
+            lines.push(parsed);
+        }
+    }
+    Ok(lines)
+}
+
+/// Start time and part number of a file named by `part_path`.
+fn parse_part(path: &Path) -> Option<(u64, u32)> {
+    let name = path.file_name()?.to_str()?;
+    let (started_at, part) = name
+        .strip_prefix("session-")?
+        .strip_suffix(".jsonl")?
+        .split_once('-')?;
+    let number = |digits: &str| -> Option<u64> {
+        // parse alone would take a sign too
+        if digits.is_empty() || !digits.bytes().all(|byte| byte.is_ascii_digit()) {
+            return None;
+        }
+        digits.parse().ok()
+    };
+    Some((number(started_at)?, number(part)?.try_into().ok()?))
+}
+
+/// Deletes the oldest sessions beyond `MAX_SESSIONS`, all parts of each.
+/// The session started at `active` is kept however old.
+fn prune_sessions(dir: &Path, active: u64) {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+    let files: Vec<(u64, PathBuf)> = entries
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter_map(|path| Some((parse_part(&path)?.0, path)))
+        .collect();
+    let mut sessions: Vec<u64> = files.iter().map(|(started_at, _)| *started_at).collect();
+    sessions.sort_unstable();
+    sessions.dedup();
+    if sessions.len() <= MAX_SESSIONS {
+        return;
+    }
+    let oldest = &sessions[..sessions.len() - MAX_SESSIONS];
+    for (started_at, path) in &files {
+        if *started_at == active || oldest.binary_search(started_at).is_err() {
+            continue;
+        }
+        if let Err(err) = fs::remove_file(path) {
+            eprintln!("Failed to remove old session {}: {}", path.display(), err);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("keyviz-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn monitor() -> MonitorInfo {
+        MonitorInfo {
+            name: None,
+            scale: 1.0,
+            position: (0, 0),
+            size: (1920, 1080),
+        }
+    }
+
+    fn key(name: &str) -> InputEvent {
+        InputEvent::KeyEvent {
+            pressed: true,
+            name: name.to_string(),
+            repeat: 0,
+        }
+    }
+
+    fn event_count(lines: &[SessionLine]) -> usize {
+        lines
+            .iter()
+            .filter(|line| matches!(line, SessionLine::Event { .. }))
+            .count()
+    }
+
+    #[test]
+    fn sessions_rotate_and_read_back_across_parts() {
+        let dir = temp_dir("rotate");
+        let mut recorder = Recorder::default();
+        let first = recorder.start(dir.clone(), monitor()).unwrap();
+        recorder.session.as_mut().unwrap().max_part_bytes = 200;
+        for _ in 0..10 {
+            recorder.record(&key("KeyA"));
+        }
+        recorder.stop();
+
+        let parts = fs::read_dir(&dir).unwrap().count();
+        assert!(parts > 1, "expected several parts, got {}", parts);
+        // any part leads to the whole session
+        let last = part_path(&dir, parse_part(&first).unwrap().0, parts as u32);
+        for path in [&first, &last] {
+            let lines = read_session(path).unwrap();
+            assert_eq!(event_count(&lines), 10);
+            let headers = lines
+                .iter()
+                .filter(|line| matches!(line, SessionLine::Header { .. }))
+                .count();
+            assert_eq!(headers, parts);
+        }
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn truncated_last_line_is_skipped() {
+        let dir = temp_dir("truncated");
+        let mut recorder = Recorder::default();
+        let path = recorder.start(dir.clone(), monitor()).unwrap();
+        recorder.record(&key("KeyA"));
+        recorder.record(&key("KeyB"));
+        recorder.stop();
+
+        let mut bytes = fs::read(&path).unwrap();
+        bytes.truncate(bytes.len() - 5);
+        fs::write(&path, &bytes).unwrap();
+        assert_eq!(event_count(&read_session(&path).unwrap()), 1);
+
+        // damage anywhere else is still an error
+        let mut bytes = fs::read(&path).unwrap();
+        bytes.splice(0..1, *b"x");
+        fs::write(&path, &bytes).unwrap();
+        assert!(read_session(&path).is_err());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn other_files_are_read_alone() {
+        let dir = temp_dir("alone");
+        let mut recorder = Recorder::default();
+        let path = recorder.start(dir.clone(), monitor()).unwrap();
+        recorder.record(&key("KeyA"));
+        recorder.stop();
+        for name in ["demo-take.jsonl", "demo-other.jsonl", "session-1-x.jsonl"] {
+            fs::copy(&path, dir.join(name)).unwrap();
+        }
+
+        assert_eq!(
+            event_count(&read_session(&dir.join("demo-take.jsonl")).unwrap()),
+            1
+        );
+        assert_eq!(
+            event_count(&read_session(&dir.join("session-1-x.jsonl")).unwrap()),
+            1
+        );
+        assert_eq!(event_count(&read_session(&path).unwrap()), 1);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn pruning_removes_whole_sessions_but_the_active_one() {
+        let dir = temp_dir("prune");
+        // the active session is the oldest here, e.g. after a clock change
+        let active = 1;
+        for started_at in active..=MAX_SESSIONS as u64 + 2 {
+            for part in 1..=2 {
+                File::create(part_path(&dir, started_at, part)).unwrap();
+            }
+        }
+        prune_sessions(&dir, active);
+
+        let mut left: Vec<u64> = fs::read_dir(&dir)
+            .unwrap()
+            .filter_map(|entry| Some(parse_part(&entry.unwrap().path())?.0))
+            .collect();
+        left.sort_unstable();
+        left.dedup();
+        assert_eq!(left.len(), MAX_SESSIONS + 1);
+        assert_eq!(left[..2], [active, 3]);
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), left.len() * 2);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,188 @@
+use serde::{Deserialize, Serialize};
+
+use crate::app::{chord::chord_matches, event::InputEvent};
+
+/// Key name the overlay shows in place of a redacted key.
+pub const REDACTED_KEY: &str = "Redacted";
+// pressing either ends redaction, the token is typed
+const ENTER_KEYS: [&str; 2] = ["Return", "KpReturn"];
+
+/// When typed keys are masked, e.g. while pasting a token on stream.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct RedactionConfig {
+    // chord turning redaction on and off, empty for none
+    pub shortcut: Vec<String>,
+    // keys typed in a row that turn it on and off, e.g. three "BackQuote"
+    pub marker: Vec<String>,
+    // recordings keep the real keys unless this is set
+    pub redact_recordings: bool,
+}
+
+impl Default for RedactionConfig {
+    fn default() -> Self {
+        RedactionConfig {
+            shortcut: vec!["ShiftLeft".to_string(), "F9".to_string()],
+            marker: vec![],
+            redact_recordings: false,
+        }
+    }
+}
+
+/// Masks the names of keys typed while redacting. Only what's emitted is
+/// masked, pressed keys and the toggle shortcut keep the real names.
+#[derive(Debug, Default)]
+pub struct Redaction {
+    pub active: bool,
+    // keys whose press went out masked, so their release does too
+    masked: Vec<String>,
+    // the latest presses, to spot the marker
+    typed: Vec<String>,
+}
+
+impl Redaction {
+    /// Turns redaction on or off, returning whether that changed anything.
+    pub fn set_active(&mut self, active: bool) -> bool {
+        self.typed.clear();
+        let changed = self.active != active;
+        self.active = active;
+        changed
+    }
+
+    /// Returns `event` as it should be shown, and the new state when this
+    /// event turned redaction on or off. Enter ends it and is shown as is,
+    /// the shortcut and the marker toggle it once their last key is down.
+    pub fn redact(
+        &mut self,
+        config: &RedactionConfig,
+        pressed_keys: &[String],
+        any_side: bool,
+        event: InputEvent,
+    ) -> (InputEvent, Option<bool>) {
+        let InputEvent::KeyEvent {
+            pressed,
+            name,
+            repeat,
+        } = event
+        else {
+            return (event, None);
+        };
+        let fresh_press = pressed && repeat == 0;
+
+        let mut toggled = None;
+        if fresh_press && self.active && ENTER_KEYS.contains(&name.as_str()) {
+            self.set_active(false);
+            toggled = Some(false);
+        }
+
+        let masked = if fresh_press {
+            if self.active && !self.masked.contains(&name) {
+                self.masked.push(name.clone());
+            }
+            self.active
+        } else if pressed {
+            self.masked.contains(&name)
+        } else {
+            let masked = self.masked.contains(&name);
+            self.masked.retain(|key| key != &name);
+            masked
+        };
+
+        if fresh_press && toggled.is_none() {
+            self.typed.push(name.clone());
+            // only as many presses as the marker has keys are kept
+            let excess = self.typed.len().saturating_sub(config.marker.len());
+            self.typed.drain(..excess);
+            let shortcut = chord_matches(&config.shortcut, pressed_keys, any_side);
+            let marker = !config.marker.is_empty() && config.marker == self.typed;
+            if shortcut || marker {
+                let active = !self.active;
+                self.set_active(active);
+                toggled = Some(active);
+            }
+        }
+
+        let name = if masked {
+            REDACTED_KEY.to_string()
+        } else {
+            name
+        };
+        let event = InputEvent::KeyEvent {
+            pressed,
+            name,
+            repeat,
+        };
+        (event, toggled)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(name: &str, pressed: bool) -> InputEvent {
+        InputEvent::KeyEvent {
+            pressed,
+            name: name.to_string(),
+            repeat: 0,
+        }
+    }
+
+    fn shown(event: InputEvent) -> (String, bool) {
+        match event {
+            InputEvent::KeyEvent { name, pressed, .. } => (name, pressed),
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn masks_between_marker_and_enter() {
+        let config = RedactionConfig {
+            shortcut: vec![],
+            marker: vec!["BackQuote".into(), "BackQuote".into()],
+            redact_recordings: false,
+        };
+        let mut redaction = Redaction::default();
+        let mut type_key = |name: &str| {
+            let (press, toggled) = redaction.redact(&config, &[], false, key(name, true));
+            let (release, _) = redaction.redact(&config, &[], false, key(name, false));
+            (shown(press).0, shown(release).0, toggled)
+        };
+
+        assert_eq!(
+            type_key("BackQuote"),
+            ("BackQuote".into(), "BackQuote".into(), None)
+        );
+        assert_eq!(
+            type_key("BackQuote"),
+            ("BackQuote".into(), "BackQuote".into(), Some(true))
+        );
+        assert_eq!(
+            type_key("KeyA"),
+            ("Redacted".into(), "Redacted".into(), None)
+        );
+        assert_eq!(
+            type_key("Return"),
+            ("Return".into(), "Return".into(), Some(false))
+        );
+        assert_eq!(type_key("KeyA"), ("KeyA".into(), "KeyA".into(), None));
+    }
+
+    #[test]
+    fn releases_match_their_press() {
+        let config = RedactionConfig::default();
+        let mut redaction = Redaction::default();
+        let held = ["ShiftLeft", "F9", "KeyX"].map(String::from);
+        // the shortcut turns it on, its own keys were pressed in the clear
+        redaction.redact(&config, &held[..1], false, key("ShiftLeft", true));
+        let (_, toggled) = redaction.redact(&config, &held[..2], false, key("F9", true));
+        assert_eq!(toggled, Some(true));
+        let (press, _) = redaction.redact(&config, &held, false, key("KeyX", true));
+        assert_eq!(shown(press), ("Redacted".into(), true));
+        assert!(redaction.set_active(false));
+        let (release, _) = redaction.redact(&config, &held, false, key("KeyX", false));
+        assert_eq!(shown(release), ("Redacted".into(), false));
+        let (release, _) = redaction.redact(&config, &[], false, key("F9", false));
+        assert_eq!(shown(release), ("F9".into(), false));
+    }
+}
@@ -0,0 +1,579 @@
+use std::{
+    fs::{self, File},
+    io::{self, BufWriter},
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use ab_glyph::{point, Font, FontRef, PxScale, ScaleFont};
+use serde::Deserialize;
+use tiny_skia::{
+    Color, FillRule, Paint, Path as SkPath, PathBuilder, Pixmap, PremultipliedColorU8, Stroke,
+    Transform,
+};
+
+use crate::app::{
+    export::{group_chords, Chord, ChordOptions},
+    recorder::{read_session, SessionLine},
+    style::{parse_color, KeyStyle},
+};
+
+const FONT: &[u8] = include_bytes!("../../assets/fonts/DejaVuSans-Bold.ttf");
+// longest a single animation frame may last, apng delays are u16
+const MAX_FRAME_MS: u64 = 60_000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RenderFormat {
+    // a numbered sequence, one file per frame
+    Png,
+    Gif,
+    Apng,
+}
+
+impl FromStr for RenderFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "png" => Ok(RenderFormat::Png),
+            "gif" => Ok(RenderFormat::Gif),
+            "apng" => Ok(RenderFormat::Apng),
+            _ => Err(format!("unknown render format {}", s)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct RenderOptions {
+    pub format: RenderFormat,
+    pub fps: u32,
+}
+
+impl Default for RenderOptions {
+    fn default() -> Self {
+        RenderOptions {
+            format: RenderFormat::Gif,
+            fps: 30,
+        }
+    }
+}
+
+/// What a frame shows, frames with the same scene look the same.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Scene {
+    chord: Option<usize>,
+    // 0 to 255
+    opacity: u8,
+}
+
+/// Keycap geometry for the current style, everything derived from the
+/// text size like the overlay does.
+struct Layout<'a> {
+    style: &'a KeyStyle,
+    font: FontRef<'a>,
+    size: f32,
+}
+
+impl<'a> Layout<'a> {
+    fn padding_inline(&self) -> f32 {
+        let radius = self.style.border.radius as f32;
+        self.size * if radius < 0.75 { 0.5 } else { radius - 0.25 }
+    }
+
+    fn key_height(&self) -> f32 {
+        self.size * 2.5
+    }
+
+    fn key_gap(&self) -> f32 {
+        self.size * 0.3
+    }
+
+    fn group_padding(&self) -> f32 {
+        if self.style.background.enabled {
+            self.size * 0.4
+        } else {
+            0.0
+        }
+    }
+
+    fn border_width(&self) -> f32 {
+        if self.style.border.enabled {
+            self.style.border.width as f32
+        } else {
+            0.0
+        }
+    }
+
+    fn label(&self, key: &str) -> String {
+        match self.style.text.caps.as_str() {
+            "uppercase" => key.to_uppercase(),
+            "lowercase" => key.to_lowercase(),
+            _ => key.to_string(),
+        }
+    }
+
+    fn text_width(&self, text: &str) -> f32 {
+        let font = self.font.as_scaled(PxScale::from(self.size));
+        let mut width = 0.0;
+        let mut previous = None;
+        for chr in text.chars() {
+            let glyph = font.glyph_id(chr);
+            if let Some(previous) = previous {
+                width += font.kern(previous, glyph);
+            }
+            width += font.h_advance(glyph);
+            previous = Some(glyph);
+        }
+        width
+    }
+
+    fn key_width(&self, key: &str) -> f32 {
+        let content = self.text_width(&self.label(key)) + 2.0 * self.padding_inline();
+        (content + 2.0 * self.border_width()).max(self.size * 2.25)
+    }
+
+    fn chord_size(&self, chord: &Chord) -> (f32, f32) {
+        let keys: f32 = chord.keys.iter().map(|key| self.key_width(key)).sum();
+        let gaps = self.key_gap() * chord.keys.len().saturating_sub(1) as f32;
+        let padding = 2.0 * self.group_padding();
+        (keys + gaps + padding, self.key_height() + padding)
+    }
+}
+
+/// Renders the session at `path` to `output`, a directory for png
+/// sequences or a file otherwise, and returns the path written.
+pub fn render_session(
+    path: &Path,
+    output: Option<&Path>,
+    options: &RenderOptions,
+    chord_options: &ChordOptions,
+    style: &KeyStyle,
+) -> io::Result<PathBuf> {
+    let events: Vec<_> = read_session(path)?
+        .into_iter()
+        .filter_map(|line| match line {
+            SessionLine::Event { t, event } => Some((t, event)),
+            _ => None,
+        })
+        .collect();
+    let chords = group_chords(&events, chord_options);
+    if chords.is_empty() {
+        let message = "nothing to render, the session has no shortcuts";
+        return Err(io::Error::new(io::ErrorKind::InvalidData, message));
+    }
+    if options.fps == 0 {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "fps must be above 0",
+        ));
+    }
+
+    let layout = Layout {
+        style,
+        font: FontRef::try_from_slice(FONT).expect("bundled font is valid"),
+        size: style.text.size.max(1.0) as f32,
+    };
+    let margin = layout.size * 0.5;
+    let (width, height) = chords.iter().fold((0.0f32, 0.0f32), |(w, h), chord| {
+        let (chord_w, chord_h) = layout.chord_size(chord);
+        (w.max(chord_w), h.max(chord_h))
+    });
+    let width = (width + 2.0 * margin).ceil() as u32;
+    let height = (height + 2.0 * margin).ceil() as u32;
+
+    let frames = timeline(&chords, style, options.fps);
+    let output = match output {
+        Some(output) => output.to_path_buf(),
+        None if options.format == RenderFormat::Png => path.with_extension("frames"),
+        None if options.format == RenderFormat::Gif => path.with_extension("gif"),
+        None => path.with_extension("png"),
+    };
+    let draw = |scene: Scene| -> io::Result<Pixmap> {
+        let mut pixmap = Pixmap::new(width, height)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "frame is too large"))?;
+        if let Some(index) = scene.chord {
+            draw_chord(&mut pixmap, &layout, &chords[index], scene.opacity);
+        }
+        Ok(pixmap)
+    };
+
+    match options.format {
+        RenderFormat::Png => {
+            fs::create_dir_all(&output)?;
+            let frame_ms = 1000.0 / options.fps as f64;
+            let mut number = 0;
+            for (scene, duration_ms) in &frames {
+                let pixmap = draw(*scene)?;
+                let data = pixmap
+                    .encode_png()
+                    .map_err(|e| io::Error::other(e.to_string()))?;
+                // constant frame rate, so video tools can pick it up
+                let count = (*duration_ms as f64 / frame_ms).round().max(1.0) as u32;
+                for _ in 0..count {
+                    fs::write(output.join(format!("frame-{:05}.png", number)), &data)?;
+                    number += 1;
+                }
+            }
+        }
+        RenderFormat::Gif => {
+            let (Ok(gif_width), Ok(gif_height)) = (u16::try_from(width), u16::try_from(height))
+            else {
+                let message = format!(
+                    "{}x{} frames are too large for a gif, which allows {} pixels a side",
+                    width,
+                    height,
+                    u16::MAX
+                );
+                return Err(io::Error::new(io::ErrorKind::InvalidInput, message));
+            };
+            let mut encoder = gif::Encoder::new(
+                BufWriter::new(File::create(&output)?),
+                gif_width,
+                gif_height,
+                &[],
+            )
+            .map_err(to_io)?;
+            encoder.set_repeat(gif::Repeat::Infinite).map_err(to_io)?;
+            let delays = gif_delays(frames.iter().map(|(_, duration_ms)| *duration_ms));
+            for ((scene, _), delay) in frames.iter().zip(delays) {
+                // gif transparency is all or nothing, translucent pixels get a solid color
+                let mut rgba = straight_rgba(&draw(*scene)?);
+                let mut frame = gif::Frame::from_rgba_speed(gif_width, gif_height, &mut rgba, 10);
+                frame.delay = delay;
+                frame.dispose = gif::DisposalMethod::Background;
+                encoder.write_frame(&frame).map_err(to_io)?;
+            }
+        }
+        RenderFormat::Apng => {
+            let mut encoder =
+                png::Encoder::new(BufWriter::new(File::create(&output)?), width, height);
+            encoder.set_color(png::ColorType::Rgba);
+            encoder.set_depth(png::BitDepth::Eight);
+            encoder
+                .set_animated(frames.len() as u32, 0)
+                .map_err(to_io)?;
+            let mut writer = encoder.write_header().map_err(to_io)?;
+            for (scene, duration_ms) in &frames {
+                writer
+                    .set_frame_delay(*duration_ms as u16, 1000)
+                    .map_err(to_io)?;
+                writer
+                    .set_dispose_op(png::DisposeOp::Background)
+                    .map_err(to_io)?;
+                writer
+                    .write_image_data(&straight_rgba(&draw(*scene)?))
+                    .map_err(to_io)?;
+            }
+            writer.finish().map_err(to_io)?;
+        }
+    }
+    Ok(output)
+}
+
+fn to_io(err: impl std::error::Error + Send + Sync + 'static) -> io::Error {
+    io::Error::other(err)
+}
+
+/// Gif delays count centiseconds, the rounding is carried over so the
+/// animation keeps time. Frames last at least one centisecond.
+fn gif_delays(durations_ms: impl Iterator<Item = u64>) -> Vec<u16> {
+    let mut elapsed_ms = 0;
+    let mut written_cs = 0;
+    durations_ms
+        .map(|duration_ms| {
+            elapsed_ms += duration_ms;
+            let delay = (elapsed_ms / 10).saturating_sub(written_cs).max(1);
+            written_cs += delay;
+            // frames are capped at MAX_FRAME_MS, well within a u16
+            delay as u16
+        })
+        .collect()
+}
+
+/// Samples the session at `fps` and merges consecutive frames showing the
+/// same scene, so idle stretches don't cost a frame each.
+fn timeline(chords: &[Chord], style: &KeyStyle, fps: u32) -> Vec<(Scene, u64)> {
+    let fade_ms = if style.appearance.animation == "none" {
+        0.0
+    } else {
+        style.appearance.animation_duration * 1000.0
+    };
+    let end_ms = chords
+        .last()
+        .map_or(0, |chord| chord.end_ms + fade_ms as u64);
+    let frame_ms = 1000.0 / fps as f64;
+
+    let mut frames: Vec<(Scene, u64)> = vec![];
+    let mut current = 0;
+    let mut frame = 0u64;
+    loop {
+        let t = frame as f64 * frame_ms;
+        if t > end_ms as f64 {
+            break;
+        }
+        while current < chords.len() && (chords[current].end_ms as f64) <= t {
+            current += 1;
+        }
+        let scene = match chords.get(current) {
+            Some(chord) if chord.start_ms as f64 <= t => {
+                // no fade between chords that follow each other
+                let fades_in = current == 0 || chords[current - 1].end_ms < chord.start_ms;
+                let fades_out = chords
+                    .get(current + 1)
+                    .is_none_or(|next| next.start_ms > chord.end_ms);
+                let mut opacity = 1.0f64;
+                if fade_ms > 0.0 && fades_in {
+                    opacity = opacity.min((t - chord.start_ms as f64) / fade_ms);
+                }
+                if fade_ms > 0.0 && fades_out {
+                    opacity = opacity.min((chord.end_ms as f64 - t) / fade_ms);
+                }
+                Scene {
+                    chord: Some(current),
+                    opacity: (opacity.clamp(0.0, 1.0) * 255.0).round() as u8,
+                }
+            }
+            _ => Scene {
+                chord: None,
+                opacity: 0,
+            },
+        };
+        let end = ((frame + 1) as f64 * frame_ms) as u64;
+        let start = (t as u64).min(end);
+        match frames.last_mut() {
+            Some((last, duration)) if *last == scene && *duration + end - start <= MAX_FRAME_MS => {
+                *duration += end - start;
+            }
+            _ => frames.push((scene, end - start)),
+        }
+        frame += 1;
+    }
+    frames
+}
+
+fn draw_chord(pixmap: &mut Pixmap, layout: &Layout, chord: &Chord, opacity: u8) {
+    let style = layout.style;
+    let opacity = opacity as f32 / 255.0;
+    let size = layout.size;
+    let (chord_w, chord_h) = layout.chord_size(chord);
+    // centered, like the default bottom-center alignment
+    let left = ((pixmap.width() as f32 - chord_w) / 2.0).round();
+    let top = ((pixmap.height() as f32 - chord_h) / 2.0).round();
+
+    if style.background.enabled {
+        let radius = style.border.radius as f32 * size * 1.75;
+        if let Some(path) = rounded_rect(left, top, chord_w, chord_h, radius) {
+            fill(pixmap, &path, &style.background.color, opacity);
+        }
+    }
+
+    let radius = style.border.radius as f32 * size * 1.25;
+    let border = layout.border_width();
+    let key_top = top + layout.group_padding();
+    let mut x = left + layout.group_padding();
+    for key in &chord.keys {
+        let key_w = layout.key_width(key);
+        let face_h = size * 2.25;
+        // the darker side peeks out below the face
+        let inset = border / 2.0;
+        let side = rounded_rect(
+            x + inset,
+            key_top + size * 0.25 + inset,
+            key_w - border,
+            face_h - border,
+            radius,
+        );
+        let face = rounded_rect(
+            x + inset,
+            key_top + inset,
+            key_w - border,
+            face_h - border,
+            radius,
+        );
+        let colors = [&style.color.secondary_color, &style.color.color];
+        for (path, color) in [side, face].iter().zip(colors) {
+            let Some(path) = path else {
+                continue;
+            };
+            fill(pixmap, path, color, opacity);
+            if border > 0.0 {
+                stroke(pixmap, path, &style.border.color, border, opacity);
+            }
+        }
+
+        let label = layout.label(key);
+        let text_x = x + (key_w - layout.text_width(&label)) / 2.0;
+        let text_y = key_top + face_h / 2.0;
+        draw_text(pixmap, layout, &label, (text_x, text_y), opacity);
+        x += key_w + layout.key_gap();
+    }
+}
+
+fn paint(color: &str, opacity: f32) -> Paint<'static> {
+    let [r, g, b, a] = parse_color(color).unwrap_or([0, 0, 0, 255]);
+    let mut color = Color::from_rgba8(r, g, b, a);
+    color.apply_opacity(opacity);
+    let mut paint = Paint::default();
+    paint.set_color(color);
+    paint.anti_alias = true;
+    paint
+}
+
+fn fill(pixmap: &mut Pixmap, path: &SkPath, color: &str, opacity: f32) {
+    let paint = paint(color, opacity);
+    pixmap.fill_path(path, &paint, FillRule::Winding, Transform::identity(), None);
+}
+
+fn stroke(pixmap: &mut Pixmap, path: &SkPath, color: &str, width: f32, opacity: f32) {
+    let paint = paint(color, opacity);
+    let stroke = Stroke {
+        width,
+        ..Stroke::default()
+    };
+    pixmap.stroke_path(path, &paint, &stroke, Transform::identity(), None);
+}
+
+/// Draws `text` starting at `x`, vertically centered on `y`.
+fn draw_text(pixmap: &mut Pixmap, layout: &Layout, text: &str, (x, y): (f32, f32), opacity: f32) {
+    let scale = PxScale::from(layout.size);
+    let font = layout.font.as_scaled(scale);
+    let [r, g, b, a] = parse_color(&layout.style.text.color).unwrap_or([0, 0, 0, 255]);
+    let alpha = a as f32 / 255.0 * opacity;
+    let baseline = y + (font.ascent() + font.descent()) / 2.0;
+
+    let width = pixmap.width() as i32;
+    let height = pixmap.height() as i32;
+    let pixels = pixmap.pixels_mut();
+    let mut caret = x;
+    let mut previous = None;
+    for chr in text.chars() {
+        let id = font.glyph_id(chr);
+        if let Some(previous) = previous {
+            caret += font.kern(previous, id);
+        }
+        previous = Some(id);
+        let glyph = id.with_scale_and_position(scale, point(caret, baseline));
+        caret += font.h_advance(id);
+        let Some(outline) = layout.font.outline_glyph(glyph) else {
+            continue;
+        };
+        let bounds = outline.px_bounds();
+        outline.draw(|gx, gy, coverage| {
+            let px = bounds.min.x as i32 + gx as i32;
+            let py = bounds.min.y as i32 + gy as i32;
+            if px < 0 || py < 0 || px >= width || py >= height {
+                return;
+            }
+            let pixel = &mut pixels[(py * width + px) as usize];
+            *pixel = blend(*pixel, [r, g, b], alpha * coverage);
+        });
+    }
+}
+
+/// Source-over of a straight color onto a premultiplied pixel.
+fn blend(dst: PremultipliedColorU8, [r, g, b]: [u8; 3], alpha: f32) -> PremultipliedColorU8 {
+    let alpha = alpha.clamp(0.0, 1.0);
+    let keep = 1.0 - alpha;
+    let mix = |src: u8, dst: u8| (src as f32 * alpha + dst as f32 * keep).round() as u8;
+    let out_a = mix(255, dst.alpha());
+    // premultiplied channels never exceed alpha
+    PremultipliedColorU8::from_rgba(
+        mix(r, dst.red()).min(out_a),
+        mix(g, dst.green()).min(out_a),
+        mix(b, dst.blue()).min(out_a),
+        out_a,
+    )
+    .unwrap_or(dst)
+}
+
+fn straight_rgba(pixmap: &Pixmap) -> Vec<u8> {
+    pixmap
+        .pixels()
+        .iter()
+        .flat_map(|pixel| {
+            let color = pixel.demultiply();
+            [color.red(), color.green(), color.blue(), color.alpha()]
+        })
+        .collect()
+}
+
+fn rounded_rect(x: f32, y: f32, w: f32, h: f32, radius: f32) -> Option<SkPath> {
+    let r = radius.min(w / 2.0).min(h / 2.0).max(0.0);
+    // control point distance approximating a quarter circle
+    let k = r * 0.552_284_8;
+    let mut pb = PathBuilder::new();
+    pb.move_to(x + r, y);
+    pb.line_to(x + w - r, y);
+    pb.cubic_to(x + w - r + k, y, x + w, y + r - k, x + w, y + r);
+    pb.line_to(x + w, y + h - r);
+    pb.cubic_to(x + w, y + h - r + k, x + w - r + k, y + h, x + w - r, y + h);
+    pb.line_to(x + r, y + h);
+    pb.cubic_to(x + r - k, y + h, x, y + h - r + k, x, y + h - r);
+    pb.line_to(x, y + r);
+    pb.cubic_to(x, y + r - k, x + r - k, y, x + r, y);
+    pb.close();
+    pb.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chord(start_ms: u64, end_ms: u64) -> Chord {
+        Chord {
+            start_ms,
+            end_ms,
+            keys: vec!["A".into()],
+        }
+    }
+
+    fn scenes(frames: &[(Scene, u64)]) -> Vec<(Option<usize>, u8, u64)> {
+        frames
+            .iter()
+            .map(|(scene, duration)| (scene.chord, scene.opacity, *duration))
+            .collect()
+    }
+
+    #[test]
+    fn frames_fade_and_merge_while_unchanged() {
+        // fades over 250ms by default
+        let frames = timeline(&[chord(0, 1_000)], &KeyStyle::default(), 10);
+        assert_eq!(
+            scenes(&frames),
+            [
+                (Some(0), 0, 100),
+                (Some(0), 102, 100),
+                (Some(0), 204, 100),
+                (Some(0), 255, 500),
+                (Some(0), 204, 100),
+                (Some(0), 102, 100),
+                (None, 0, 300),
+            ]
+        );
+    }
+
+    #[test]
+    fn long_frames_are_split() {
+        let mut style = KeyStyle::default();
+        style.appearance.animation = "none".into();
+        let frames = timeline(&[chord(0, 150_000)], &style, 1);
+        assert_eq!(
+            scenes(&frames),
+            [
+                (Some(0), 255, MAX_FRAME_MS),
+                (Some(0), 255, MAX_FRAME_MS),
+                (Some(0), 255, 30_000),
+                (None, 0, 1_000),
+            ]
+        );
+    }
+
+    #[test]
+    fn gif_delays_carry_the_rounding_over() {
+        // 30fps frames don't divide into centiseconds
+        assert_eq!(gif_delays([33, 33, 34].into_iter()), [3, 3, 4]);
+        assert_eq!(gif_delays([15, 15, 15, 15].into_iter()), [1, 2, 1, 2]);
+        assert_eq!(gif_delays([4, 4, 12].into_iter()), [1, 1, 1]);
+    }
+}
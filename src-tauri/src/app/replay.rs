@@ -0,0 +1,217 @@
+use std::{
+    path::Path,
+    sync::{Arc, Condvar, Mutex},
+    thread,
+    time::{Duration, Instant},
+};
+
+use serde::Serialize;
+use tauri::{AppHandle, Emitter, Manager};
+
+use crate::app::{
+    event::InputEvent,
+    recorder::{read_session, SessionLine},
+    state::AppState,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct ReplayStatus {
+    pub position_ms: u64,
+    pub duration_ms: u64,
+    pub speed: f64,
+    pub paused: bool,
+}
+
+/// Timeline of a running replay, shared between the commands and the replay thread.
+struct Timeline {
+    // position at `anchor`
+    position_ms: f64,
+    anchor: Instant,
+    duration_ms: u64,
+    speed: f64,
+    paused: bool,
+    stopped: bool,
+    // set by seeks, the thread has to find its place again
+    seeked: bool,
+}
+
+impl Timeline {
+    fn position_ms(&self) -> f64 {
+        if self.paused {
+            self.position_ms
+        } else {
+            self.position_ms + self.anchor.elapsed().as_secs_f64() * 1000.0 * self.speed
+        }
+    }
+
+    fn reanchor(&mut self) {
+        self.position_ms = self.position_ms();
+        self.anchor = Instant::now();
+    }
+}
+
+/// Handle to a session replaying through the overlay. The live listener
+/// is muted for as long as it exists in `AppState`.
+#[derive(Clone)]
+pub struct Replay {
+    timeline: Arc<(Mutex<Timeline>, Condvar)>,
+}
+
+impl Replay {
+    /// Loads the session at `path` and starts emitting its events to the
+    /// main window on their original timeline, scaled by `speed`.
+    pub fn start(app: &AppHandle, path: &Path, speed: f64) -> Result<Replay, String> {
+        let events: Vec<(u64, InputEvent)> = read_session(path)
+            .map_err(|e| e.to_string())?
+            .into_iter()
+            .filter_map(|line| match line {
+                SessionLine::Event { t, event } => Some((t, event)),
+                _ => None,
+            })
+            .collect();
+        let duration_ms = events.last().map_or(0, |(t, _)| *t);
+
+        let replay = Replay {
+            timeline: Arc::new((
+                Mutex::new(Timeline {
+                    position_ms: 0.0,
+                    anchor: Instant::now(),
+                    duration_ms,
+                    speed: valid_speed(speed)?,
+                    paused: false,
+                    stopped: false,
+                    seeked: false,
+                }),
+                Condvar::new(),
+            )),
+        };
+        let thread_replay = replay.clone();
+        let app_handle = app.clone();
+        thread::spawn(move || thread_replay.run(app_handle, events));
+        Ok(replay)
+    }
+
+    pub fn status(&self) -> ReplayStatus {
+        let timeline = self.timeline.0.lock().unwrap();
+        ReplayStatus {
+            position_ms: (timeline.position_ms() as u64).min(timeline.duration_ms),
+            duration_ms: timeline.duration_ms,
+            speed: timeline.speed,
+            paused: timeline.paused,
+        }
+    }
+
+    fn update(&self, change: impl FnOnce(&mut Timeline)) {
+        let (lock, changed) = &*self.timeline;
+        let mut timeline = lock.lock().unwrap();
+        timeline.reanchor();
+        change(&mut timeline);
+        changed.notify_all();
+    }
+
+    pub fn pause(&self) {
+        self.update(|timeline| timeline.paused = true);
+    }
+
+    pub fn resume(&self) {
+        self.update(|timeline| timeline.paused = false);
+    }
+
+    pub fn stop(&self) {
+        self.update(|timeline| timeline.stopped = true);
+    }
+
+    pub fn seek(&self, position_ms: u64) {
+        self.update(|timeline| {
+            timeline.position_ms = position_ms.min(timeline.duration_ms) as f64;
+            timeline.seeked = true;
+        });
+    }
+
+    pub fn set_speed(&self, speed: f64) -> Result<(), String> {
+        let speed = valid_speed(speed)?;
+        self.update(|timeline| timeline.speed = speed);
+        Ok(())
+    }
+
+    fn run(self, app: AppHandle, events: Vec<(u64, InputEvent)>) {
+        let (lock, changed) = &*self.timeline;
+        let mut pressed_keys: Vec<String> = vec![];
+        let mut next = 0;
+
+        let mut timeline = lock.lock().unwrap();
+        loop {
+            if timeline.stopped {
+                break;
+            }
+            if timeline.seeked {
+                timeline.seeked = false;
+                // start over from a clean overlay
+                release_keys(&app, &mut pressed_keys);
+                let position = timeline.position_ms as u64;
+                next = events.partition_point(|(t, _)| *t < position);
+            }
+            if timeline.paused {
+                timeline = changed.wait(timeline).unwrap();
+                continue;
+            }
+            let Some((t, event)) = events.get(next) else {
+                break;
+            };
+            let position = timeline.position_ms();
+            if (*t as f64) > position {
+                let wait_ms = (*t as f64 - position) / timeline.speed;
+                timeline = changed
+                    .wait_timeout(timeline, Duration::from_secs_f64(wait_ms / 1000.0))
+                    .unwrap()
+                    .0;
+                continue;
+            }
+            if let InputEvent::KeyEvent { pressed, name, .. } = event {
+                pressed_keys.retain(|key| key != name);
+                if *pressed {
+                    pressed_keys.push(name.clone());
+                }
+            }
+            app.emit_to("main", "input-event", event).unwrap();
+            next += 1;
+        }
+        drop(timeline);
+
+        release_keys(&app, &mut pressed_keys);
+        // unmute the live listener, unless a newer replay took over
+        let state = app.state::<Mutex<AppState>>();
+        let mut app_state = state.lock().unwrap();
+        if app_state
+            .replay
+            .as_ref()
+            .is_some_and(|replay| Arc::ptr_eq(&replay.timeline, &self.timeline))
+        {
+            app_state.replay = None;
+        }
+        app.emit("replay-finished", ()).unwrap();
+    }
+}
+
+fn valid_speed(speed: f64) -> Result<f64, String> {
+    if speed.is_finite() && speed > 0.0 {
+        Ok(speed)
+    } else {
+        Err(format!("invalid replay speed {}", speed))
+    }
+}
+
+fn release_keys(app: &AppHandle, pressed_keys: &mut Vec<String>) {
+    for name in pressed_keys.drain(..) {
+        app.emit_to(
+            "main",
+            "input-event",
+            InputEvent::KeyEvent {
+                pressed: false,
+                name,
+                repeat: 0,
+            },
+        )
+        .unwrap();
+    }
+}
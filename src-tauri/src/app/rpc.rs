@@ -0,0 +1,328 @@
+use std::{
+    fs,
+    io::{self, BufRead, BufReader, Write},
+    net::Shutdown,
+    os::unix::{
+        fs::{DirBuilderExt, MetadataExt, PermissionsExt},
+        net::{UnixListener, UnixStream},
+    },
+    path::PathBuf,
+    sync::{
+        mpsc::{self, SyncSender, TrySendError},
+        Arc, Mutex,
+    },
+    thread,
+};
+
+use serde::Deserialize;
+use serde_json::{json, Value};
+use tauri::{AppHandle, EventId, Listener, Manager};
+
+use crate::app::{
+    actions::{self, Action},
+    commands,
+    state::AppState,
+};
+
+/// Events a client can subscribe to, pushed as `event` notifications.
+const EVENTS: [&str; 4] = [
+    "input-event",
+    "listening-toggle",
+    "monitor-changed",
+    "recording-status",
+];
+
+// json-rpc 2.0 error codes
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+const SERVER_ERROR: i64 = -32000;
+
+/// Lines queued for a client before it counts as stuck and is dropped.
+const CLIENT_QUEUE: usize = 1024;
+
+/// Where the control socket lives, `$XDG_RUNTIME_DIR/keyviz.sock`, or in a
+/// directory only we can enter under the temp dir without one.
+pub fn socket_path() -> io::Result<PathBuf> {
+    if let Some(dir) = std::env::var_os("XDG_RUNTIME_DIR") {
+        return Ok(PathBuf::from(dir).join("keyviz.sock"));
+    }
+    // the temp dir may be shared between users, e.g. /tmp
+    let uid = unsafe { libc::getuid() };
+    let dir = std::env::temp_dir().join(format!("keyviz-{}", uid));
+    match fs::DirBuilder::new().mode(0o700).create(&dir) {
+        Ok(()) => {}
+        Err(err) if err.kind() == io::ErrorKind::AlreadyExists => {
+            // left by an earlier run, or planted by someone else
+            let metadata = fs::symlink_metadata(&dir)?;
+            if !metadata.is_dir() || metadata.uid() != uid || metadata.mode() & 0o077 != 0 {
+                return Err(io::Error::new(
+                    io::ErrorKind::PermissionDenied,
+                    format!("{} is not private to this user", dir.display()),
+                ));
+            }
+        }
+        Err(err) => return Err(err),
+    }
+    Ok(dir.join("keyviz.sock"))
+}
+
+/// Serves newline delimited JSON-RPC 2.0 on the control socket, one
+/// request or response per line.
+pub fn start(app: AppHandle) -> io::Result<PathBuf> {
+    let path = socket_path()?;
+    if path.exists() {
+        if UnixStream::connect(&path).is_ok() {
+            return Err(io::Error::new(
+                io::ErrorKind::AddrInUse,
+                format!("{} is served by another process", path.display()),
+            ));
+        }
+        // left behind by an instance that didn't get to clean up
+        fs::remove_file(&path)?;
+    }
+    let listener = UnixListener::bind(&path)?;
+    fs::set_permissions(&path, fs::Permissions::from_mode(0o600))?;
+
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            match stream {
+                Ok(stream) => {
+                    let app = app.clone();
+                    thread::spawn(move || serve(app, stream));
+                }
+                Err(err) => eprintln!("Control socket failed to accept: {}", err),
+            }
+        }
+    });
+    println!("🔌 Control socket listening on {}", path.display());
+    Ok(path)
+}
+
+/// Lines for a client's writer thread, so events never wait on a slow reader.
+#[derive(Clone)]
+struct Outbox {
+    sender: SyncSender<String>,
+    stream: Arc<UnixStream>,
+}
+
+impl Outbox {
+    /// Queues `line`, dropping the client once it stops keeping up. Returns
+    /// whether the client is still there.
+    fn send(&self, line: String) -> bool {
+        match self.sender.try_send(line) {
+            Ok(()) => true,
+            Err(TrySendError::Full(_)) => {
+                eprintln!("Control socket client fell behind, disconnecting it");
+                // ends its reader, which unsubscribes
+                let _ = self.stream.shutdown(Shutdown::Both);
+                false
+            }
+            Err(TrySendError::Disconnected(_)) => false,
+        }
+    }
+}
+
+struct Client {
+    outbox: Outbox,
+    subscriptions: Vec<EventId>,
+}
+
+fn serve(app: AppHandle, stream: UnixStream) {
+    let (Ok(mut writer), Ok(shutdown)) = (stream.try_clone(), stream.try_clone()) else {
+        return;
+    };
+    let (sender, receiver) = mpsc::sync_channel::<String>(CLIENT_QUEUE);
+    thread::spawn(move || {
+        for line in receiver {
+            if writeln!(writer, "{}", line).is_err() {
+                break;
+            }
+        }
+    });
+
+    let mut client = Client {
+        outbox: Outbox {
+            sender,
+            stream: Arc::new(shutdown),
+        },
+        subscriptions: vec![],
+    };
+    for line in BufReader::new(stream).lines() {
+        let Ok(line) = line else {
+            break;
+        };
+        if line.trim().is_empty() {
+            continue;
+        }
+        if let Some(response) = handle(&app, &mut client, &line) {
+            if !client.outbox.send(response.to_string()) {
+                break;
+            }
+        }
+    }
+    unsubscribe(&app, &mut client);
+}
+
+#[derive(Debug, Deserialize)]
+struct Request {
+    jsonrpc: String,
+    // absent for notifications, which get no response
+    id: Option<Value>,
+    method: String,
+    #[serde(default)]
+    params: Value,
+}
+
+struct RpcError {
+    code: i64,
+    message: String,
+}
+
+impl RpcError {
+    fn new(code: i64, message: impl ToString) -> Self {
+        RpcError {
+            code,
+            message: message.to_string(),
+        }
+    }
+}
+
+fn handle(app: &AppHandle, client: &mut Client, line: &str) -> Option<Value> {
+    let value: Value = match serde_json::from_str(line) {
+        Ok(value) => value,
+        Err(err) => return Some(error_response(Value::Null, RpcError::new(PARSE_ERROR, err))),
+    };
+    let id = value.get("id").cloned().unwrap_or(Value::Null);
+    let request = match serde_json::from_value::<Request>(value) {
+        Ok(request) if request.jsonrpc == "2.0" => request,
+        Ok(_) => {
+            return Some(error_response(
+                id,
+                RpcError::new(INVALID_REQUEST, "not 2.0"),
+            ))
+        }
+        Err(err) => return Some(error_response(id, RpcError::new(INVALID_REQUEST, err))),
+    };
+
+    let result = call(app, client, &request.method, request.params);
+    let id = request.id?;
+    Some(match result {
+        Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+        Err(err) => error_response(id, err),
+    })
+}
+
+fn error_response(id: Value, err: RpcError) -> Value {
+    json!({
+        "jsonrpc": "2.0",
+        "id": id,
+        "error": { "code": err.code, "message": err.message },
+    })
+}
+
+fn parse_params<T: for<'de> Deserialize<'de>>(params: Value) -> Result<T, RpcError> {
+    // no params at all reads as an empty object
+    let params = if params.is_null() { json!({}) } else { params };
+    serde_json::from_value(params).map_err(|e| RpcError::new(INVALID_PARAMS, e))
+}
+
+#[derive(Deserialize)]
+struct SubscribeParams {
+    events: Option<Vec<String>>,
+}
+
+fn call(
+    app: &AppHandle,
+    client: &mut Client,
+    method: &str,
+    params: Value,
+) -> Result<Value, RpcError> {
+    let state = app.state::<Mutex<AppState>>();
+    // same paths as the tray, answers whether we listen afterwards
+    let listening = |action| {
+        actions::run(app, action);
+        json!(state.lock().unwrap().listening)
+    };
+    match method {
+        "toggle" => Ok(listening(Action::Toggle)),
+        "start" => Ok(listening(Action::Start)),
+        "stop" => Ok(listening(Action::Stop)),
+        "getState" => {
+            let app_state = state.lock().unwrap();
+            Ok(json!({
+                "listening": app_state.listening,
+                "pressedKeys": app_state.pressed_keys,
+                "monitor": app_state.monitor_info(),
+                "recording": app_state.recorder.status(),
+            }))
+        }
+        "getSettings" => Ok(json!(state.lock().unwrap().config.key_event)),
+        "setSettings" => {
+            let changes = parse_params(params)?;
+            let settings = state
+                .lock()
+                .unwrap()
+                .update_key_event_state(app, changes)
+                .map_err(|e| RpcError::new(INVALID_PARAMS, e))?;
+            Ok(json!(settings))
+        }
+        "subscribe" => {
+            let SubscribeParams { events } = parse_params(params)?;
+            let events = events.unwrap_or_else(|| EVENTS.map(String::from).to_vec());
+            if let Some(unknown) = events.iter().find(|name| !EVENTS.contains(&name.as_str())) {
+                return Err(RpcError::new(
+                    INVALID_PARAMS,
+                    format!("unknown event {}", unknown),
+                ));
+            }
+            unsubscribe(app, client);
+            for name in &events {
+                let outbox = client.outbox.clone();
+                let name = name.clone();
+                let id = app.listen_any(name.clone(), move |event| {
+                    let payload: Value = serde_json::from_str(event.payload()).unwrap_or_default();
+                    let notification = json!({
+                        "jsonrpc": "2.0",
+                        "method": "event",
+                        "params": { "event": name, "payload": payload },
+                    });
+                    // a client that went away unsubscribes once its reader ends
+                    outbox.send(notification.to_string());
+                });
+                client.subscriptions.push(id);
+            }
+            Ok(json!(events))
+        }
+        "unsubscribe" => {
+            unsubscribe(app, client);
+            Ok(Value::Null)
+        }
+        "startRecording" => commands::start_recording(app.clone())
+            .map(Value::from)
+            .map_err(|e| RpcError::new(SERVER_ERROR, e)),
+        "stopRecording" => {
+            commands::stop_recording(app.clone());
+            Ok(Value::Null)
+        }
+        "pauseRecording" => {
+            commands::pause_recording(app.clone());
+            Ok(Value::Null)
+        }
+        "resumeRecording" => {
+            commands::resume_recording(app.clone());
+            Ok(Value::Null)
+        }
+        _ => Err(RpcError::new(
+            METHOD_NOT_FOUND,
+            format!("unknown method {}", method),
+        )),
+    }
+}
+
+fn unsubscribe(app: &AppHandle, client: &mut Client) {
+    for id in client.subscriptions.drain(..) {
+        app.unlisten(id);
+    }
+}
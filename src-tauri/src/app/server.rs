@@ -0,0 +1,504 @@
+use std::{
+    io::{self, BufRead, BufReader, ErrorKind, Write},
+    net::{Ipv4Addr, TcpListener, TcpStream},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc::{self, Receiver, RecvTimeoutError, SyncSender, TrySendError},
+        Arc, Mutex,
+    },
+    thread,
+    time::Duration,
+};
+
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, EventId, Listener, Manager};
+use tauri_plugin_store::StoreExt;
+use tungstenite::{
+    handshake::server::{ErrorResponse, Request, Response},
+    http::StatusCode,
+    Message, WebSocket,
+};
+
+use crate::app::state::AppState;
+
+/// Events forwarded to websocket clients, as `{"event": ..., "payload": ...}`.
+const FORWARDED_EVENTS: [&str; 3] = ["input-event", "listening-toggle", "monitor-changed"];
+// how often blocked threads look up from their sockets
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+// browser source page, connects back to the websocket it was served from
+const OVERLAY_PAGE: &str = include_str!("../../assets/overlay.html");
+// most a request head may take before the client is dropped
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+// messages queued for a client before it counts as stuck and is dropped
+const CLIENT_QUEUE: usize = 1024;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct ServerConfig {
+    pub enabled: bool,
+    pub port: u16,
+    // required as `?token=` or a bearer authorization when set
+    pub token: Option<String>,
+    // browser origins allowed to connect besides the server's own, clients
+    // that aren't browsers send none
+    pub allowed_origins: Vec<String>,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        ServerConfig {
+            enabled: false,
+            port: 9876,
+            token: None,
+            allowed_origins: vec![],
+        }
+    }
+}
+
+impl ServerConfig {
+    /// Reads the server settings saved in the store.
+    pub fn load(app: &AppHandle) -> ServerConfig {
+        app.store("store.json")
+            .ok()
+            .and_then(|store| store.get("server_config"))
+            .and_then(|value| serde_json::from_value(value).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn save(&self, app: &AppHandle) -> Result<(), String> {
+        let store = app.store("store.json").map_err(|e| e.to_string())?;
+        let value = serde_json::to_value(self).map_err(|e| e.to_string())?;
+        store.set("server_config", value);
+        Ok(())
+    }
+
+    /// Names the server answers to, anything else may be a rebound dns name.
+    fn local_hosts(&self) -> [String; 2] {
+        [
+            format!("127.0.0.1:{}", self.port),
+            format!("localhost:{}", self.port),
+        ]
+    }
+
+    /// Checks a request against the host, the origin allowlist and the token.
+    fn authorize(&self, request: &Request) -> Result<(), (StatusCode, &'static str)> {
+        let header = |name: &str| {
+            request
+                .headers()
+                .get(name)
+                .map(|value| value.to_str().unwrap_or_default())
+        };
+        let local_hosts = self.local_hosts();
+        let host = header("host").unwrap_or_default();
+        if !local_hosts
+            .iter()
+            .any(|local| local.eq_ignore_ascii_case(host))
+        {
+            return Err((StatusCode::FORBIDDEN, "host not allowed"));
+        }
+        // any web page can open a websocket to localhost, browsers tell by the
+        // origin. The overlay page served from here always gets through.
+        if let Some(origin) = header("origin") {
+            let own = local_hosts
+                .iter()
+                .any(|local| origin.eq_ignore_ascii_case(&format!("http://{}", local)));
+            if !own && !self.allowed_origins.iter().any(|allowed| allowed == origin) {
+                return Err((StatusCode::FORBIDDEN, "origin not allowed"));
+            }
+        }
+        let Some(token) = &self.token else {
+            return Ok(());
+        };
+        let from_query = request
+            .uri()
+            .query()
+            .unwrap_or_default()
+            .split('&')
+            .filter_map(|pair| pair.split_once('='))
+            .any(|(key, value)| key == "token" && same_token(value, token));
+        let from_header = header("authorization")
+            .and_then(|value| value.strip_prefix("Bearer "))
+            .is_some_and(|value| same_token(value, token));
+        if from_query || from_header {
+            Ok(())
+        } else {
+            Err((StatusCode::UNAUTHORIZED, "invalid token"))
+        }
+    }
+}
+
+/// Compares without stopping at the first difference, so response times
+/// don't give the token away.
+fn same_token(given: &str, token: &str) -> bool {
+    given.len() == token.len()
+        && given
+            .bytes()
+            .zip(token.bytes())
+            .fold(0, |diff, (a, b)| diff | (a ^ b))
+            == 0
+}
+
+struct Running {
+    port: u16,
+    // kept open to restart on the same port without binding it again
+    listener: TcpListener,
+    stopped: Arc<AtomicBool>,
+    listeners: Vec<EventId>,
+}
+
+/// Localhost websocket server mirroring the events sent to the overlay. Plain
+/// http requests get a browser source version of the overlay instead.
+#[derive(Default)]
+pub struct EventServer {
+    running: Option<Running>,
+}
+
+impl EventServer {
+    pub fn port(&self) -> Option<u16> {
+        self.running.as_ref().map(|running| running.port)
+    }
+
+    /// Replaces any running server with one for `config`, or just stops it
+    /// when disabled. A running server is left as is when this fails.
+    pub fn apply(&mut self, app: &AppHandle, config: &ServerConfig) -> io::Result<()> {
+        if !config.enabled {
+            self.stop(app);
+            return Ok(());
+        }
+        let listener = match &self.running {
+            Some(running) if running.port == config.port => running.listener.try_clone()?,
+            _ => TcpListener::bind((Ipv4Addr::LOCALHOST, config.port))?,
+        };
+        // polled, so the accept loop can notice a stop
+        listener.set_nonblocking(true)?;
+        let port = listener.local_addr()?.port();
+        let kept = listener.try_clone()?;
+        self.stop(app);
+
+        let clients: Arc<Mutex<Vec<SyncSender<Arc<str>>>>> = Arc::default();
+        let listeners = FORWARDED_EVENTS
+            .iter()
+            .map(|&name| {
+                let clients = clients.clone();
+                app.listen_any(name, move |event| {
+                    let message: Arc<str> =
+                        format!(r#"{{"event":"{}","payload":{}}}"#, name, event.payload()).into();
+                    // clients that went away drop their receiver, and ones
+                    // that fell behind end once their sender is dropped
+                    clients.lock().unwrap().retain(|client| {
+                        match client.try_send(message.clone()) {
+                            Ok(()) => true,
+                            Err(TrySendError::Full(_)) => {
+                                eprintln!("Event server client fell behind, disconnecting it");
+                                false
+                            }
+                            Err(TrySendError::Disconnected(_)) => false,
+                        }
+                    });
+                })
+            })
+            .collect();
+
+        let stopped = Arc::new(AtomicBool::new(false));
+        let thread_stopped = stopped.clone();
+        // the port actually bound, for checking hosts and origins
+        let config = ServerConfig {
+            port,
+            ..config.clone()
+        };
+        let app = app.clone();
+        thread::spawn(move || accept_loop(app, listener, config, clients, thread_stopped));
+
+        println!("🔌 Event server listening on ws://127.0.0.1:{}", port);
+        self.running = Some(Running {
+            port,
+            listener: kept,
+            stopped,
+            listeners,
+        });
+        Ok(())
+    }
+
+    pub fn stop(&mut self, app: &AppHandle) {
+        if let Some(running) = self.running.take() {
+            running.stopped.store(true, Ordering::Relaxed);
+            for id in running.listeners {
+                app.unlisten(id);
+            }
+        }
+    }
+}
+
+fn accept_loop(
+    app: AppHandle,
+    listener: TcpListener,
+    config: ServerConfig,
+    clients: Arc<Mutex<Vec<SyncSender<Arc<str>>>>>,
+    stopped: Arc<AtomicBool>,
+) {
+    while !stopped.load(Ordering::Relaxed) {
+        let stream = match listener.accept() {
+            Ok((stream, _)) => stream,
+            Err(err) if err.kind() == ErrorKind::WouldBlock => {
+                thread::sleep(POLL_INTERVAL);
+                continue;
+            }
+            Err(err) => {
+                eprintln!("Event server failed to accept: {}", err);
+                continue;
+            }
+        };
+        let app = app.clone();
+        let config = config.clone();
+        let clients = clients.clone();
+        let stopped = stopped.clone();
+        thread::spawn(move || {
+            if !is_websocket(&stream) {
+                if let Err(err) = serve_page(&app, stream, &config) {
+                    eprintln!("Event server failed to serve a page: {}", err);
+                }
+                return;
+            }
+            let Some(socket) = handshake(stream, &config) else {
+                return;
+            };
+            let (sender, receiver) = mpsc::sync_channel(CLIENT_QUEUE);
+            clients.lock().unwrap().push(sender);
+            serve(socket, receiver, &stopped);
+        });
+    }
+    // dropping the senders ends every client
+    clients.lock().unwrap().clear();
+}
+
+/// Peeks at the request head for a websocket upgrade, leaving it unread.
+fn is_websocket(stream: &TcpStream) -> bool {
+    let _ = stream.set_nonblocking(false);
+    let _ = stream.set_read_timeout(Some(POLL_INTERVAL));
+    let mut buf = [0; 4096];
+    let mut waited = Duration::ZERO;
+    loop {
+        let len = stream.peek(&mut buf).unwrap_or(0);
+        let head = String::from_utf8_lossy(&buf[..len]).to_ascii_lowercase();
+        if head.contains("\r\n\r\n") || len == buf.len() || waited >= REQUEST_TIMEOUT {
+            return head.contains("upgrade: websocket");
+        }
+        thread::sleep(POLL_INTERVAL);
+        waited += POLL_INTERVAL;
+    }
+}
+
+/// Answers a plain http request, the overlay page lives at the root.
+fn serve_page(app: &AppHandle, stream: TcpStream, config: &ServerConfig) -> io::Result<()> {
+    stream.set_read_timeout(Some(REQUEST_TIMEOUT))?;
+    let mut reader = BufReader::new(stream);
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line)?;
+    let mut parts = request_line.split_whitespace();
+    let (method, target) = (
+        parts.next().unwrap_or_default(),
+        parts.next().unwrap_or("/"),
+    );
+
+    let mut request = Request::builder().uri(target);
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line)? == 0 || line.trim().is_empty() {
+            break;
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            request = request.header(name.trim(), value.trim());
+        }
+    }
+    let request = request
+        .body(())
+        .map_err(|e| io::Error::new(ErrorKind::InvalidData, e))?;
+
+    let (status, content_type, body) = match config.authorize(&request) {
+        Err((status, reason)) => (status, "text/plain", reason.to_string()),
+        Ok(()) if method != "GET" => (StatusCode::METHOD_NOT_ALLOWED, "text/plain", String::new()),
+        Ok(()) if request.uri().path() != "/" => {
+            (StatusCode::NOT_FOUND, "text/plain", "not found".into())
+        }
+        Ok(()) => {
+            let page_config = {
+                let state = app.state::<Mutex<AppState>>();
+                let config = &state.lock().unwrap().config;
+                serde_json::json!({
+                    "style": config.key_style,
+                    "settings": config.key_event,
+                })
+            };
+            // keep the json from closing the script tag
+            let page_config = page_config.to_string().replace("</", "<\\/");
+            let page = OVERLAY_PAGE.replace("/*KEYVIZ_CONFIG*/", &page_config);
+            (StatusCode::OK, "text/html; charset=utf-8", page)
+        }
+    };
+    let mut stream = reader.into_inner();
+    write!(
+        stream,
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nCache-Control: no-store\r\nConnection: close\r\n\r\n{}",
+        status,
+        content_type,
+        body.len(),
+        body
+    )?;
+    stream.flush()
+}
+
+// the error response is tungstenite's to pick
+#[allow(clippy::result_large_err)]
+fn handshake(stream: TcpStream, config: &ServerConfig) -> Option<WebSocket<TcpStream>> {
+    stream.set_nonblocking(false).ok()?;
+    let callback = |request: &Request, response: Response| {
+        config
+            .authorize(request)
+            .map(|_| response)
+            .map_err(|(status, reason)| {
+                let mut error = ErrorResponse::new(Some(reason.to_string()));
+                *error.status_mut() = status;
+                error
+            })
+    };
+    match tungstenite::accept_hdr(stream, callback) {
+        Ok(socket) => Some(socket),
+        Err(err) => {
+            eprintln!("Event server rejected a client: {}", err);
+            None
+        }
+    }
+}
+
+/// Writes queued events to one client, answering its pings and closes in between.
+fn serve(mut socket: WebSocket<TcpStream>, receiver: Receiver<Arc<str>>, stopped: &AtomicBool) {
+    // only peeks for incoming frames, events shouldn't wait on it
+    if socket
+        .get_mut()
+        .set_read_timeout(Some(Duration::from_millis(1)))
+        .is_err()
+    {
+        return;
+    }
+    loop {
+        let mut queued = match receiver.recv_timeout(POLL_INTERVAL) {
+            Ok(message) => vec![message],
+            Err(RecvTimeoutError::Timeout) => vec![],
+            Err(RecvTimeoutError::Disconnected) => break,
+        };
+        if stopped.load(Ordering::Relaxed) {
+            break;
+        }
+        queued.extend(receiver.try_iter());
+        for message in queued {
+            if socket.send(Message::text(message.as_ref())).is_err() {
+                return;
+            }
+        }
+        match socket.read() {
+            Ok(_) => {}
+            Err(tungstenite::Error::Io(err))
+                if matches!(err.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => {}
+            Err(_) => return,
+        }
+    }
+    let _ = socket.close(None);
+    let _ = socket.flush();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(token: Option<&str>, allowed_origins: &[&str]) -> ServerConfig {
+        ServerConfig {
+            enabled: true,
+            port: 9876,
+            token: token.map(String::from),
+            allowed_origins: allowed_origins
+                .iter()
+                .map(|origin| origin.to_string())
+                .collect(),
+        }
+    }
+
+    fn request(uri: &str, headers: &[(&str, &str)]) -> Request {
+        let mut request = Request::builder().uri(uri);
+        for (name, value) in headers {
+            request = request.header(*name, *value);
+        }
+        request.body(()).unwrap()
+    }
+
+    fn status(config: &ServerConfig, request: &Request) -> Option<StatusCode> {
+        config.authorize(request).err().map(|(status, _)| status)
+    }
+
+    #[test]
+    fn hosts_must_be_local() {
+        let config = config(None, &[]);
+        assert_eq!(
+            status(&config, &request("/", &[("host", "127.0.0.1:9876")])),
+            None
+        );
+        assert_eq!(
+            status(&config, &request("/", &[("host", "LocalHost:9876")])),
+            None
+        );
+        // a rebound name resolving to us, or another port
+        for host in ["evil.example:9876", "127.0.0.1:80"] {
+            let request = request("/", &[("host", host)]);
+            assert_eq!(status(&config, &request), Some(StatusCode::FORBIDDEN));
+        }
+        assert_eq!(
+            status(&config, &request("/", &[])),
+            Some(StatusCode::FORBIDDEN)
+        );
+    }
+
+    #[test]
+    fn browser_origins_need_allowing() {
+        let config = config(None, &["https://obs.example"]);
+        let from = |origin| request("/", &[("host", "127.0.0.1:9876"), ("origin", origin)]);
+        assert_eq!(status(&config, &from("https://obs.example")), None);
+        assert_eq!(status(&config, &from("http://127.0.0.1:9876")), None);
+        assert_eq!(
+            status(&config, &from("https://evil.example")),
+            Some(StatusCode::FORBIDDEN)
+        );
+        assert_eq!(status(&config, &from("null")), Some(StatusCode::FORBIDDEN));
+    }
+
+    #[test]
+    fn served_page_connects_back() {
+        let config = config(Some("s3cret"), &["https://obs.example"]);
+        for host in ["127.0.0.1:9876", "localhost:9876"] {
+            // the page, then its websocket from the same origin
+            let page = request("/?token=s3cret", &[("host", host)]);
+            assert_eq!(status(&config, &page), None);
+            let origin = format!("http://{}", host);
+            let socket = request("/?token=s3cret", &[("host", host), ("origin", &origin)]);
+            assert_eq!(status(&config, &socket), None);
+        }
+    }
+
+    #[test]
+    fn tokens_come_by_query_or_header() {
+        let config = config(Some("s3cret"), &[]);
+        let host = ("host", "127.0.0.1:9876");
+        assert_eq!(
+            status(&config, &request("/?a=1&token=s3cret", &[host])),
+            None
+        );
+        let bearer = ("authorization", "Bearer s3cret");
+        assert_eq!(status(&config, &request("/", &[host, bearer])), None);
+        for uri in ["/", "/?token=s3cre", "/?token=s3cret2", "/?token=S3cret"] {
+            assert_eq!(
+                status(&config, &request(uri, &[host])),
+                Some(StatusCode::UNAUTHORIZED)
+            );
+        }
+        assert!(same_token("", ""));
+        assert!(!same_token("abc", "abd"));
+    }
+}
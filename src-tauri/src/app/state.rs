@@ -1,45 +1,88 @@
-use serde::Deserialize;
-use tauri::{image::Image, include_image, Emitter, Wry};
-use tauri_plugin_store::StoreExt;
+use std::{collections::HashMap, path::PathBuf};
+
+use serde::{Deserialize, Serialize};
+use tauri::{image::Image, include_image, Emitter, Manager, Wry};
+
+use crate::app::actions::Action;
+use crate::app::chord::{ChordTracker, KeySequence};
+use crate::app::config::{push_to_webview, Config, KEY_EVENT_STORE};
+use crate::app::event::InputEvent;
+use crate::app::focus::{FocusAction, FocusedWindow};
+use crate::app::privacy::PrivacyPause;
+use crate::app::recorder::{MonitorInfo, Recorder, RecordingMenu, RecordingStatus};
+use crate::app::redact::Redaction;
+use crate::app::replay::Replay;
+use crate::app::server::{EventServer, ServerConfig};
+
+/// The tray entry reflecting whether we listen, managed as app state.
+pub struct ListeningMenu {
+    pub toggle: tauri::menu::MenuItem<Wry>,
+}
 
 #[derive(Default)]
 pub struct AppState {
     pub listening: bool,
     pub pressed_keys: Vec<String>,
-    pub toggle_shortcut: Vec<String>,
+    // auto-repeats of each held key so far
+    pub repeat_counts: HashMap<String, u32>,
 
     pub monitor_name: Option<String>,
     pub monitor_scale: f64,
     pub monitor_position: (i32, i32),
+    pub monitor_size: (u32, u32),
+
+    pub recorder: Recorder,
+    // a session replaying through the overlay mutes live events
+    pub replay: Option<Replay>,
+    pub server: EventServer,
+    pub config: Config,
+
+    pub focused_window: Option<FocusedWindow>,
+    // listening was stopped by a focus rule, and resumes on leaving the window
+    pub paused_by_focus: bool,
+    // events are held back while a secret may be typed
+    pub privacy_pause: PrivacyPause,
+    // masks the keys shown while a token is typed
+    pub redaction: Redaction,
+    // recent chords, for hotkeys pressed as a sequence
+    pub chords: ChordTracker,
 }
 
 impl AppState {
     pub fn new(app: &tauri::AppHandle) -> Self {
-        let mut toggle_shortcut = vec!["Shift".to_string(), "F10".to_string()];
-
         // load saved config from store
-        if let Ok(store) = app.store("store.json") {
-            if let Some(value) = store.get("key_event_store") {
-                // the value comes in as a String: "{\"state\": ...}"
-                if let Some(json_str) = value.as_str() {
-                    // parse the inner string
-                    match serde_json::from_str::<KeyEventStore>(json_str) {
-                        Ok(parsed) => {
-                            toggle_shortcut = parsed.state.toggle_shortcut;
-                        }
-                        Err(e) => eprintln!("Failed to parse inner config JSON: {}", e),
-                    }
-                }
-            }
+        let config = Config::load(app);
+
+        let mut server = EventServer::default();
+        if let Err(err) = server.apply(app, &ServerConfig::load(app)) {
+            eprintln!("Failed to start event server: {}", err);
+        }
+
+        Self {
+            server,
+            ..Self::detached(config)
         }
+    }
 
+    /// State without an app behind it, for listening headless.
+    pub fn detached(config: Config) -> Self {
         Self {
             listening: true,
             pressed_keys: vec![],
-            toggle_shortcut,
+            repeat_counts: HashMap::new(),
             monitor_name: None,
             monitor_scale: 1.0,
             monitor_position: (0, 0),
+            monitor_size: (0, 0),
+            recorder: Recorder::default(),
+            replay: None,
+            server: EventServer::default(),
+            config,
+            focused_window: None,
+            paused_by_focus: false,
+            privacy_pause: PrivacyPause::default(),
+            redaction: Redaction::default(),
+            chords: ChordTracker::default(),
         }
     }
     pub fn toggle_listener(&mut self, app: &tauri::AppHandle, toggle: &tauri::menu::MenuItem<Wry>) {
@@ -64,23 +107,313 @@ impl AppState {
         app.emit_to("main", "listening-toggle", self.listening)
             .unwrap();
     }
+
+    /// Runs the focus rules for a newly focused window, returning what the
+    /// first that matches asks for. Leaving a window that paused listening
+    /// resumes it, unless the next one pauses too.
+    pub fn focus_changed(&mut self, window: Option<FocusedWindow>) -> Vec<Action> {
+        let rule_action = window.as_ref().and_then(|window| {
+            self.config
+                .focus_rules
+                .iter()
+                .find(|rule| rule.window.matches(window))
+                .map(|rule| rule.action.clone())
+        });
+        self.focused_window = window;
+
+        let mut actions = vec![];
+        if rule_action == Some(FocusAction::Pause) {
+            // paused by hand stays that way after leaving the window
+            if self.listening {
+                self.paused_by_focus = true;
+            }
+            actions.push(Action::Stop);
+            return actions;
+        }
+        if self.paused_by_focus || rule_action == Some(FocusAction::Resume) {
+            self.paused_by_focus = false;
+            actions.push(Action::Start);
+        }
+        if let Some(FocusAction::Profile(name)) = rule_action {
+            if self.config.active_profile.as_ref() != Some(&name) {
+                actions.push(Action::Profile(name));
+            }
+        }
+        actions
+    }
+
+    /// Whether input events go out, to the overlay and everyone else.
+    pub fn emitting(&self) -> bool {
+        self.listening && self.replay.is_none() && !self.privacy_pause.paused()
+    }
+
+    /// Holds back or resumes input events for privacy, telling the overlay
+    /// so it can show why.
+    pub fn set_privacy_pause(&mut self, app: &tauri::AppHandle, pause: PrivacyPause) {
+        let was_paused = self.privacy_pause.paused();
+        let was_emitting = self.emitting();
+        self.privacy_pause = pause;
+        if pause.paused() == was_paused {
+            return;
+        }
+
+        if pause.paused() {
+            println!("🙈 Privacy paused");
+            // keys held now would otherwise stay down on the overlay
+            if was_emitting {
+                self.emit_releases(app, self.pressed_keys.clone());
+            }
+        } else {
+            println!("🙉 Privacy resumed");
+        }
+        app.emit("privacy-paused", pause.paused()).unwrap();
+    }
+
+    /// Turns masking of typed keys on or off, telling the overlay.
+    pub fn set_redacting(&mut self, app: &tauri::AppHandle, active: bool) {
+        if self.redaction.set_active(active) {
+            Self::announce_redaction(app, active);
+        }
+    }
+
+    /// Masks an event about to be emitted while redacting, following the
+    /// redaction shortcut, marker and Enter on the way.
+    pub fn redact(&mut self, app: &tauri::AppHandle, input_event: InputEvent) -> InputEvent {
+        let (input_event, toggled) = self.redaction.redact(
+            &self.config.redaction,
+            &self.pressed_keys,
+            self.config.either_side_modifiers,
+            input_event,
+        );
+        if let Some(active) = toggled {
+            Self::announce_redaction(app, active);
+        }
+        input_event
+    }
+
+    /// Emits releases for keys the overlay would otherwise keep down, masked
+    /// and recorded like the listener's own events.
+    pub fn emit_releases(&mut self, app: &tauri::AppHandle, keys: Vec<String>) {
+        for name in keys {
+            let input_event = InputEvent::KeyEvent {
+                pressed: false,
+                name,
+                repeat: 0,
+            };
+            let shown = self.redact(app, input_event.clone());
+            if self.config.redaction.redact_recordings {
+                self.recorder.record(&shown);
+            } else {
+                self.recorder.record(&input_event);
+            }
+            app.emit("input-event", shown).unwrap();
+        }
+    }
+
+    fn announce_redaction(app: &tauri::AppHandle, active: bool) {
+        if active {
+            println!("🕶️ Redaction enabled");
+        } else {
+            println!("👓 Redaction disabled");
+        }
+        app.emit("redaction-toggle", active).unwrap();
+    }
+
+    pub fn monitor_info(&self) -> MonitorInfo {
+        MonitorInfo {
+            name: self.monitor_name.clone(),
+            scale: self.monitor_scale,
+            position: self.monitor_position,
+            size: self.monitor_size,
+        }
+    }
+
+    /// Starts a new session file in the app data dir and returns its path.
+    pub fn start_recording(&mut self, app: &tauri::AppHandle) -> Result<PathBuf, String> {
+        let dir = app
+            .path()
+            .app_data_dir()
+            .map_err(|e| e.to_string())?
+            .join("sessions");
+        let path = self
+            .recorder
+            .start(dir, self.monitor_info())
+            .map_err(|e| e.to_string())?;
+        println!("⏺️ Recording to {}", path.display());
+        self.update_recording_status(app);
+        Ok(path)
+    }
+
+    pub fn stop_recording(&mut self, app: &tauri::AppHandle) {
+        self.recorder.stop();
+        println!("⏹️ Recording stopped");
+        self.update_recording_status(app);
+    }
+
+    pub fn pause_recording(&mut self, app: &tauri::AppHandle) {
+        self.recorder.pause();
+        self.update_recording_status(app);
+    }
+
+    pub fn resume_recording(&mut self, app: &tauri::AppHandle) {
+        self.recorder.resume();
+        self.update_recording_status(app);
+    }
+
+    fn update_recording_status(&self, app: &tauri::AppHandle) {
+        let status = self.recorder.status();
+        if let Some(menu) = app.try_state::<RecordingMenu>() {
+            let record_text = match status {
+                RecordingStatus::Idle => "Start Recording",
+                _ => "Stop Recording",
+            };
+            let pause_text = match status {
+                RecordingStatus::Paused => "Resume Recording",
+                _ => "Pause Recording",
+            };
+            menu.record.set_text(record_text).unwrap();
+            menu.pause.set_text(pause_text).unwrap();
+            menu.pause
+                .set_enabled(status != RecordingStatus::Idle)
+                .unwrap();
+        }
+        app.emit("recording-status", status).unwrap();
+    }
+
+    /// Merges `changes`, camelCase overlay settings, into the persisted ones
+    /// and syncs them to the overlay the way the settings window does.
+    pub fn update_key_event_state(
+        &mut self,
+        app: &tauri::AppHandle,
+        changes: serde_json::Map<String, serde_json::Value>,
+    ) -> Result<KeyEventState, String> {
+        let mut merged: serde_json::Map<String, serde_json::Value> =
+            serde_json::to_value(&self.config.key_event)
+                .and_then(serde_json::from_value)
+                .map_err(|e| e.to_string())?;
+        if let Some(key) = changes.keys().find(|key| !merged.contains_key(*key)) {
+            return Err(format!("unknown setting {}", key));
+        }
+        merged.extend(changes.clone());
+        let mut config = self.config.clone();
+        config.key_event = serde_json::from_value(merged.into()).map_err(|e| e.to_string())?;
+        if let Some(problem) = config.validate().pop() {
+            return Err(problem);
+        }
+        let settings = config.key_event.clone();
+        let normalized = serde_json::to_value(&settings).map_err(|e| e.to_string())?;
+        let fields = changes
+            .keys()
+            .map(|key| (key.clone(), normalized[key].clone()))
+            .collect();
+        push_to_webview(app, KEY_EVENT_STORE, &fields)?;
+
+        self.config = config;
+        self.config.save(app)?;
+        Ok(settings)
+    }
+
+    /// Drops keys the OS no longer reports as held down, e.g. when a release
+    /// got lost to a focus change or screen lock, and returns them.
+    #[cfg(not(target_os = "macos"))]
+    pub fn drop_released_keys(&mut self, key_state: &rdev::KeyStateReader) -> Vec<String> {
+        let held: Vec<String> = key_state
+            .pressed_keys()
+            .iter()
+            .map(|key| format!("{:?}", key))
+            .collect();
+
+        let (still_held, released): (Vec<String>, Vec<String>) = self
+            .pressed_keys
+            .drain(..)
+            .partition(|key_name| held.contains(key_name));
+        self.pressed_keys = still_held;
+        released
+    }
+
+    /// Drops keys that are no longer held and releases them in the overlay.
+    #[cfg(not(target_os = "macos"))]
+    pub fn sync_pressed_keys(&mut self, app: &tauri::AppHandle, key_state: &rdev::KeyStateReader) {
+        let released = self.drop_released_keys(key_state);
+
+        if !self.emitting() {
+            return;
+        }
+        for key_name in &released {
+            println!("Releasing stuck key: {}", key_name);
+        }
+        self.emit_releases(app, released);
+    }
 }
 
-#[derive(Debug, Deserialize)]
-struct KeyEventStore {
-    pub state: KeyEventState,
-    // pub version: u32,
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum KeyFilter {
+    None,
+    #[default]
+    Modifiers,
+    Custom,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
-struct KeyEventState {
-    // pub drag_threshold: u32,
-    // pub filter_hotkeys: bool,
-    // pub ignore_modifiers: Vec<String>,
-    // pub show_event_history: bool,
-    // pub max_history: u32,
-    // pub linger_duration_ms: u32,
-    // pub show_mouse_events: bool,
-    pub toggle_shortcut: Vec<String>,
+pub struct KeyEventState {
+    #[serde(default = "default_drag_threshold")]
+    pub drag_threshold: u32,
+    #[serde(default)]
+    pub show_event_history: bool,
+    #[serde(default = "default_max_history")]
+    pub max_history: u32,
+    #[serde(default)]
+    pub filter: KeyFilter,
+    #[serde(default = "default_allowed_keys")]
+    pub allowed_keys: Vec<String>,
+    #[serde(default = "default_linger_duration_ms")]
+    pub linger_duration_ms: u64,
+    #[serde(default = "default_true")]
+    pub show_mouse_events: bool,
+    #[serde(default = "default_toggle_shortcut")]
+    pub toggle_shortcut: KeySequence,
+    #[serde(default)]
+    pub show_key_repeats: bool,
+}
+
+impl Default for KeyEventState {
+    fn default() -> Self {
+        KeyEventState {
+            drag_threshold: default_drag_threshold(),
+            show_event_history: false,
+            max_history: default_max_history(),
+            filter: KeyFilter::default(),
+            allowed_keys: default_allowed_keys(),
+            linger_duration_ms: default_linger_duration_ms(),
+            show_mouse_events: true,
+            toggle_shortcut: default_toggle_shortcut(),
+            show_key_repeats: false,
+        }
+    }
+}
+
+fn default_drag_threshold() -> u32 {
+    50
+}
+
+fn default_toggle_shortcut() -> KeySequence {
+    KeySequence::chord(&["ShiftLeft", "F10"])
+}
+
+fn default_allowed_keys() -> Vec<String> {
+    vec!["ControlLeft".into(), "MetaLeft".into(), "Alt".into()]
+}
+
+fn default_max_history() -> u32 {
+    5
+}
+
+fn default_linger_duration_ms() -> u64 {
+    5_000
+}
+
+fn default_true() -> bool {
+    true
 }
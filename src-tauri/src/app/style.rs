@@ -0,0 +1,229 @@
+use serde::{Deserialize, Serialize};
+
+/// The keycap style the frontend keeps in `key_style_store`. Missing fields
+/// take the frontend defaults.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct KeyStyle {
+    pub appearance: AppearanceStyle,
+    pub layout: LayoutStyle,
+    pub color: ColorStyle,
+    pub modifier: ModifierStyle,
+    pub text: TextStyle,
+    pub border: BorderStyle,
+    pub background: BackgroundStyle,
+    pub mouse: MouseStyle,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct AppearanceStyle {
+    // display the overlay sits on, the first one when unset
+    pub monitor: Option<String>,
+    pub flex_direction: String,
+    pub alignment: String,
+    pub margin_x: f64,
+    pub margin_y: f64,
+    pub animation: String,
+    // in seconds
+    pub animation_duration: f64,
+    // keycap look: minimal, laptop, lowprofile or pbt
+    pub style: String,
+}
+
+impl Default for AppearanceStyle {
+    fn default() -> Self {
+        AppearanceStyle {
+            monitor: None,
+            flex_direction: "column".into(),
+            alignment: "bottom-center".into(),
+            margin_x: 100.0,
+            margin_y: 100.0,
+            animation: "fade".into(),
+            animation_duration: 0.25,
+            style: "lowprofile".into(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct LayoutStyle {
+    pub show_icon: bool,
+    pub show_symbol: bool,
+    pub show_press_count: bool,
+    pub icon_alignment: String,
+}
+
+impl Default for LayoutStyle {
+    fn default() -> Self {
+        LayoutStyle {
+            show_icon: true,
+            show_symbol: true,
+            show_press_count: true,
+            icon_alignment: "flex-end".into(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct ColorStyle {
+    pub color: String,
+    pub secondary_color: String,
+    pub use_gradient: bool,
+}
+
+impl Default for ColorStyle {
+    fn default() -> Self {
+        ColorStyle {
+            color: "#ffffff".into(),
+            secondary_color: "#1a1a1a".into(),
+            use_gradient: true,
+        }
+    }
+}
+
+/// Colors for modifier keys, used instead of the regular ones when highlighted.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct ModifierStyle {
+    pub highlight: bool,
+    pub color: String,
+    pub secondary_color: String,
+    pub text_color: String,
+    pub border_color: String,
+}
+
+impl Default for ModifierStyle {
+    fn default() -> Self {
+        ModifierStyle {
+            highlight: false,
+            color: "#3a86ff".into(),
+            secondary_color: "#000000".into(),
+            text_color: "#000000".into(),
+            border_color: "#000000".into(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TextStyle {
+    pub size: f64,
+    pub color: String,
+    pub caps: String,
+    // icon, text or text-short
+    pub variant: String,
+    pub alignment: String,
+}
+
+impl Default for TextStyle {
+    fn default() -> Self {
+        TextStyle {
+            size: 32.0,
+            color: "#000000".into(),
+            caps: "capitalize".into(),
+            variant: "text-short".into(),
+            alignment: "center".into(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BorderStyle {
+    pub enabled: bool,
+    pub width: f64,
+    pub color: String,
+    // relative to the text size
+    pub radius: f64,
+}
+
+impl Default for BorderStyle {
+    fn default() -> Self {
+        BorderStyle {
+            enabled: true,
+            width: 2.0,
+            color: "#1a1a1a".into(),
+            radius: 0.5,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BackgroundStyle {
+    pub enabled: bool,
+    pub color: String,
+}
+
+impl Default for BackgroundStyle {
+    fn default() -> Self {
+        BackgroundStyle {
+            enabled: true,
+            color: "#ffffff99".into(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct MouseStyle {
+    pub show_clicks: bool,
+    pub size: f64,
+    pub color: String,
+    pub keep_highlight: bool,
+    pub show_indicator: bool,
+    pub indicator_size: f64,
+    pub indicator_offset_x: f64,
+    pub indicator_offset_y: f64,
+}
+
+impl Default for MouseStyle {
+    fn default() -> Self {
+        MouseStyle {
+            show_clicks: false,
+            size: 150.0,
+            color: "#009dff".into(),
+            keep_highlight: true,
+            show_indicator: true,
+            indicator_size: 50.0,
+            indicator_offset_x: 50.0,
+            indicator_offset_y: 50.0,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct KeyStyleStore {
+    state: KeyStyle,
+}
+
+impl KeyStyle {
+    /// Parses either the persisted store value, `{"state": ...}`, or a
+    /// file written by the style export in settings.
+    pub fn from_json(json_str: &str) -> Result<KeyStyle, serde_json::Error> {
+        let value: serde_json::Value = serde_json::from_str(json_str)?;
+        if value.get("state").is_some() {
+            serde_json::from_value::<KeyStyleStore>(value).map(|store| store.state)
+        } else {
+            serde_json::from_value(value)
+        }
+    }
+}
+
+/// Parses "#rgb", "#rrggbb" or "#rrggbbaa" into rgba.
+pub fn parse_color(color: &str) -> Option<[u8; 4]> {
+    let hex = color.trim().strip_prefix('#')?;
+    let channel = |i: usize| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok();
+    match hex.len() {
+        3 => {
+            let short = |i: usize| u8::from_str_radix(hex.get(i..i + 1)?, 16).ok();
+            Some([short(0)? * 17, short(1)? * 17, short(2)? * 17, 255])
+        }
+        6 => Some([channel(0)?, channel(2)?, channel(4)?, 255]),
+        8 => Some([channel(0)?, channel(2)?, channel(4)?, channel(6)?]),
+        _ => None,
+    }
+}
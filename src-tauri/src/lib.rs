@@ -3,28 +3,55 @@ use std::sync::Mutex;
 use tauri::{
     image::Image,
     include_image,
-    menu::{Menu, MenuItem},
+    menu::{Menu, MenuItem, Submenu},
     tray::TrayIconBuilder,
-    Emitter, Manager, WebviewWindowBuilder,
+    Emitter, Manager, RunEvent,
 };
 
 mod app;
-use app::commands::{log, set_main_window_monitor, set_toggle_shortcut};
+use app::actions::{self, Action, RecordAction};
+use app::commands::{
+    delete_profile, export_profile, export_session, get_focus_rules, get_hotkeys,
+    get_privacy_config, get_privacy_pause, get_recording_status, get_redaction_config,
+    get_replay_status, get_server_config, import_profile, list_profiles, log, pause_recording,
+    pause_replay, render_session_frames, request_lock_state, resume_recording, resume_replay,
+    save_profile, seek_replay, set_focus_rules, set_hotkeys, set_main_window_monitor,
+    set_privacy_config, set_redacting, set_redaction_config, set_replay_speed, set_server_config,
+    set_show_key_repeats, set_toggle_shortcut, start_recording, start_replay, stop_recording,
+    stop_replay, switch_profile,
+};
+#[cfg(not(target_os = "macos"))]
+use app::event::start_key_state_sync;
 use app::event::start_listener;
-use app::state::AppState;
+use app::profile::{self, ProfileMenu};
+use app::recorder::RecordingMenu;
+use app::state::{AppState, ListeningMenu};
 use app::window::config_window;
 
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
+    // one-shot command line tasks, like exporting a session
+    let args: Vec<String> = std::env::args().collect();
+    if let Some(code) = app::cli::run(&args) {
+        std::process::exit(code);
+    }
+
     tauri::Builder::default()
-        .plugin(tauri_plugin_single_instance::init(|_, __, ___| {}))
+        // `keyviz <action>` while running hands the action over to us
+        .plugin(tauri_plugin_single_instance::init(
+            |app, args, _| match Action::from_args(&args) {
+                Ok(Some(action)) => actions::run(app, action),
+                Ok(None) => {}
+                Err(err) => eprintln!("Ignoring forwarded arguments: {}", err),
+            },
+        ))
         .plugin(tauri_plugin_prevent_default::init())
         .plugin(tauri_plugin_fs::init())
         .plugin(tauri_plugin_dialog::init())
         .plugin(tauri_plugin_store::Builder::new().build())
         .plugin(tauri_plugin_os::init())
         .plugin(tauri_plugin_opener::init())
-        .setup(|app| {
+        .setup(move |app| {
             // prepare window
             if let Some(window) = app.get_webview_window("main") {
                 config_window(&window);
@@ -33,49 +60,101 @@ pub fn run() {
             let app_handle = app.handle();
             // manage app state
             app.manage(Mutex::new(AppState::new(&app_handle)));
+            // follow settings changes without reading the webview stores
+            app::config::start_sync(app_handle.clone());
 
             // tray actions
             let toggle_item = MenuItem::with_id(app, "toggle", "Stop", true, None::<&str>)?;
+            let record_item =
+                MenuItem::with_id(app, "record", "Start Recording", true, None::<&str>)?;
+            let pause_record_item =
+                MenuItem::with_id(app, "pause_record", "Pause Recording", false, None::<&str>)?;
+            // filled from the saved profiles once managed
+            let profile_submenu = Submenu::with_id(app, "profiles", "Profiles", true)?;
             let settings_item = MenuItem::with_id(app, "settings", "Settings", true, None::<&str>)?;
             let quit_item = MenuItem::with_id(app, "quit", "Quit", true, None::<&str>)?;
 
+            // scriptable control, next to the tray
+            #[cfg(unix)]
+            if let Err(err) = app::rpc::start(app_handle.clone()) {
+                eprintln!("Failed to start control socket: {}", err);
+            }
+
+            // for desktop integrations
+            #[cfg(target_os = "linux")]
+            if let Err(err) = app::dbus::start(app_handle.clone()) {
+                eprintln!("Failed to start D-Bus service: {}", err);
+            }
+
             // start global input listener
             start_listener(app_handle.clone(), toggle_item.clone());
+            // recover from lost key releases
+            #[cfg(not(target_os = "macos"))]
+            start_key_state_sync(app_handle.clone());
 
             // setup tray menu
-            let menu = Menu::with_items(app, &[&toggle_item, &settings_item, &quit_item])?;
+            let menu = Menu::with_items(
+                app,
+                &[
+                    &toggle_item,
+                    &record_item,
+                    &pause_record_item,
+                    &profile_submenu,
+                    &settings_item,
+                    &quit_item,
+                ],
+            )?;
+            app.manage(ListeningMenu {
+                toggle: toggle_item.clone(),
+            });
+            app.manage(RecordingMenu {
+                record: record_item,
+                pause: pause_record_item,
+            });
+            app.manage(ProfileMenu {
+                submenu: profile_submenu,
+            });
+            profile::refresh_menu(app_handle);
             let _ = TrayIconBuilder::with_id("keyviz-tray")
                 .icon(Image::from(include_image!("icons/tray.png")))
                 .menu(&menu)
                 .show_menu_on_left_click(true)
-                .on_menu_event(move |app, event| match event.id.as_ref() {
-                    "toggle" => {
-                        let state = app.state::<Mutex<AppState>>();
-                        let mut app_state = state.lock().unwrap();
-                        app_state.toggle_listener(app, &toggle_item);
-                    }
-                    "settings" => {
-                        if let Some(window) = app.get_webview_window("settings") {
-                            let _ = window.set_focus();
+                .on_menu_event(|app, event| {
+                    let action = match event.id.as_ref() {
+                        "toggle" => Action::Toggle,
+                        "record" => Action::Record(RecordAction::Toggle),
+                        "pause_record" => Action::Record(RecordAction::TogglePause),
+                        "settings" => Action::Settings,
+                        "quit" => {
+                            app.exit(0);
                             return;
                         }
-                        let webview_url = tauri::WebviewUrl::App("index.html#/settings".into());
-                        WebviewWindowBuilder::new(app, "settings", webview_url.clone())
-                            .title("Keyviz")
-                            .inner_size(800.0, 640.0)
-                            .min_inner_size(640.0, 480.0)
-                            .max_inner_size(1000.0, 800.0)
-                            .maximizable(false)
-                            .build()
-                            .unwrap();
-
-                        app.emit_to("main", "settings-window", true).unwrap();
-                    }
-                    "quit" => std::process::exit(0),
-                    _ => println!("um... what?"),
+                        id if id.starts_with(profile::MENU_ID_PREFIX) => {
+                            Action::Profile(id[profile::MENU_ID_PREFIX.len()..].to_string())
+                        }
+                        _ => {
+                            println!("um... what?");
+                            return;
+                        }
+                    };
+                    actions::run(app, action);
                 })
                 .build(app);
 
+            // focus rules, switching profiles or pausing per application
+            #[cfg(target_os = "linux")]
+            if let Err(err) = app::focus::start(app_handle.clone()) {
+                eprintln!("Failed to watch the focused window: {:?}", err);
+            }
+            // hold back events while prompts read a secret
+            #[cfg(target_os = "linux")]
+            app::privacy::start_grab_watch(app_handle.clone());
+
+            // an action given on first launch applies once we're up
+            if let Ok(Some(action)) = Action::from_args(&args) {
+                actions::run(app_handle, action);
+            }
+
             Ok(())
         })
         .on_window_event(|window, event| {
@@ -95,8 +174,49 @@ pub fn run() {
         .invoke_handler(tauri::generate_handler![
             log,
             set_toggle_shortcut,
-            set_main_window_monitor
+            set_show_key_repeats,
+            set_main_window_monitor,
+            request_lock_state,
+            start_recording,
+            stop_recording,
+            pause_recording,
+            resume_recording,
+            get_recording_status,
+            export_session,
+            render_session_frames,
+            start_replay,
+            stop_replay,
+            pause_replay,
+            resume_replay,
+            seek_replay,
+            set_replay_speed,
+            get_replay_status,
+            get_server_config,
+            set_server_config,
+            list_profiles,
+            save_profile,
+            switch_profile,
+            delete_profile,
+            export_profile,
+            import_profile,
+            get_focus_rules,
+            set_focus_rules,
+            get_privacy_config,
+            set_privacy_config,
+            get_privacy_pause,
+            get_redaction_config,
+            set_redaction_config,
+            set_redacting,
+            get_hotkeys,
+            set_hotkeys
         ])
-        .run(tauri::generate_context!())
-        .expect("error while running tauri application");
+        .build(tauri::generate_context!())
+        .expect("error while running tauri application")
+        .run(|app, event| {
+            // the session file is buffered, write it out before going away
+            if let RunEvent::Exit = event {
+                let state = app.state::<Mutex<AppState>>();
+                state.lock().unwrap().recorder.stop();
+            }
+        });
 }
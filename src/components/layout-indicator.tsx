@@ -0,0 +1,33 @@
+import { useKeyEvent } from "@/stores/key_event";
+import { useKeyStyle } from "@/stores/key_style";
+import { AnimatePresence, motion } from "motion/react";
+
+export const LayoutIndicator = () => {
+    const layoutChange = useKeyEvent(state => state.layoutChange);
+    const text = useKeyStyle(state => state.text);
+    const color = useKeyStyle(state => state.color.color);
+
+    const style = {
+        color: text.color,
+        backgroundColor: color,
+        fontSize: text.size * 0.5,
+        paddingInline: text.size * 0.5,
+        paddingBlock: text.size * 0.25,
+    };
+
+    return <AnimatePresence>
+        {
+            layoutChange &&
+            <motion.div
+                key={layoutChange.changedAt}
+                className="absolute top-1/2 left-1/2 -translate-x-1/2 -translate-y-1/2 rounded-full font-semibold"
+                style={style}
+                initial={{ opacity: 0 }}
+                animate={{ opacity: 1 }}
+                exit={{ opacity: 0 }}
+            >
+                {layoutChange.name}
+            </motion.div>
+        }
+    </AnimatePresence>;
+};
@@ -0,0 +1,30 @@
+import { useKeyEvent } from "@/stores/key_event";
+import { useKeyStyle } from "@/stores/key_style";
+import { LockIcon } from "lucide-react";
+
+export const LockBadge = () => {
+    const lockState = useKeyEvent(state => state.lockState);
+    const text = useKeyStyle(state => state.text);
+    const color = useKeyStyle(state => state.color.color);
+
+    const locks = [
+        lockState.caps_lock && "Caps",
+        lockState.num_lock && "Num",
+        lockState.scroll_lock && "Scroll",
+    ].filter(Boolean);
+
+    if (locks.length === 0) return null;
+
+    const style = {
+        color: text.color,
+        backgroundColor: color,
+        fontSize: text.size * 0.4,
+        gap: text.size * 0.15,
+        paddingInline: text.size * 0.3,
+        paddingBlock: text.size * 0.15,
+    };
+
+    return <div className="absolute top-4 right-4 flex items-center rounded-full font-semibold" style={style}>
+        <LockIcon size="1em" /> {locks.join(" · ")}
+    </div>;
+};
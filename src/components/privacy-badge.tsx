@@ -0,0 +1,20 @@
+import { useKeyStyle } from "@/stores/key_style";
+import { EyeOffIcon } from "lucide-react";
+
+export const PrivacyBadge = () => {
+    const text = useKeyStyle(state => state.text);
+    const color = useKeyStyle(state => state.color.color);
+
+    const style = {
+        color: text.color,
+        backgroundColor: color,
+        fontSize: text.size * 0.4,
+        gap: text.size * 0.15,
+        paddingInline: text.size * 0.3,
+        paddingBlock: text.size * 0.15,
+    };
+
+    return <div className="absolute top-4 left-4 flex items-center rounded-full font-semibold" style={style}>
+        <EyeOffIcon size="1em" /> Privacy paused
+    </div>;
+};
@@ -17,7 +17,7 @@ import { ToggleGroup, ToggleGroupItem } from '@/components/ui/toggle-group';
 import { cn } from "@/lib/utils";
 import { KeyEventState, useKeyEvent } from "@/stores/key_event";
 import { KeyStyleState, useKeyStyle } from "@/stores/key_style";
-import { ArrowHorizontalIcon, ArrowVerticalIcon, FilterHorizontalIcon, FilterIcon, LayerIcon, ToggleOnIcon } from "@hugeicons/core-free-icons";
+import { ArrowHorizontalIcon, ArrowVerticalIcon, FilterHorizontalIcon, FilterIcon, LayerIcon, RepeatIcon, ToggleOnIcon } from "@hugeicons/core-free-icons";
 import { HugeiconsIcon } from "@hugeicons/react";
 import { CustomFilter } from '../custom-filter';
 
@@ -28,6 +28,7 @@ export const GeneralSettings = () => {
         allowedKeys,
         showEventHistory, setShowEventHistory,
         maxHistory, setMaxHistory,
+        showKeyRepeats, setShowKeyRepeats,
         toggleShortcut, setToggleShortcut
     } = useKeyEvent();
 
@@ -128,6 +129,23 @@ export const GeneralSettings = () => {
             </Item>
         </div>
 
+        <Item variant="muted">
+            <ItemContent>
+                <ItemTitle>
+                    <HugeiconsIcon icon={RepeatIcon} size="1em" /> Key Repeat
+                </ItemTitle>
+                <ItemDescription>
+                    Count auto-repeated presses of a held key instead of ignoring them
+                </ItemDescription>
+            </ItemContent>
+            <ItemActions>
+                <Switch checked={showKeyRepeats} onCheckedChange={value => {
+                    setShowKeyRepeats(value);
+                    invoke('set_show_key_repeats', { show: value });
+                }} />
+            </ItemActions>
+        </Item>
+
         <Item variant="muted">
             <ItemHeader className="flex-col items-start">
                 <ItemTitle>
@@ -138,7 +156,7 @@ export const GeneralSettings = () => {
                 </ItemDescription>
             </ItemHeader>
             <ItemContent>
-                <ShortcutRecorder value={toggleShortcut} onChange={shortcut => {
+                <ShortcutRecorder value={typeof toggleShortcut === "string" ? [toggleShortcut] : toggleShortcut} onChange={shortcut => {
                     setToggleShortcut(shortcut);
                     invoke('set_toggle_shortcut', { shortcut });
                 }} />
@@ -0,0 +1,55 @@
+import { useKeyEvent } from "@/stores/key_event";
+import { useKeyStyle } from "@/stores/key_style";
+import { EraserIcon } from "lucide-react";
+
+export const TouchOverlay = () => {
+    const showMouseEvents = useKeyEvent(state => state.showMouseEvents);
+    const touches = useKeyEvent(state => state.touches);
+    const pen = useKeyEvent(state => state.pen);
+    const style = useKeyStyle(state => state.mouse);
+
+    if (!showMouseEvents) return null;
+
+    const touchSize = style.size * 0.4;
+    // pen marker grows with pressure
+    const penSize = pen ? touchSize * (0.3 + 0.7 * pen.pressure) : 0;
+    const showPen = pen && (pen.contact || pen.barrel_buttons !== 0);
+
+    return (
+        <div className="absolute top-0 left-0 w-full h-full pointer-events-none overflow-hidden">
+            {Object.entries(touches).map(([id, { x, y }]) => (
+                <div
+                    key={id}
+                    className="absolute top-0 left-0 rounded-full"
+                    style={{
+                        width: touchSize,
+                        height: touchSize,
+                        transform: `translate3d(${x}px, ${y}px, 0) translate(-50%, -50%)`,
+                        backgroundColor: style.color,
+                        opacity: 0.6,
+                    }}
+                />
+            ))}
+
+            {pen && showPen && (
+                <div
+                    className="absolute top-0 left-0 flex items-center justify-center rounded-full"
+                    style={{
+                        width: penSize,
+                        height: penSize,
+                        transform: `translate3d(${pen.x}px, ${pen.y}px, 0) translate(-50%, -50%)`,
+                        border: `${Math.max(2, touchSize / 20)}px solid ${style.color}`,
+                    }}
+                >
+                    {pen.eraser && <EraserIcon size="60%" color={style.color} />}
+                    {/* one dot per held barrel button */}
+                    <div className="absolute -right-3 top-0 flex flex-col gap-1">
+                        {[0, 1].filter(bit => pen.barrel_buttons & (1 << bit)).map(bit => (
+                            <div key={bit} className="size-2 rounded-full" style={{ backgroundColor: style.color }} />
+                        ))}
+                    </div>
+                </div>
+            )}
+        </div>
+    );
+};
@@ -1,6 +1,6 @@
 import { platform } from "@tauri-apps/plugin-os";
 import { MouseLeftClickIcon, MouseMiddleClickIcon, MouseRightClickIcon, MouseRightDragIcon, MouseScrollDownIcon, MouseScrollUpIcon, ReturnIcon } from "@/components/ui/icons";
-import { ArrowBigUpDashIcon, ArrowBigUpIcon, ArrowDownIcon, ArrowDownToLineIcon, ArrowLeftIcon, ArrowLeftRightIcon, ArrowRightIcon, ArrowRightToLineIcon, ArrowUpIcon, ArrowUpToLineIcon, ChevronUpIcon, CircleArrowOutUpLeftIcon, CommandIcon, DeleteIcon, Grid2X2Icon, ImageIcon, LockIcon, LucideIcon, MouseIcon, MoveDownRightIcon, MoveUpLeftIcon, OptionIcon, PauseIcon, SpaceIcon, SparkleIcon, Volume2Icon, VolumeXIcon } from "lucide-react";
+import { ArrowBigUpDashIcon, ArrowBigUpIcon, ArrowDownIcon, ArrowDownToLineIcon, ArrowLeftIcon, ArrowLeftRightIcon, ArrowRightIcon, ArrowRightToLineIcon, ArrowUpIcon, ArrowUpToLineIcon, ChevronUpIcon, CircleArrowOutUpLeftIcon, CommandIcon, DeleteIcon, Grid2X2Icon, HandIcon, ImageIcon, LockIcon, LucideIcon, MouseIcon, MoveDownRightIcon, MoveUpLeftIcon, Minimize2Icon, OptionIcon, PauseIcon, PointerIcon, SpaceIcon, SparkleIcon, Volume2Icon, VolumeXIcon } from "lucide-react";
 
 // ───────────── Platform Logic ─────────────
 const currentPlatform = platform();
@@ -183,6 +183,12 @@ export const keymaps: Record<string, DisplayData> = {
         icon: CircleArrowOutUpLeftIcon,
         category: "special",
     },
+    Redacted: {
+        label: "redacted",
+        glyph: "•••",
+        icon: LockIcon,
+        category: "special",
+    },
 
     // ───────────── Digits ──────────────
     Num1: {
@@ -437,6 +443,21 @@ export const keymaps: Record<string, DisplayData> = {
         icon: MouseScrollDownIcon,
         category: "mouse",
     },
+    Swipe: {
+        label: "swipe",
+        icon: HandIcon,
+        category: "mouse",
+    },
+    Pinch: {
+        label: "pinch",
+        icon: Minimize2Icon,
+        category: "mouse",
+    },
+    Hold: {
+        label: "hold",
+        icon: PointerIcon,
+        category: "mouse",
+    },
 };
 
 // ───────────── Apply Mappings for Modifiers ─────────────
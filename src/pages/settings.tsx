@@ -1,4 +1,5 @@
-import { useState } from "react";
+import { useEffect, useState } from "react";
+import { listen } from "@tauri-apps/api/event";
 
 import { AboutPage, AppearanceSettings, GeneralSettings, KeycapSettings, MouseSettings } from "@/components/settings";
 import { VERSION } from "@/components/settings/about";
@@ -18,6 +19,12 @@ const sideBar = [
 const Settings = () => {
     const [activeTab, setActiveTab] = useState(sideBar[0].title);
 
+    // switching profiles rewrites the persisted stores from the backend
+    useEffect(() => {
+        const unlisten = listen<string>("profile-changed", () => window.location.reload());
+        return () => { unlisten.then((f) => f()); };
+    }, []);
+
     return (
         <div className="flex w-screen h-screen overflow-hidden border-t bg-background">
             <div className="w-44 p-2 flex flex-col gap-y-1 rounded-xl">
@@ -1,5 +1,9 @@
 import { KeyOverlay } from "@/components/key-overlay";
+import { LayoutIndicator } from "@/components/layout-indicator";
+import { LockBadge } from "@/components/lock-badge";
 import { MouseOverlay } from "@/components/mouse-overlay";
+import { PrivacyBadge } from "@/components/privacy-badge";
+import { TouchOverlay } from "@/components/touch-overlay";
 import { KEY_EVENT_STORE, KeyEventStore, useKeyEvent } from "@/stores/key_event";
 import { KEY_STYLE_STORE, KeyStyleStore, useKeyStyle } from '@/stores/key_style';
 import { listenForUpdates } from '@/stores/sync';
@@ -16,6 +20,8 @@ export function Visualization() {
 
   // listening for input events
   const [isListening, setIsListening] = useState(true);
+  // events held back while a secret may be typed
+  const [privacyPaused, setPrivacyPaused] = useState(false);
 
   useEffect(() => {
     const unlistenPromises = [
@@ -30,8 +36,16 @@ export function Visualization() {
       }),
       // ───────────── listener toggle ─────────────
       listen<boolean>("listening-toggle", (event) => setIsListening(event.payload)),
+      // ───────────── privacy guard ─────────────
+      listen<boolean>("privacy-paused", (event) => setPrivacyPaused(event.payload)),
+      // ───────────── clear history hotkey ─────────────
+      listen("clear-history", () => useKeyEvent.setState({ groups: [] })),
     ];
     const id = setInterval(tick, 250);
+    // lock badge needs the state before the first lock key press
+    invoke("request_lock_state");
+    invoke<{ window: boolean; grab: boolean }>("get_privacy_pause")
+      .then((pause) => setPrivacyPaused(pause.window || pause.grab));
 
     return () => {
       clearInterval(id);
@@ -56,6 +70,10 @@ export function Visualization() {
 
   return <div className="w-screen h-screen relative overflow-hidden">
     <MouseOverlay />
+    <TouchOverlay />
     <KeyOverlay />
+    <LockBadge />
+    <LayoutIndicator />
+    {privacyPaused && <PrivacyBadge />}
   </div>;
 }
\ No newline at end of file
@@ -1,4 +1,4 @@
-import { EventPayload, KeyEvent, MappedKeys, MODIFIERS, MouseButton, MouseButtonEvent, MouseMoveEvent, MouseWheelEvent, RawKey, RawKeyEvent } from "@/types/event";
+import { EventPayload, GestureEvent, KeyEvent, LockStateEvent, MappedKeys, MODIFIERS, MouseButton, MouseButtonEvent, MouseMoveEvent, MouseWheelEvent, PenEvent, RawKey, RawKeyEvent } from "@/types/event";
 import { getCurrentWindow } from "@tauri-apps/api/window";
 import { createJSONStorage, persist } from "zustand/middleware";
 import { tauriStorage } from "./storage";
@@ -7,6 +7,7 @@ import { createSyncedStore } from "./sync";
 
 export const KEY_EVENT_STORE = "key_event_store";
 const SCROLL_LINGER_MS = 300;
+const LAYOUT_LINGER_MS = 1_500;
 
 interface KeyGroup {
     keys: KeyEvent[];
@@ -17,6 +18,10 @@ export interface KeyEventState {
     // ───────────── physical state ─────────────
     pressedKeys: string[];
     pressedMouseButton: MouseButton | null;
+    lockState: Omit<LockStateEvent, "type">;
+    layoutChange: { name: string; changedAt: number; } | null;
+    touches: Record<number, { x: number; y: number; }>;
+    pen: Omit<PenEvent, "type"> | null;
     mouse: {
         x: number;
         y: number;
@@ -36,7 +41,9 @@ export interface KeyEventState {
     maxHistory: number;
     showMouseEvents: boolean;
     lingerDurationMs: number;
-    toggleShortcut: string[];
+    showKeyRepeats: boolean;
+    // one chord as its keys, a sequence like "Control+KeyK, Control+KeyV" as text
+    toggleShortcut: string[] | string;
 }
 
 interface KeyEventActions {
@@ -48,16 +55,19 @@ interface KeyEventActions {
     setMaxHistory(value: KeyEventState["maxHistory"]): void;
     setShowMouseEvents(value: KeyEventState["showMouseEvents"]): void;
     setLingerDurationMs(value: KeyEventState["lingerDurationMs"]): void;
+    setShowKeyRepeats(value: KeyEventState["showKeyRepeats"]): void;
     setToggleShortcut(value: KeyEventState["toggleShortcut"]): void;
     // ───────────── event actions ─────────────
     onEvent(event: EventPayload): void;
     onKeyPress(event: RawKeyEvent): void;
+    onKeyRepeat(event: RawKeyEvent): void;
     ignoreEvent(event: RawKeyEvent, pressedKeys: string[]): boolean;
     onKeyRelease(event: RawKeyEvent): void;
     onMouseMove(event: MouseMoveEvent): void;
     onMouseButtonPress(event: MouseButtonEvent): void;
     onMouseButtonRelease(event: MouseButtonEvent): void;
     onMouseWheel(event: MouseWheelEvent): void;
+    onGesture(event: GestureEvent): void;
     tick(): void;
 }
 
@@ -68,6 +78,10 @@ const createKeyEventStore = createSyncedStore<KeyEventStore>(
     (set, get) => ({
         pressedKeys: <string[]>[],
         pressedMouseButton: null,
+        lockState: { caps_lock: false, num_lock: false, scroll_lock: false },
+        layoutChange: null,
+        touches: {},
+        pen: null,
         mouse: { x: 0, y: 0, wheel: 0, dragging: false },
         groups: <KeyGroup[]>[],
         listening: true,
@@ -83,6 +97,7 @@ const createKeyEventStore = createSyncedStore<KeyEventStore>(
         maxHistory: 5,
         lingerDurationMs: 5_000,
         showMouseEvents: true,
+        showKeyRepeats: false,
         toggleShortcut: [RawKey.ShiftLeft, RawKey.F10],
 
         setDragThreshold(value: number) {
@@ -106,6 +121,9 @@ const createKeyEventStore = createSyncedStore<KeyEventStore>(
         setLingerDurationMs(value: number) {
             set({ lingerDurationMs: value });
         },
+        setShowKeyRepeats(value: boolean) {
+            set({ showKeyRepeats: value });
+        },
         setToggleShortcut(value: string[]) {
             set({ toggleShortcut: value });
         },
@@ -114,7 +132,9 @@ const createKeyEventStore = createSyncedStore<KeyEventStore>(
             switch (event.type) {
                 case "KeyEvent":
                     if (!MappedKeys.has(event.name)) return;
-                    if (event.pressed) {
+                    if (event.pressed && event.repeat) {
+                        state.onKeyRepeat(event);
+                    } else if (event.pressed) {
                         state.onKeyPress(event);
                     } else {
                         state.onKeyRelease(event);
@@ -136,6 +156,37 @@ const createKeyEventStore = createSyncedStore<KeyEventStore>(
                 case "MouseWheelEvent":
                     state.onMouseWheel(event);
                     break;
+
+                case "LockStateEvent": {
+                    const { type, ...lockState } = event;
+                    set({ lockState });
+                    break;
+                }
+
+                case "LayoutChangeEvent":
+                    set({ layoutChange: { name: event.name, changedAt: Date.now() } });
+                    break;
+
+                case "GestureEvent":
+                    state.onGesture(event);
+                    break;
+
+                case "TouchEvent": {
+                    const touches = { ...state.touches };
+                    if (event.phase === "Up") {
+                        delete touches[event.id];
+                    } else {
+                        touches[event.id] = { x: event.x, y: event.y };
+                    }
+                    set({ touches });
+                    break;
+                }
+
+                case "PenEvent": {
+                    const { type, ...pen } = event;
+                    set({ pen });
+                    break;
+                }
             }
         },
         onKeyPress(event: RawKeyEvent) {
@@ -214,6 +265,17 @@ const createKeyEventStore = createSyncedStore<KeyEventStore>(
 
             set({ pressedKeys, groups });
         },
+        onKeyRepeat(event: RawKeyEvent) {
+            const state = get();
+            const groups = [...state.groups];
+            const last = groups.length - 1;
+            // bump the press count of the held key, if it's shown
+            const existingKey = last >= 0 ? groups[last].keys.find(gKey => gKey.name === event.name) : undefined;
+            if (existingKey) {
+                existingKey.press();
+                set({ groups });
+            }
+        },
         ignoreEvent(event, pressedKeys) {
             const state = get();
             if (state.filter === "modifiers") {
@@ -335,6 +397,17 @@ const createKeyEventStore = createSyncedStore<KeyEventStore>(
 
             set({ mouse });
         },
+        onGesture(event: GestureEvent) {
+            const state = get();
+            if (!state.showMouseEvents) return;
+            // simulate gesture as a key held from begin to end
+            const raw_key = RawKey[event.gesture];
+            if (event.phase === "Begin" && !state.pressedKeys.includes(raw_key)) {
+                state.onKeyPress({ type: "KeyEvent", name: raw_key, pressed: true });
+            } else if (event.phase === "End" || event.phase === "Cancel") {
+                state.onKeyRelease({ type: "KeyEvent", name: raw_key, pressed: false });
+            }
+        },
         tick() {
             // todo: remove pressed keys with unsually long linger duration
             const state = get();
@@ -350,6 +423,11 @@ const createKeyEventStore = createSyncedStore<KeyEventStore>(
                 set({ mouse: { ...state.mouse, wheel: 0, lastScrollAt: undefined } });
             }
 
+            // hide layout name after a moment
+            if (state.layoutChange && now - state.layoutChange.changedAt > LAYOUT_LINGER_MS) {
+                set({ layoutChange: null });
+            }
+
             // don't remove keys while styling
             if (state.settingsOpen) return;
 
@@ -381,7 +459,7 @@ const createKeyEventStore = createSyncedStore<KeyEventStore>(
         name: KEY_EVENT_STORE,
         storage: createJSONStorage(() => tauriStorage),
         partialize: (state) => {
-            const { pressedKeys, pressedMouseButton, mouse, groups, settingsOpen, ...persistedState } = state;
+            const { pressedKeys, pressedMouseButton, lockState, layoutChange, touches, pen, mouse, groups, settingsOpen, ...persistedState } = state;
             return persistedState;
         },
     }),
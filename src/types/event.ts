@@ -2,12 +2,19 @@ export type EventPayload =
   | RawKeyEvent
   | MouseButtonEvent
   | MouseMoveEvent
-  | MouseWheelEvent;
+  | MouseWheelEvent
+  | LockStateEvent
+  | LayoutChangeEvent
+  | GestureEvent
+  | TouchEvent
+  | PenEvent;
 
 export interface RawKeyEvent {
   type: "KeyEvent";
   pressed: boolean;
   name: string;
+  // auto-repeat counter, 0 for a fresh press
+  repeat?: number;
 }
 
 export interface MouseButtonEvent {
@@ -28,6 +35,52 @@ export interface MouseWheelEvent {
   delta_y: number;
 }
 
+export interface LockStateEvent {
+  type: "LockStateEvent";
+  caps_lock: boolean;
+  num_lock: boolean;
+  scroll_lock: boolean;
+}
+
+export interface LayoutChangeEvent {
+  type: "LayoutChangeEvent";
+  name: string;
+}
+
+export interface GestureEvent {
+  type: "GestureEvent";
+  gesture: "Swipe" | "Pinch" | "Hold";
+  phase: "Begin" | "Update" | "End" | "Cancel";
+  fingers: number;
+  delta_x: number;
+  delta_y: number;
+  // pinch scale relative to the start, 1.0 for swipes
+  scale: number;
+}
+
+export interface TouchEvent {
+  type: "TouchEvent";
+  id: number;
+  phase: "Down" | "Move" | "Up";
+  x: number;
+  y: number;
+}
+
+export interface PenEvent {
+  type: "PenEvent";
+  x: number;
+  y: number;
+  // 0 to 1
+  pressure: number;
+  // -1 to 1, 0 when upright
+  tilt_x: number;
+  tilt_y: number;
+  contact: boolean;
+  eraser: boolean;
+  // bitmask, lowest bit is the first barrel button
+  barrel_buttons: number;
+}
+
 export type MouseButton =
   | "Left"
   | "Right"
@@ -180,6 +233,14 @@ export const RawKey = {
   Drag: "Drag",
   ScrollUp: "ScrollUp",
   ScrollDown: "ScrollDown",
+
+  // ───────────── Touchpad (Virtual) ─────────────
+  Swipe: "Swipe",
+  Pinch: "Pinch",
+  Hold: "Hold",
+
+  // ───────────── Redaction (Virtual) ─────────────
+  Redacted: "Redacted",
 } as const;
 
 export type RawKeyValue = typeof RawKey[keyof typeof RawKey];